@@ -59,6 +59,7 @@ fn txexe_benchmark(c: &mut Criterion) {
         epoch_height: 0,
         pos_view: None,
         finalized_epoch: None,
+        finalized_epoch_hash: None,
         transaction_epoch_bound: TRANSACTION_DEFAULT_EPOCH_BOUND,
     };
     let mut group = c.benchmark_group("Execute 1 transaction");
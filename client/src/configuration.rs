@@ -35,7 +35,7 @@ use cfxcore::{
     spec::CommonParams,
     sync::{ProtocolConfiguration, StateSyncConfiguration, SyncGraphConfig},
     sync_parameters::*,
-    transaction_pool::TxPoolConfig,
+    transaction_pool::{GcStrategy, TxPoolConfig},
     NodeType,
 };
 use diem_types::term_state::{
@@ -145,6 +145,7 @@ build_config! {
         (unnamed_21autumn_transition_height, (Option<u64>), None)
         (unnamed_21autumn_cip43_init_end, (Option<u64>), None)
         (cip78_patch_transition_number,(Option<u64>),None)
+        (future_internal_contracts_transition_number, (Option<u64>), None)
         (referee_bound, (usize), REFEREE_DEFAULT_BOUND)
         (timer_chain_beta, (u64), TIMER_CHAIN_DEFAULT_BETA)
         (timer_chain_block_difficulty_ratio, (u64), TIMER_CHAIN_BLOCK_DEFAULT_DIFFICULTY_RATIO)
@@ -245,6 +246,22 @@ build_config! {
         (tx_pool_min_tx_gas_price, (u64), 1)
         (tx_weight_scaling, (u64), 1)
         (tx_weight_exp, (u8), 1)
+        (tx_weight_aging_factor, (u64), 0)
+        (tx_pool_packed_fee_window, (usize), 1000)
+        (tx_pool_forced_insert_overflow_margin, (usize), 0)
+        (tx_pool_min_balance_reserve, (u64), 0)
+        (tx_pool_strict_balance_margin, (Option<u64>), None)
+        (tx_pool_local_reserved_slots, (usize), 0)
+        (tx_pool_max_txs_per_sender, (Option<usize>), None)
+        (tx_pool_memory_capacity, (Option<usize>), None)
+        (tx_pool_furthest_future_nonce_offset, (u32), 2000)
+        (tx_pool_min_replace_bump_percent, (u64), 0)
+        (tx_pool_gc_check_count, (usize), 5)
+        (tx_pool_gc_strategy, (String), "lowest_gas_price".into())
+        (tx_pool_gc_thrashing_window, (usize), 0)
+        (tx_pool_gc_thrashing_threshold_percent, (u64), 90)
+        (tx_pool_max_single_tx_gas_percent, (u64), 100)
+        (tx_pool_min_gas_price, (u64), 0)
 
         // Storage Section.
         (additional_maintained_snapshot_count, (u32), 1)
@@ -919,7 +936,45 @@ impl Configuration {
             min_tx_price: self.raw_conf.tx_pool_min_tx_gas_price,
             tx_weight_scaling: self.raw_conf.tx_weight_scaling,
             tx_weight_exp: self.raw_conf.tx_weight_exp,
+            tx_weight_aging_factor: self.raw_conf.tx_weight_aging_factor,
             target_block_gas_limit: self.raw_conf.target_block_gas_limit,
+            packed_fee_window: self.raw_conf.tx_pool_packed_fee_window,
+            forced_insert_overflow_margin: self
+                .raw_conf
+                .tx_pool_forced_insert_overflow_margin,
+            min_balance_reserve: U256::from(
+                self.raw_conf.tx_pool_min_balance_reserve,
+            ),
+            strict_balance_margin: self
+                .raw_conf
+                .tx_pool_strict_balance_margin
+                .map(U256::from),
+            local_reserved_slots: self.raw_conf.tx_pool_local_reserved_slots,
+            max_txs_per_sender: self
+                .raw_conf
+                .tx_pool_max_txs_per_sender
+                .unwrap_or(usize::max_value()),
+            memory_capacity: self.raw_conf.tx_pool_memory_capacity,
+            furthest_future_nonce_offset: self
+                .raw_conf
+                .tx_pool_furthest_future_nonce_offset,
+            min_replace_bump_percent: self
+                .raw_conf
+                .tx_pool_min_replace_bump_percent,
+            gc_check_count: self.raw_conf.tx_pool_gc_check_count,
+            gc_strategy: match self.raw_conf.tx_pool_gc_strategy.as_str() {
+                "lowest_gas_price" => GcStrategy::LowestGasPrice,
+                "oldest_timestamp" => GcStrategy::OldestTimestamp,
+                _ => panic!("Invalid tx_pool_gc_strategy parameter!"),
+            },
+            gc_thrashing_window: self.raw_conf.tx_pool_gc_thrashing_window,
+            gc_thrashing_threshold_percent: self
+                .raw_conf
+                .tx_pool_gc_thrashing_threshold_percent,
+            max_single_tx_gas_percent: self
+                .raw_conf
+                .tx_pool_max_single_tx_gas_percent,
+            min_gas_price: self.raw_conf.tx_pool_min_gas_price,
         }
     }
 
@@ -1137,6 +1192,14 @@ impl Configuration {
             .raw_conf
             .unnamed_21autumn_transition_height
             .unwrap_or(default_transition_time);
+        params.transition_numbers.pos_stake_query = self
+            .raw_conf
+            .future_internal_contracts_transition_number
+            .unwrap_or(default_transition_time);
+        params.transition_numbers.pivot_decision_query = self
+            .raw_conf
+            .future_internal_contracts_transition_number
+            .unwrap_or(default_transition_time);
 
         let mut base_block_rewards = BTreeMap::new();
         base_block_rewards.insert(0, INITIAL_BASE_MINING_REWARD_IN_UCFX.into());
@@ -480,7 +480,7 @@ impl RpcImpl {
             bail!(request_rejected_in_catch_up_mode(None));
         }
         let (signed_trans, failed_trans) =
-            self.tx_pool.insert_new_transactions(vec![tx]);
+            self.tx_pool.insert_new_local_transactions(vec![tx]);
         // FIXME: how is it possible?
         if signed_trans.len() + failed_trans.len() > 1 {
             // This should never happen
@@ -1615,6 +1615,8 @@ impl TestRpc for TestRpcImpl {
             fn pos_force_vote_proposal(&self, block_id: H256) -> JsonRpcResult<()>;
             fn pos_force_propose(&self, round: U64, parent_block_id: H256, payload: Vec<TransactionPayload>) -> JsonRpcResult<()>;
             fn pos_trigger_timeout(&self, timeout_type: String) -> JsonRpcResult<()>;
+            fn pos_force_timeout(&self, round: U64) -> JsonRpcResult<()>;
+            fn pos_drop_vote(&self, round: U64) -> JsonRpcResult<()>;
             fn pos_force_sign_pivot_decision(&self, block_hash: H256, height: U64) -> JsonRpcResult<()>;
             fn pos_get_chosen_proposal(&self) -> JsonRpcResult<Option<PosBlock>>;
         }
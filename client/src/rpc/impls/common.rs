@@ -741,6 +741,29 @@ impl RpcImpl {
         })
     }
 
+    pub fn pos_force_timeout(&self, round: U64) -> RpcResult<()> {
+        if !self.network.is_test_mode() {
+            // Reject force vote if test RPCs are enabled in a mainnet node,
+            // because this may cause staked CFXs locked
+            // permanently.
+            bail!(RpcError::internal_error())
+        }
+        self.pos_handler.force_timeout(round).map_err(|e| {
+            warn!("pos_force_timeout: err={:?}", e);
+            RpcError::internal_error().into()
+        })
+    }
+
+    pub fn pos_drop_vote(&self, round: U64) -> RpcResult<()> {
+        if !self.network.is_test_mode() {
+            bail!(RpcError::internal_error())
+        }
+        self.pos_handler.drop_vote(round).map_err(|e| {
+            warn!("pos_drop_vote: err={:?}", e);
+            RpcError::internal_error().into()
+        })
+    }
+
     pub fn pos_force_sign_pivot_decision(
         &self, block_hash: H256, height: U64,
     ) -> RpcResult<()> {
@@ -997,14 +1020,13 @@ impl RpcImpl {
     }
 
     pub fn txpool_status(&self) -> JsonRpcResult<TxPoolStatus> {
-        let (ready_len, deferred_len, received_len, unexecuted_len) =
-            self.tx_pool.stats();
+        let stats = self.tx_pool.stats();
 
         Ok(TxPoolStatus {
-            deferred: U64::from(deferred_len),
-            ready: U64::from(ready_len),
-            received: U64::from(received_len),
-            unexecuted: U64::from(unexecuted_len),
+            deferred: U64::from(stats.total_deferred),
+            ready: U64::from(stats.total_ready_accounts),
+            received: U64::from(stats.total_received),
+            unexecuted: U64::from(stats.total_unpacked),
         })
     }
 
@@ -1147,6 +1147,8 @@ impl TestRpc for TestRpcImpl {
             fn pos_force_vote_proposal(&self, block_id: H256) -> JsonRpcResult<()>;
             fn pos_force_propose(&self, round: U64, parent_block_id: H256, payload: Vec<TransactionPayload>) -> JsonRpcResult<()>;
             fn pos_trigger_timeout(&self, timeout_type: String) -> JsonRpcResult<()>;
+            fn pos_force_timeout(&self, round: U64) -> JsonRpcResult<()>;
+            fn pos_drop_vote(&self, round: U64) -> JsonRpcResult<()>;
             fn pos_force_sign_pivot_decision(&self, block_hash: H256, height: U64) -> JsonRpcResult<()>;
             fn pos_get_chosen_proposal(&self) -> JsonRpcResult<Option<PosBlock>>;
         }
@@ -143,6 +143,12 @@ pub trait TestRpc {
     #[rpc(name = "pos_trigger_timeout")]
     fn pos_trigger_timeout(&self, timeout_type: String) -> RpcResult<()>;
 
+    #[rpc(name = "pos_force_timeout")]
+    fn pos_force_timeout(&self, round: U64) -> RpcResult<()>;
+
+    #[rpc(name = "pos_drop_vote")]
+    fn pos_drop_vote(&self, round: U64) -> RpcResult<()>;
+
     #[rpc(name = "pos_force_sign_pivot_decision")]
     fn pos_force_sign_pivot_decision(
         &self, block_hash: H256, height: U64,
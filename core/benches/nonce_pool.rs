@@ -0,0 +1,105 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+//! Compare the compact and the treap representations of `NoncePool` over
+//! senders of different sizes. Run with
+//! `cargo bench -p cfxcore --features testonly_code --bench nonce_pool`.
+
+use cfx_types::{Address, U256};
+use cfxcore::transaction_pool::{NoncePool, TxWithReadyInfo};
+use cfxkey::{Generator, Random};
+use criterion::{
+    black_box, criterion_group, criterion_main, BatchSize, BenchmarkId,
+    Criterion,
+};
+use primitives::{Action, Transaction};
+use std::sync::Arc;
+
+/// The sizes of the senders, around `COMPACT_NONCE_POOL_MAX_SIZE`.
+const SIZES: &[usize] = &[2, 4, 8, 16, 32, 64, 256];
+
+/// A contiguous chain of `size` transactions of one sender from nonce 0.
+fn new_chain(size: usize) -> Vec<TxWithReadyInfo> {
+    let sender = Random.generate().unwrap();
+    (0..size)
+        .map(|nonce| TxWithReadyInfo {
+            transaction: Arc::new(
+                Transaction {
+                    nonce: nonce.into(),
+                    gas_price: 10.into(),
+                    gas: 50_000.into(),
+                    action: Action::Call(Address::random()),
+                    value: 10_000.into(),
+                    storage_limit: 0,
+                    epoch_height: 0,
+                    chain_id: 0,
+                    data: Vec::new(),
+                }
+                .sign(sender.secret()),
+            ),
+            packed: false,
+            sponsored_gas: 0.into(),
+            sponsored_storage: 0,
+        })
+        .collect()
+}
+
+/// The lookups done for a sender whenever its readiness is recalculated.
+fn bench_lookup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nonce_pool_lookup");
+    for size in SIZES {
+        let txs = new_chain(*size);
+        for (name, compact) in &[("compact", true), ("treap", false)] {
+            let nonce_pool =
+                NoncePool::with_representation(txs.clone(), *compact);
+            group.bench_with_input(
+                BenchmarkId::new(*name, size),
+                size,
+                |b, size| {
+                    let mut round = 0;
+                    b.iter(|| {
+                        let nonce = U256::from(round % size);
+                        round += 1;
+                        black_box(nonce_pool.count_from(&nonce));
+                        black_box(nonce_pool.get_tx_by_nonce(nonce));
+                        black_box(
+                            nonce_pool.recalculate_readiness_with_local_info(
+                                nonce,
+                                U256::max_value(),
+                            ),
+                        );
+                    })
+                },
+            );
+        }
+    }
+    group.finish();
+}
+
+/// Remove the transaction with the lowest nonce, as when it is packed or
+/// collected. A treap shrinking to the demote size is turned into a vector,
+/// which is included in its numbers.
+fn bench_remove_lowest(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nonce_pool_remove_lowest");
+    for size in SIZES {
+        let txs = new_chain(*size);
+        for (name, compact) in &[("compact", true), ("treap", false)] {
+            group.bench_function(BenchmarkId::new(*name, size), |b| {
+                b.iter_batched(
+                    || NoncePool::with_representation(txs.clone(), *compact),
+                    // Return the pool so that dropping it is not measured.
+                    |mut nonce_pool| {
+                        let removed = nonce_pool.remove_lowest_nonce();
+                        (nonce_pool, removed)
+                    },
+                    BatchSize::SmallInput,
+                )
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_lookup, bench_remove_lowest);
+criterion_main!(benches);
@@ -18,4 +18,8 @@ lazy_static! {
         Address::from_str("0888000000000000000000000000000000000004").unwrap();
     pub static ref POS_REGISTER_CONTRACT_ADDRESS: Address =
         Address::from_str("0888000000000000000000000000000000000005").unwrap();
+    pub static ref POS_STAKE_QUERY_CONTRACT_ADDRESS: Address =
+        Address::from_str("0888000000000000000000000000000000000006").unwrap();
+    pub static ref PIVOT_DECISION_QUERY_CONTRACT_ADDRESS: Address =
+        Address::from_str("0888000000000000000000000000000000000007").unwrap();
 }
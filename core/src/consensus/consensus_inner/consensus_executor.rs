@@ -1211,10 +1211,10 @@ impl ConsensusExecutionHandler {
                 .and_then(|header| header.pos_reference().as_ref());
             let pos_view_number =
                 pos_id.and_then(|id| self.pos_verifier.get_pos_view(id));
-            let pivot_decision_epoch = pos_id
+            let pivot_decision = pos_id
                 .and_then(|id| self.pos_verifier.get_pivot_decision(id))
                 .and_then(|hash| self.data_man.block_header_by_hash(&hash))
-                .map(|header| header.height());
+                .map(|header| (header.hash(), header.height()));
 
             let mut env = Env {
                 number: block_number,
@@ -1226,7 +1226,8 @@ impl ConsensusExecutionHandler {
                 gas_limit: U256::from(block.block_header.gas_limit()),
                 epoch_height: pivot_block.block_header.height(),
                 pos_view: pos_view_number,
-                finalized_epoch: pivot_decision_epoch,
+                finalized_epoch: pivot_decision.map(|(_, height)| height),
+                finalized_epoch_hash: pivot_decision.map(|(hash, _)| hash),
                 transaction_epoch_bound: self
                     .verification_config
                     .transaction_epoch_bound,
@@ -1820,10 +1821,10 @@ impl ConsensusExecutionHandler {
         let pos_id = best_block_header.pos_reference().as_ref();
         let pos_view_number =
             pos_id.and_then(|id| self.pos_verifier.get_pos_view(id));
-        let pivot_decision_epoch = pos_id
+        let pivot_decision = pos_id
             .and_then(|id| self.pos_verifier.get_pivot_decision(id))
             .and_then(|hash| self.data_man.block_header_by_hash(&hash))
-            .map(|header| header.height());
+            .map(|header| (header.hash(), header.height()));
 
         let start_block_number = match self.data_man.get_epoch_execution_context(epoch_id) {
             Some(v) => v.start_block_number + epoch_size as u64,
@@ -1882,7 +1883,8 @@ impl ConsensusExecutionHandler {
             gas_limit: tx.gas.clone(),
             epoch_height: block_height,
             pos_view: pos_view_number,
-            finalized_epoch: pivot_decision_epoch,
+            finalized_epoch: pivot_decision.map(|(_, height)| height),
+            finalized_epoch_hash: pivot_decision.map(|(hash, _)| hash),
             transaction_epoch_bound: self
                 .verification_config
                 .transaction_epoch_bound,
@@ -420,6 +420,28 @@ impl PosHandler {
             .map_err(|e| anyhow::anyhow!("try_send: err={:?}", e))
     }
 
+    pub fn force_timeout(&self, round: U64) -> anyhow::Result<()> {
+        self.test_command_sender
+            .lock()
+            .as_mut()
+            .ok_or(anyhow::anyhow!("Pos not initialized!"))?
+            .try_send(TestCommand::ForceTimeout {
+                round: round.as_u64(),
+            })
+            .map_err(|e| anyhow::anyhow!("try_send: err={:?}", e))
+    }
+
+    pub fn drop_vote(&self, round: U64) -> anyhow::Result<()> {
+        self.test_command_sender
+            .lock()
+            .as_mut()
+            .ok_or(anyhow::anyhow!("Pos not initialized!"))?
+            .try_send(TestCommand::DropVote {
+                round: round.as_u64(),
+            })
+            .map_err(|e| anyhow::anyhow!("try_send: err={:?}", e))
+    }
+
     pub fn force_sign_pivot_decision(
         &self, pivot_decision: PivotBlockDecision,
     ) -> anyhow::Result<()> {
@@ -497,6 +497,7 @@ mod tests {
             epoch_height: 0,
             pos_view: None,
             finalized_epoch: None,
+            finalized_epoch_hash: None,
             transaction_epoch_bound: TRANSACTION_DEFAULT_EPOCH_BOUND,
         }
     }
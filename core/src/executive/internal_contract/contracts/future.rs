@@ -1,7 +1,13 @@
 use super::{macros::*, SolFnTable};
-use crate::vm::Spec;
+use crate::{
+    evm::{ActionParams, GasPriceTier, Spec},
+    executive::InternalRefContext,
+    trace::{trace::ExecTrace, Tracer},
+    vm::{self, Env},
+};
 use cfx_parameters::internal_contract_addresses::*;
-use cfx_types::Address;
+use cfx_state::state_trait::StateOpsTrait;
+use cfx_types::{Address, U256};
 
 // Set the internal contract addresses to be activated in the future. So we can
 // update the hardcoded test mode genesis state  without waiting for the
@@ -9,3 +15,157 @@ use cfx_types::Address;
 make_solidity_contract! {
     pub(super) struct AntiReentrancyConfig(ANTI_REENTRANCY_CONTRACT_ADDRESS, "placeholder");
 }
+
+make_solidity_contract! {
+    pub(super) struct PoSStakeQuery(POS_STAKE_QUERY_CONTRACT_ADDRESS, generate_pos_stake_query_fn_table, initialize: |params: &CommonParams| params.transition_numbers.pos_stake_query, is_active: |spec: &Spec| spec.pos_stake_query);
+}
+
+fn generate_pos_stake_query_fn_table() -> SolFnTable {
+    make_function_table!(GetPoSStake)
+}
+
+group_impl_is_active!(|spec: &Spec| spec.pos_stake_query, GetPoSStake);
+
+make_solidity_function! {
+    struct GetPoSStake(Address, "getPoSStake(address)", U256);
+}
+
+// Reads the identifier and the votes of `address` in the PoS register contract.
+impl_function_type!(GetPoSStake, "query", gas: |spec: &Spec| 2 * spec.sload_gas + 2 * spec.sha3_gas);
+
+impl ExecutionTrait for GetPoSStake {
+    fn execute_inner(
+        &self, address: Address, _params: &ActionParams,
+        context: &mut InternalRefContext,
+        _tracer: &mut dyn Tracer<Output = ExecTrace>,
+    ) -> vm::Result<U256>
+    {
+        Ok(context.state.pos_locked_staking(&address)?)
+    }
+}
+
+make_solidity_contract! {
+    pub(super) struct PivotDecisionQuery(PIVOT_DECISION_QUERY_CONTRACT_ADDRESS, generate_pivot_decision_query_fn_table, initialize: |params: &CommonParams| params.transition_numbers.pivot_decision_query, is_active: |spec: &Spec| spec.pivot_decision_query);
+}
+
+fn generate_pivot_decision_query_fn_table() -> SolFnTable {
+    make_function_table!(LatestPivotDecision)
+}
+
+group_impl_is_active!(
+    |spec: &Spec| spec.pivot_decision_query,
+    LatestPivotDecision
+);
+
+make_solidity_function! {
+    struct LatestPivotDecision((), "latestPivotDecision()", (H256, U256));
+}
+
+// same gas cost as the `NUMBER` opcode
+impl_function_type!(LatestPivotDecision, "query", gas: |spec: &Spec| spec.tier_step_gas[(GasPriceTier::Base).idx()]);
+
+impl ExecutionTrait for LatestPivotDecision {
+    fn execute_inner(
+        &self, _input: (), _params: &ActionParams,
+        context: &mut InternalRefContext,
+        _tracer: &mut dyn Tracer<Output = ExecTrace>,
+    ) -> vm::Result<(H256, U256)>
+    {
+        Ok(latest_pivot_decision(context.env))
+    }
+}
+
+/// The hash and the height of the pivot block last committed by PoS, or zeros
+/// before the PoS chain is launched.
+fn latest_pivot_decision(env: &Env) -> (H256, U256) {
+    (
+        env.finalized_epoch_hash.unwrap_or_default(),
+        env.finalized_epoch.unwrap_or(0).into(),
+    )
+}
+
+#[test]
+fn test_pos_stake_query_contract_sig() {
+    check_signature!(GetPoSStake, "629174fa");
+}
+
+#[test]
+fn test_pos_stake_query_input() {
+    use solidity_abi::ABIDecodable;
+
+    let address: Address =
+        "1a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d".parse().unwrap();
+    let input =
+        "0000000000000000000000001a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d"
+            .from_hex::<Vec<u8>>()
+            .unwrap();
+    let decoded =
+        <<GetPoSStake as InterfaceTrait>::Input as ABIDecodable>::abi_decode(
+            &input,
+        )
+        .unwrap();
+    assert_eq!(decoded, address);
+}
+
+#[test]
+fn test_pos_stake_query_activation() {
+    use super::InternalContractMap;
+
+    let mut params = CommonParams::default();
+    params.transition_numbers.pos_stake_query = 10;
+    let contracts = InternalContractMap::new(&params);
+    let address = &*POS_STAKE_QUERY_CONTRACT_ADDRESS;
+    assert!(contracts.initialized_at(10).contains(address));
+
+    let spec = Spec::new_spec_from_common_params(&params, 9);
+    assert!(contracts.contract(address, &spec).is_none());
+    assert!(!GetPoSStake::instance().is_active(&spec));
+
+    let spec = Spec::new_spec_from_common_params(&params, 10);
+    assert!(contracts.contract(address, &spec).is_some());
+    assert!(GetPoSStake::instance().is_active(&spec));
+}
+
+#[test]
+fn test_pivot_decision_query_contract_sig() {
+    check_signature!(LatestPivotDecision, "0d243c7e");
+}
+
+#[test]
+fn test_pivot_decision_query_output() {
+    use solidity_abi::ABIEncodable;
+
+    let mut env = Env::default();
+    assert_eq!(
+        latest_pivot_decision(&env).abi_encode(),
+        vec![0u8; 64],
+        "Zeros before the PoS chain is launched"
+    );
+
+    env.finalized_epoch = Some(0x1234);
+    env.finalized_epoch_hash = Some(H256::repeat_byte(0xab));
+    let expected = "abababababababababababababababababababababababababababababababab\
+                    0000000000000000000000000000000000000000000000000000000000001234"
+        .from_hex::<Vec<u8>>()
+        .unwrap();
+    assert_eq!(latest_pivot_decision(&env).abi_encode(), expected);
+}
+
+#[test]
+fn test_pivot_decision_query_activation() {
+    use super::InternalContractMap;
+
+    let mut params = CommonParams::default();
+    params.transition_numbers.pivot_decision_query = 10;
+    let contracts = InternalContractMap::new(&params);
+    let address = &*PIVOT_DECISION_QUERY_CONTRACT_ADDRESS;
+    assert!(contracts.initialized_at(10).contains(address));
+
+    let spec = Spec::new_spec_from_common_params(&params, 9);
+    assert!(contracts.contract(address, &spec).is_none());
+    assert!(!LatestPivotDecision::instance().is_active(&spec));
+
+    let spec = Spec::new_spec_from_common_params(&params, 10);
+    assert!(contracts.contract(address, &spec).is_some());
+    assert!(LatestPivotDecision::instance().is_active(&spec));
+}
@@ -195,5 +195,7 @@ pub fn all_internal_contracts() -> Vec<Box<dyn InternalContractTrait>> {
         Box::new(future::AntiReentrancyConfig::instance()),
         Box::new(Context::instance()),
         Box::new(PoSRegister::instance()),
+        Box::new(future::PoSStakeQuery::instance()),
+        Box::new(future::PivotDecisionQuery::instance()),
     ]
 }
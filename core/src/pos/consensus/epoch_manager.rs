@@ -22,7 +22,7 @@ use super::{
     metrics_safety_rules::MetricsSafetyRules,
     network::{
         ConsensusMsg, ConsensusNetworkSender, IncomingBlockRetrievalRequest,
-        NetworkReceivers,
+        MessageDelays, NetworkReceivers,
     },
     persistent_liveness_storage::{
         LedgerRecoveryData, PersistentLivenessStorage, RecoveryData,
@@ -120,6 +120,8 @@ pub struct EpochManager {
         SignedTransaction,
         oneshot::Sender<anyhow::Result<SubmissionStatus>>,
     )>,
+    /// Shared by the network senders of all epochs.
+    message_delays: MessageDelays,
 }
 
 impl EpochManager {
@@ -147,6 +149,7 @@ impl EpochManager {
         let sr_config = &node_config.consensus.safety_rules;
         let safety_rules_manager = SafetyRulesManager::new(sr_config);
         diem_debug!("EpochManager.author={:?}", author);
+        let message_delays = MessageDelays::new(time_service.clone());
         Self {
             author,
             config,
@@ -164,6 +167,7 @@ impl EpochManager {
             reconfig_events,
             pow_handler,
             tx_sender,
+            message_delays,
         }
     }
 
@@ -452,12 +456,13 @@ impl EpochManager {
 
         diem_info!(epoch = epoch, "Create ProposerElection");
         let proposer_election = self.create_proposer_election(&epoch_state);
-        let network_sender = ConsensusNetworkSender::new(
+        let mut network_sender = ConsensusNetworkSender::new(
             self.author,
             self.network_sender.clone(),
             //self.self_sender.clone(),
             epoch_state.verifier.clone(),
         );
+        network_sender.set_message_delays(self.message_delays.clone());
 
         let mut processor = RoundManager::new(
             epoch_state,
@@ -506,12 +511,13 @@ impl EpochManager {
     )
     {
         let epoch = epoch_state.epoch;
-        let network_sender = ConsensusNetworkSender::new(
+        let mut network_sender = ConsensusNetworkSender::new(
             self.author,
             self.network_sender.clone(),
             //self.self_sender.clone(),
             epoch_state.verifier.clone(),
         );
+        network_sender.set_message_delays(self.message_delays.clone());
         self.processor = Some(RoundProcessor::Recovery(RecoveryManager::new(
             epoch_state,
             network_sender,
@@ -874,6 +880,25 @@ impl EpochManager {
                 diem_debug!("TestCommand::NewRoundTimeout, round={}", round);
                 self.process_new_round_timeout(round).await
             }
+            TestCommand::ForceTimeout { round } => {
+                match self.processor_mut() {
+                    RoundProcessor::Normal(p) => p.force_timeout(round).await,
+                    _ => anyhow::bail!("RoundManager not started yet"),
+                }
+            }
+            TestCommand::DropVote { round } => match self.processor_mut() {
+                RoundProcessor::Normal(p) => p.drop_vote(round),
+                _ => anyhow::bail!("RoundManager not started yet"),
+            },
+            TestCommand::DelayMessagesTo { peer, millis } => {
+                diem_debug!("TestCommand::DelayMessagesTo, peer={}", peer);
+                self.message_delays.set(peer, Duration::from_millis(millis));
+                Ok(())
+            }
+            TestCommand::ClearDelays => {
+                self.message_delays.clear();
+                Ok(())
+            }
             TestCommand::BroadcastPivotDecision(decision) => {
                 self.force_sign_pivot_decision(decision).await
             }
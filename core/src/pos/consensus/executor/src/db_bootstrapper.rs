@@ -19,7 +19,7 @@ use diem_types::{
     account_address::AccountAddress,
     account_config::diem_root_address,
     block_info::{
-        BlockInfo, PivotBlockDecision, GENESIS_EPOCH, GENESIS_ROUND,
+        PivotBlockDecision, GENESIS_EPOCH, GENESIS_ROUND,
         GENESIS_TIMESTAMP_USECS,
     },
     diem_timestamp::DiemTimestampResource,
@@ -208,18 +208,19 @@ pub fn calculate_genesis<V: VMExecutor>(
         get_state_timestamp(&state_view)?
     };
 
+    let genesis_block = executor.db_with_cache.get_block(&block_id)?;
+    let block_info = Executor::<V>::build_block_info(
+        genesis_block.lock().output(),
+        epoch,
+        GENESIS_ROUND,
+        block_id,
+        root_hash,
+        timestamp_usecs,
+        genesis_pivot_decision,
+    );
     let ledger_info_with_sigs = LedgerInfoWithSignatures::new(
         LedgerInfo::new(
-            BlockInfo::new(
-                epoch,
-                GENESIS_ROUND,
-                block_id,
-                root_hash,
-                genesis_version,
-                timestamp_usecs,
-                Some(next_epoch_state.clone()),
-                genesis_pivot_decision,
-            ),
+            block_info,
             HashValue::zero(), /* consensus_data_hash */
         ),
         BTreeMap::default(), /* signatures */
@@ -256,4 +257,225 @@ fn get_state_epoch(state_view: &VerifiedStateView) -> Result<u64> {
     Ok(rsrc.epoch())
 }
 
-fn genesis_block_id() -> HashValue { HashValue::zero() }
+pub(crate) fn genesis_block_id() -> HashValue { HashValue::zero() }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::FakeVM;
+    use diem_crypto::hash::SPARSE_MERKLE_PLACEHOLDER_HASH;
+    use diem_types::{
+        account_state::AccountState,
+        account_state_blob::AccountStateBlob,
+        contract_event::ContractEvent,
+        diem_timestamp::DiemTimestamp,
+        on_chain_config::{new_epoch_event_key, OnChainConfig, ValidatorSet},
+        term_state::pos_state_config::POS_STATE_CONFIG,
+        transaction::{
+            ChangeSet, TransactionOutput, TransactionStatus, WriteSetPayload,
+        },
+        validator_signer::ValidatorSigner,
+        validator_verifier::{ValidatorConsensusInfo, ValidatorVerifier},
+        vm_status::{KeptVMStatus, StatusCode, VMStatus},
+        write_set::{WriteOp, WriteSet, WriteSetMut},
+    };
+    use std::convert::TryFrom;
+    use storage_interface::mock::{
+        MockAccountReader, MockDbReader, MockDbWriter,
+    };
+
+    /// A VM which applies the write set of a direct genesis change set, as
+    /// the genesis transaction of an existing chain does. It reads the
+    /// written paths first, so that the executor has the proofs of the
+    /// written accounts to update the state tree.
+    struct ChangeSetVM;
+
+    impl VMExecutor for ChangeSetVM {
+        fn execute_block(
+            transactions: Vec<Transaction>, state_view: &dyn StateView,
+            _catch_up_mode: bool,
+        ) -> Result<Vec<TransactionOutput>, VMStatus>
+        {
+            transactions
+                .into_iter()
+                .map(|transaction| match transaction {
+                    Transaction::GenesisTransaction(
+                        WriteSetPayload::Direct(change_set),
+                    ) => {
+                        for (access_path, _) in change_set.write_set() {
+                            state_view.get(access_path).map_err(|_| {
+                                VMStatus::Error(StatusCode::STORAGE_ERROR)
+                            })?;
+                        }
+                        Ok(TransactionOutput::new(
+                            change_set.write_set().clone(),
+                            change_set.events().to_vec(),
+                            0, /* gas_used */
+                            TransactionStatus::Keep(KeptVMStatus::Executed),
+                        ))
+                    }
+                    _ => Err(VMStatus::Error(StatusCode::CFX_UNEXPECTED_TX)),
+                })
+                .collect()
+        }
+    }
+
+    fn test_verifier() -> ValidatorVerifier {
+        let signer = ValidatorSigner::from_int(1);
+        let mut validators = BTreeMap::new();
+        validators.insert(
+            signer.author(),
+            ValidatorConsensusInfo::new(
+                signer.public_key(),
+                signer.vrf_public_key(),
+                1,
+            ),
+        );
+        ValidatorVerifier::new(validators)
+    }
+
+    #[test]
+    fn test_calculate_genesis_block_info() {
+        POS_STATE_CONFIG.get_or_init(Default::default);
+        let verifier = test_verifier();
+        let genesis_txn = Transaction::GenesisTransaction(
+            WriteSetPayload::Direct(ChangeSet::new(
+                WriteSet::default(),
+                vec![ContractEvent::new(
+                    new_epoch_event_key(),
+                    bcs::to_bytes(&ValidatorSet::from(&verifier)).unwrap(),
+                )],
+            )),
+        );
+        let db = DbReaderWriter {
+            reader: Arc::new(MockDbReader),
+            writer: Arc::new(MockDbWriter::default()),
+        };
+        let pivot_decision = PivotBlockDecision {
+            block_hash: Default::default(),
+            height: 0,
+        };
+
+        let committer = calculate_genesis::<FakeVM>(
+            &db,
+            TreeState::new(0, vec![], *SPARSE_MERKLE_PLACEHOLDER_HASH),
+            &genesis_txn,
+            Some(pivot_decision.clone()),
+            vec![], /* initial_seed */
+            vec![], /* initial_nodes */
+            vec![], /* initial_committee */
+        )
+        .unwrap();
+        let ledger_info = committer.ledger_info_with_sigs.ledger_info();
+        assert_eq!(ledger_info.epoch(), GENESIS_EPOCH);
+        assert_eq!(ledger_info.round(), GENESIS_ROUND);
+        assert_eq!(ledger_info.consensus_block_id(), genesis_block_id());
+        assert_eq!(ledger_info.version(), 0);
+        assert_eq!(ledger_info.timestamp_usecs(), GENESIS_TIMESTAMP_USECS);
+        assert_eq!(ledger_info.pivot_decision(), Some(&pivot_decision));
+        let next_epoch_state = ledger_info
+            .next_epoch_state()
+            .expect("genesis starts epoch 1");
+        assert_eq!(next_epoch_state.epoch, GENESIS_EPOCH + 1);
+        assert_eq!(next_epoch_state.verifier, verifier);
+        assert_eq!(
+            committer.waypoint(),
+            Waypoint::new_epoch_boundary(ledger_info).unwrap()
+        );
+    }
+    #[test]
+    fn test_commit_genesis_on_existing_chain() {
+        POS_STATE_CONFIG.get_or_init(Default::default);
+        let verifier = test_verifier();
+        // The existing chain has one transaction and is in epoch 1.
+        let configuration =
+            ConfigurationResource::default().bump_epoch_for_test();
+        let mut account_state = AccountState::default();
+        account_state.insert(
+            ConfigurationResource::resource_path(),
+            bcs::to_bytes(&configuration).unwrap(),
+        );
+        let reader = Arc::new(MockAccountReader::new(
+            config_address(),
+            AccountStateBlob::try_from(&account_state).unwrap(),
+        ));
+        let writer = Arc::new(MockDbWriter::default());
+        let db = DbReaderWriter {
+            reader: reader.clone(),
+            writer: writer.clone(),
+        };
+
+        // The genesis transaction bumps the epoch and sets the time and the
+        // validators.
+        let timestamp_usecs = 1_000_000;
+        let write_set = WriteSetMut::new(vec![
+            (
+                AccessPath::new(
+                    config_address(),
+                    ConfigurationResource::resource_path(),
+                ),
+                WriteOp::Value(
+                    bcs::to_bytes(&configuration.bump_epoch_for_test())
+                        .unwrap(),
+                ),
+            ),
+            (
+                AccessPath::new(
+                    diem_root_address(),
+                    DiemTimestampResource::resource_path(),
+                ),
+                WriteOp::Value(
+                    bcs::to_bytes(&DiemTimestampResource {
+                        diem_timestamp: DiemTimestamp {
+                            microseconds: timestamp_usecs,
+                        },
+                    })
+                    .unwrap(),
+                ),
+            ),
+            (
+                ValidatorSet::CONFIG_ID.access_path(),
+                WriteOp::Value(
+                    bcs::to_bytes(&ValidatorSet::from(&verifier)).unwrap(),
+                ),
+            ),
+        ])
+        .freeze()
+        .unwrap();
+        let genesis_txn = Transaction::GenesisTransaction(
+            WriteSetPayload::Direct(ChangeSet::new(
+                write_set,
+                vec![ContractEvent::new(
+                    new_epoch_event_key(),
+                    bcs::to_bytes(&ValidatorSet::from(&verifier)).unwrap(),
+                )],
+            )),
+        );
+
+        let committer = calculate_genesis::<ChangeSetVM>(
+            &db,
+            TreeState::new(1, vec![HashValue::zero()], reader.root_hash()),
+            &genesis_txn,
+            Some(PivotBlockDecision {
+                block_hash: Default::default(),
+                height: 0,
+            }),
+            vec![], /* initial_seed */
+            vec![], /* initial_nodes */
+            vec![], /* initial_committee */
+        )
+        .unwrap();
+        let ledger_info = committer.ledger_info_with_sigs.ledger_info();
+        assert_eq!(ledger_info.epoch(), configuration.epoch());
+        assert_eq!(ledger_info.round(), GENESIS_ROUND);
+        assert_eq!(ledger_info.consensus_block_id(), genesis_block_id());
+        assert_eq!(ledger_info.version(), 1);
+        assert_eq!(ledger_info.timestamp_usecs(), timestamp_usecs);
+        assert!(committer.ledger_info_with_sigs.signatures().is_empty());
+
+        // The genesis ledger info is committed without signatures although
+        // its epoch is not 0.
+        committer.commit().unwrap();
+        assert_eq!(writer.commits(), 1);
+    }
+}
@@ -14,7 +14,7 @@ use std::{
     sync::Arc,
 };
 
-use anyhow::{anyhow, bail, ensure, format_err, Result};
+use anyhow::{bail, ensure, format_err, Result};
 use fail::fail_point;
 
 use cached_diemdb::CachedDiemDB;
@@ -33,12 +33,13 @@ use diem_types::{
     account_address::{AccountAddress, HashAccountAddress},
     account_state::AccountState,
     account_state_blob::AccountStateBlob,
-    block_info::PivotBlockDecision,
+    block_info::{BlockInfo, PivotBlockDecision, Round, GENESIS_ROUND},
     committed_block::CommittedBlock,
     contract_event::ContractEvent,
     epoch_state::EpochState,
-    ledger_info::LedgerInfoWithSignatures,
-    on_chain_config,
+    event::EventKey,
+    ledger_info::{LedgerInfo, LedgerInfoWithSignatures},
+    on_chain_config::{self, ValidatorSet},
     proof::accumulator::InMemoryAccumulator,
     reward_distribution_event::{RewardDistributionEvent, VoteCount},
     term_state::{
@@ -50,16 +51,19 @@ use diem_types::{
         TransactionOutput, TransactionPayload, TransactionStatus,
         TransactionToCommit, Version,
     },
+    validator_verifier::ValidatorVerifier,
     write_set::{WriteOp, WriteSet},
 };
 use executor_types::{
-    BlockExecutor, ChunkExecutor, Error, ExecutedTrees, ProcessedVMOutput,
-    ProofReader, StateComputeResult, TransactionData, TransactionReplayer,
+    BlockExecutor, ChunkExecutor, Error, ExecutedTrees, ExecutorError,
+    ProcessedVMOutput, ProofReader, StateComputeResult, TransactionData,
+    TransactionReceipt, TransactionReplayer,
 };
-use pow_types::PowInterface;
+use pow_types::{PowInterface, StakingEvent};
 use storage_interface::state_view::VerifiedStateView;
 
 use crate::{
+    db_bootstrapper::genesis_block_id,
     logging::{LogEntry, LogSchema},
     metrics::{
         DIEM_EXECUTOR_COMMIT_BLOCKS_SECONDS, DIEM_EXECUTOR_ERRORS,
@@ -69,6 +73,9 @@ use crate::{
         DIEM_EXECUTOR_TRANSACTIONS_SAVED,
         DIEM_EXECUTOR_VM_EXECUTE_BLOCK_SECONDS,
     },
+    output_cache::{
+        OutputCache, OutputCacheKey, DEFAULT_OUTPUT_CACHE_CAPACITY,
+    },
     vm::VMExecutor,
 };
 use diem_types::term_state::DisputeEvent;
@@ -76,10 +83,123 @@ use diem_types::term_state::DisputeEvent;
 pub mod db_bootstrapper;
 mod logging;
 mod metrics;
+mod output_cache;
 pub mod vm;
 
 type SparseMerkleProof = diem_types::proof::SparseMerkleProof<AccountStateBlob>;
 
+/// The keys of the PoS events handled in `process_vm_outputs`.
+struct PosEventKeys {
+    pivot_select: EventKey,
+    election: EventKey,
+    retire: EventKey,
+    register: EventKey,
+    update_voting_power: EventKey,
+    dispute: EventKey,
+}
+
+impl PosEventKeys {
+    fn new() -> Self {
+        Self {
+            pivot_select: PivotBlockDecision::pivot_select_event_key(),
+            election: ElectionEvent::event_key(),
+            retire: RetireEvent::event_key(),
+            register: RegisterEvent::event_key(),
+            update_voting_power: UpdateVotingPowerEvent::event_key(),
+            dispute: DisputeEvent::event_key(),
+        }
+    }
+}
+
+/// The kinds of PoS events found in a block by
+/// `Executor::execute_block_scan_only`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockEventSummary {
+    pub has_pivot_decision: bool,
+    pub has_election: bool,
+    pub has_dispute: bool,
+    pub has_register: bool,
+    pub has_update_voting_power: bool,
+    pub has_retire: bool,
+}
+
+impl BlockEventSummary {
+    /// Return `true` if the block has any event that changes the PoS
+    /// validator set.
+    pub fn changes_validator_set(&self) -> bool {
+        self.has_election
+            || self.has_dispute
+            || self.has_register
+            || self.has_update_voting_power
+            || self.has_retire
+    }
+
+    /// Summarize the PoS events emitted by `outputs`. If
+    /// `stop_at_first_event` is set, return right after the first one.
+    fn from_outputs(
+        outputs: &[TransactionOutput], stop_at_first_event: bool,
+    ) -> Self {
+        let event_keys = PosEventKeys::new();
+        let mut summary = Self::default();
+        for output in outputs {
+            for event in output.events() {
+                if summary.add_event(&event_keys, event.key())
+                    && stop_at_first_event
+                {
+                    return summary;
+                }
+            }
+        }
+        summary
+    }
+
+    /// Record `key` and return `true` if it is the key of a PoS event.
+    fn add_event(&mut self, keys: &PosEventKeys, key: &EventKey) -> bool {
+        let flag = if *key == keys.pivot_select {
+            &mut self.has_pivot_decision
+        } else if *key == keys.election {
+            &mut self.has_election
+        } else if *key == keys.dispute {
+            &mut self.has_dispute
+        } else if *key == keys.register {
+            &mut self.has_register
+        } else if *key == keys.update_voting_power {
+            &mut self.has_update_voting_power
+        } else if *key == keys.retire {
+            &mut self.has_retire
+        } else {
+            return false;
+        };
+        *flag = true;
+        true
+    }
+}
+
+/// The validators which joined and left between two PoS epochs, returned by
+/// `Executor::validator_set_diff`. Both lists are sorted by address.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ValidatorSetDiff {
+    pub added: Vec<AccountAddress>,
+    pub removed: Vec<AccountAddress>,
+}
+
+impl ValidatorSetDiff {
+    fn new(from: &ValidatorVerifier, to: &ValidatorVerifier) -> Self {
+        let from_validators = from.address_to_validator_info();
+        let to_validators = to.address_to_validator_info();
+        Self {
+            added: to
+                .get_ordered_account_addresses_iter()
+                .filter(|address| !from_validators.contains_key(address))
+                .collect(),
+            removed: from
+                .get_ordered_account_addresses_iter()
+                .filter(|address| !to_validators.contains_key(address))
+                .collect(),
+        }
+    }
+}
+
 /// `Executor` implements all functionalities the execution module needs to
 /// provide.
 pub struct Executor<V> {
@@ -87,6 +207,7 @@ pub struct Executor<V> {
     consensus_db: Arc<dyn LedgerBlockRW>,
     phantom: PhantomData<V>,
     pow_handler: Arc<dyn PowInterface>,
+    output_cache: OutputCache,
 }
 
 impl<V> Executor<V>
@@ -96,6 +217,141 @@ where V: VMExecutor
         self.db_with_cache.committed_block_id()
     }
 
+    /// Returns the validator verifier of the latest committed PoS epoch, or
+    /// `None` if no epoch state has been committed yet.
+    pub fn validators(&self) -> Option<ValidatorVerifier> {
+        let cache = self.db_with_cache.cache.lock();
+        let epoch_state = cache.committed_trees().pos_state().epoch_state();
+        if epoch_state.epoch == 0 {
+            None
+        } else {
+            Some(epoch_state.verifier.clone())
+        }
+    }
+
+    /// Returns the validator verifier of the latest committed PoS epoch if it
+    /// is `epoch`, which signs the ledger infos committing blocks in it.
+    fn commit_verifier(&self, epoch: u64) -> Option<ValidatorVerifier> {
+        let cache = self.db_with_cache.cache.lock();
+        let epoch_state = cache.committed_trees().pos_state().epoch_state();
+        if epoch_state.epoch == epoch {
+            Some(epoch_state.verifier.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether committing the block of `output` ends the current PoS
+    /// epoch, i.e. `output` carries the state of the next epoch.
+    pub fn is_epoch_boundary(output: &ProcessedVMOutput) -> bool {
+        output.has_reconfiguration()
+    }
+
+    /// Returns the validator set of the PoS epoch started by the block of
+    /// `output`, or `None` if the block is not an epoch boundary.
+    pub fn next_epoch_validators(
+        output: &ProcessedVMOutput,
+    ) -> Option<ValidatorSet> {
+        output
+            .epoch_state()
+            .as_ref()
+            .map(|epoch_state| (&epoch_state.verifier).into())
+    }
+
+    /// Compare the validator sets of the PoS epochs `from_epoch` and
+    /// `to_epoch`, which are read from the epoch ending ledger infos.
+    pub fn validator_set_diff(
+        &self, from_epoch: u64, to_epoch: u64,
+    ) -> Result<ValidatorSetDiff> {
+        ensure!(
+            from_epoch > 0 && from_epoch <= to_epoch,
+            "Bad epoch range [{}, {}]",
+            from_epoch,
+            to_epoch,
+        );
+        let from = self.epoch_validators(from_epoch)?;
+        let to = self.epoch_validators(to_epoch)?;
+        Ok(ValidatorSetDiff::new(&from, &to))
+    }
+
+    /// The validators of `epoch` are set by the ledger info which ends the
+    /// previous epoch.
+    fn epoch_validators(&self, epoch: u64) -> Result<ValidatorVerifier> {
+        let proof = self
+            .db_with_cache
+            .db
+            .reader
+            .get_epoch_ending_ledger_infos(epoch - 1, epoch)?;
+        let ledger_info = proof
+            .ledger_info_with_sigs
+            .first()
+            .ok_or_else(|| {
+                format_err!("No ledger info ends epoch {}", epoch - 1)
+            })?
+            .ledger_info();
+        let epoch_state = ledger_info.next_epoch_state().ok_or_else(|| {
+            format_err!(
+                "The ledger info ending epoch {} has no next epoch state",
+                epoch - 1
+            )
+        })?;
+        Ok(epoch_state.verifier.clone())
+    }
+
+    /// The number of `execute_block` calls that reused the output of an
+    /// earlier execution of the same block.
+    pub fn output_cache_hits(&self) -> u64 { self.output_cache.hits() }
+
+    /// Build the `BlockInfo` of an executed block from its `output`,
+    /// including the next epoch state carried by `output`. Nothing is
+    /// written. `epoch` is the PoS membership id of the block. The pivot
+    /// decision is passed in because the genesis block takes it from the
+    /// genesis config instead of `output.pivot_block()`. `executed_state_id`
+    /// is the root hash of the `StateComputeResult` of the block, which is
+    /// only the accumulator root of `output` on an empty ledger.
+    pub fn build_block_info(
+        output: &ProcessedVMOutput, epoch: u64, round: Round, id: HashValue,
+        executed_state_id: HashValue, timestamp_usecs: u64,
+        pivot: Option<PivotBlockDecision>,
+    ) -> BlockInfo
+    {
+        BlockInfo::new(
+            epoch,
+            round,
+            id,
+            executed_state_id,
+            output.version().unwrap_or(0),
+            timestamp_usecs,
+            output.epoch_state().clone(),
+            pivot,
+        )
+    }
+
+    /// Execute the transactions of `block` on top of `parent_block_id` only
+    /// to find which kinds of PoS events it contains. Unlike `execute_block`,
+    /// no `ProcessedVMOutput` is built and nothing is added to the block
+    /// tree. If `stop_at_first_event` is set, the scan returns right after
+    /// the first PoS event.
+    pub fn execute_block_scan_only(
+        &self, block: (HashValue, Vec<Transaction>),
+        parent_block_id: HashValue, catch_up_mode: bool,
+        stop_at_first_event: bool,
+    ) -> Result<BlockEventSummary, Error>
+    {
+        let (block_id, transactions) = block;
+        let parent_block_executed_trees =
+            self.get_executed_trees(parent_block_id)?;
+        let state_view = self.get_executed_state_view(
+            StateViewId::BlockExecution { block_id },
+            &parent_block_executed_trees,
+        );
+        let vm_outputs =
+            V::execute_block(transactions, &state_view, catch_up_mode)
+                .map_err(anyhow::Error::from)?;
+
+        Ok(BlockEventSummary::from_outputs(&vm_outputs, stop_at_first_event))
+    }
+
     /// Constructs an `Executor`.
     pub fn new(
         db_with_cache: Arc<CachedDiemDB>, pow_handler: Arc<dyn PowInterface>,
@@ -107,6 +363,7 @@ where V: VMExecutor
             consensus_db,
             phantom: PhantomData,
             pow_handler,
+            output_cache: OutputCache::new(DEFAULT_OUTPUT_CACHE_CAPACITY),
         }
     }
 
@@ -277,7 +534,7 @@ where V: VMExecutor
         transactions: &[Transaction], vm_outputs: Vec<TransactionOutput>,
         parent_trees: &ExecutedTrees, parent_block_id: &HashValue,
         catch_up_mode: bool,
-    ) -> Result<ProcessedVMOutput>
+    ) -> Result<ProcessedVMOutput, Error>
     {
         // The data of each individual transaction. For convenience purpose,
         // even for the transactions that will be discarded, we will
@@ -290,13 +547,14 @@ where V: VMExecutor
         let mut txn_info_hashes = vec![];
 
         let proof_reader = ProofReader::new(account_to_proof);
-        let pivot_select_event_key =
-            PivotBlockDecision::pivot_select_event_key();
-        let election_event_key = ElectionEvent::event_key();
-        let retire_event_key = RetireEvent::event_key();
-        let register_event_key = RegisterEvent::event_key();
-        let update_voting_power_event_key = UpdateVotingPowerEvent::event_key();
-        let dispute_event_key = DisputeEvent::event_key();
+        let PosEventKeys {
+            pivot_select: pivot_select_event_key,
+            election: election_event_key,
+            retire: retire_event_key,
+            register: register_event_key,
+            update_voting_power: update_voting_power_event_key,
+            dispute: dispute_event_key,
+        } = PosEventKeys::new();
 
         // Find the next pivot block.
         let mut pivot_decision = None;
@@ -307,7 +565,9 @@ where V: VMExecutor
                 // check for pivot block selection.
                 if *event.key() == pivot_select_event_key {
                     if pivot_decision.is_some() {
-                        bail!("Multiple pivot decisions in one block!");
+                        return Err(
+                            ExecutorError::MultiplePivotDecisions.into()
+                        );
                     }
                     pivot_decision = Some(PivotBlockDecision::from_bytes(
                         event.event_data(),
@@ -331,6 +591,10 @@ where V: VMExecutor
                     parent_block_id,
                     parent_pivot_decision
                 );
+                check_pivot_decision_advances(
+                    Some(&parent_pivot_decision),
+                    pivot_decision,
+                )?;
 
                 // The check and event processing below will be skipped during
                 // PoS catching up, because pow has not processed these pivot
@@ -340,7 +604,7 @@ where V: VMExecutor
                         parent_pivot_decision.block_hash,
                         pivot_decision.block_hash,
                     ) {
-                        bail!("Invalid pivot decision for block");
+                        return Err(ExecutorError::InvalidPivotDecision.into());
                     }
 
                     // Verify if the proposer has packed all staking events as
@@ -364,11 +628,15 @@ where V: VMExecutor
                                 let register_event = RegisterEvent::from_bytes(
                                     event.event_data(),
                                 )?;
-                                match register_event.matches_staking_event(staking_events_iter.next().ok_or(anyhow!("More staking transactions packed than actual pow events"))?) {
-                                    Ok(true) => {}
-                                    Ok(false) => bail!("Packed staking transactions unmatch PoW events)"),
-                                    Err(e) => diem_error!("error decoding pow events: err={:?}", e),
-                                }
+                                match_next_staking_event(
+                                    &mut staking_events_iter,
+                                    |staking_event| {
+                                        register_event
+                                            .matches_staking_event(
+                                                staking_event,
+                                            )
+                                    },
+                                )?;
                                 new_pos_state
                                     .register_node(register_event.node_id)?;
                             } else if *event.key()
@@ -378,11 +646,15 @@ where V: VMExecutor
                                     UpdateVotingPowerEvent::from_bytes(
                                         event.event_data(),
                                     )?;
-                                match update_voting_power_event.matches_staking_event(staking_events_iter.next().ok_or(anyhow!("More staking transactions packed than actual pow events"))?) {
-                                    Ok(true) => {}
-                                    Ok(false) => bail!("Packed staking transactions unmatch PoW events)"),
-                                    Err(e) => diem_error!("error decoding pow events: err={:?}", e),
-                                }
+                                match_next_staking_event(
+                                    &mut staking_events_iter,
+                                    |staking_event| {
+                                        update_voting_power_event
+                                            .matches_staking_event(
+                                                staking_event,
+                                            )
+                                    },
+                                )?;
                                 new_pos_state.update_voting_power(
                                     &update_voting_power_event.node_address,
                                     update_voting_power_event.voting_power,
@@ -391,11 +663,15 @@ where V: VMExecutor
                                 let retire_event = RetireEvent::from_bytes(
                                     event.event_data(),
                                 )?;
-                                match retire_event.matches_staking_event(staking_events_iter.next().ok_or(anyhow!("More staking transactions packed than actual pow events"))?) {
-                                    Ok(true) => {}
-                                    Ok(false) => bail!("Packed staking transactions unmatch PoW events)"),
-                                    Err(e) => diem_error!("error decoding pow events: err={:?}", e),
-                                }
+                                match_next_staking_event(
+                                    &mut staking_events_iter,
+                                    |staking_event| {
+                                        retire_event
+                                            .matches_staking_event(
+                                                staking_event,
+                                            )
+                                    },
+                                )?;
                                 new_pos_state.retire_node(
                                     &retire_event.node_id,
                                     retire_event.votes,
@@ -403,10 +679,11 @@ where V: VMExecutor
                             }
                         }
                     }
-                    ensure!(
-                        staking_events_iter.next().is_none(),
-                        "Not all PoW staking events are packed"
-                    );
+                    if staking_events_iter.next().is_some() {
+                        return Err(
+                            ExecutorError::MissingStakingTransactions.into()
+                        );
+                    }
                 } else {
                     for vm_output in vm_outputs.clone().into_iter() {
                         for event in vm_output.events() {
@@ -441,17 +718,10 @@ where V: VMExecutor
                     }
                 }
             } else {
-                // No new pivot decision, so there should be no staking-related
-                // transactions.
-                if vm_outputs.iter().any(|output| {
-                    output.events().iter().any(|event| {
-                        *event.key() == retire_event_key
-                            || *event.key() == update_voting_power_event_key
-                    })
-                }) {
-                    bail!("Should not pack staking related transactions");
-                }
-                pivot_decision = Some(parent_pivot_decision);
+                pivot_decision = Some(inherit_pivot_decision(
+                    &vm_outputs,
+                    parent_pivot_decision,
+                )?);
             }
         }
         // TODO(lpl): This is only for pos-tool
@@ -459,6 +729,7 @@ where V: VMExecutor
             new_pos_state.set_pivot_decision(pivot_decision.clone());
         }
         let mut next_epoch_state = new_pos_state.next_view()?;
+        let total_gas_used = total_gas_used(&vm_outputs);
 
         let txn_blobs =
             itertools::zip_eq(vm_outputs.iter(), transactions.iter())
@@ -615,7 +886,7 @@ where V: VMExecutor
         let current_transaction_accumulator =
             parent_trees.txn_accumulator().append(&txn_info_hashes);
 
-        Ok(ProcessedVMOutput::new(
+        let mut output = ProcessedVMOutput::new(
             txn_data,
             ExecutedTrees::new_copy(
                 Arc::new(current_state_tree),
@@ -625,7 +896,74 @@ where V: VMExecutor
             next_epoch_state,
             // TODO(lpl): Check if we need to assert it's Some.
             pivot_decision,
-        ))
+        );
+        output.set_total_gas_used(total_gas_used);
+        Ok(output)
+    }
+
+    /// Run the VM on `transactions` on top of `parent_block_executed_trees`
+    /// and process its outputs.
+    fn execute_transactions(
+        &self, block_id: HashValue, transactions: &[Transaction],
+        parent_block_id: &HashValue,
+        parent_block_executed_trees: &ExecutedTrees, catch_up_mode: bool,
+    ) -> Result<ProcessedVMOutput, Error>
+    {
+        let state_view = self.get_executed_state_view(
+            StateViewId::BlockExecution { block_id },
+            parent_block_executed_trees,
+        );
+
+        // FIXME(lpl): Check the error processing in `execute_block`,
+        // `process_vm_outputs`, and transaction packing. We
+        // need to ensure that there is no packing behavior that
+        // makes all new proposals invalid during execution.
+        let vm_outputs = {
+            // trace_code_block!("executor::execute_block", {"block",
+            // block_id});
+            let _timer = DIEM_EXECUTOR_VM_EXECUTE_BLOCK_SECONDS.start_timer();
+            fail_point!("executor::vm_execute_block", |_| {
+                Err(Error::from(anyhow::anyhow!(
+                    "Injected error in vm_execute_block"
+                )))
+            });
+            V::execute_block(transactions.to_vec(), &state_view, catch_up_mode)
+                .map_err(anyhow::Error::from)?
+        };
+
+        // trace_code_block!("executor::process_vm_outputs", {"block",
+        // block_id});
+        let status: Vec<_> = vm_outputs
+            .iter()
+            .map(TransactionOutput::status)
+            .cloned()
+            .collect();
+        if !status.is_empty() {
+            diem_trace!("Execution status: {:?}", status);
+        }
+        let receipts: Vec<_> =
+            vm_outputs.iter().map(TransactionReceipt::from).collect();
+
+        let (account_to_state, account_to_proof) = state_view.into();
+
+        let mut output = self
+            .process_vm_outputs(
+                account_to_state,
+                account_to_proof,
+                transactions,
+                vm_outputs,
+                parent_block_executed_trees,
+                parent_block_id,
+                catch_up_mode,
+            )
+            .map_err(|err| match err {
+                Error::InternalError { error } => Error::InternalError {
+                    error: format!("Failed to execute block: {}", error),
+                },
+                err => err,
+            })?;
+        output.set_receipts(receipts);
+        Ok(output)
     }
 
     fn extract_reconfig_events(
@@ -668,6 +1006,8 @@ where V: VMExecutor
             cache.committed_trees().state_root(),
             executed_trees.state_tree(),
             executed_trees.pos_state().clone(),
+            None, /* cache_capacity */
+            false, /* strict_version */
         )
     }
 
@@ -692,6 +1032,8 @@ where V: VMExecutor
             cache.synced_trees().state_tree(),
             // TODO(lpl): State sync not used yet.
             PosState::new_empty(),
+            None, /* cache_capacity */
+            false, /* strict_version */
         );
 
         fail_point!("executor::vm_execute_chunk", |_| {
@@ -1044,59 +1386,40 @@ impl<V: VMExecutor> BlockExecutor for Executor<V> {
             let parent_block_executed_trees =
                 self.get_executed_trees(parent_block_id)?;
 
-            let state_view = self.get_executed_state_view(
-                StateViewId::BlockExecution { block_id },
-                &parent_block_executed_trees,
-            );
-
-            // FIXME(lpl): Check the error processing in `execute_block`,
-            // `process_vm_outputs`, and transaction packing. We
-            // need to ensure that there is no packing behavior that
-            // makes all new proposals invalid during execution.
-            let vm_outputs = {
-                // trace_code_block!("executor::execute_block", {"block",
-                // block_id});
-                let _timer =
-                    DIEM_EXECUTOR_VM_EXECUTE_BLOCK_SECONDS.start_timer();
-                fail_point!("executor::vm_execute_block", |_| {
-                    Err(Error::from(anyhow::anyhow!(
-                        "Injected error in vm_execute_block"
-                    )))
-                });
-                V::execute_block(
-                    transactions.clone(),
-                    &state_view,
-                    catch_up_mode,
-                )
-                .map_err(anyhow::Error::from)?
+            let cache_key = OutputCacheKey {
+                parent_id: parent_block_id,
+                id: block_id,
+                membership_id: parent_block_executed_trees
+                    .pos_state()
+                    .epoch_state()
+                    .epoch,
+            };
+            let output = match self.output_cache.get(&cache_key) {
+                Some(output) => {
+                    diem_debug!(
+                        LogSchema::new(LogEntry::BlockExecutor)
+                            .block_id(block_id),
+                        "execute_block_cache_hit"
+                    );
+                    output.as_ref().clone()
+                }
+                None => {
+                    let output = self.execute_transactions(
+                        block_id,
+                        &transactions,
+                        &parent_block_id,
+                        &parent_block_executed_trees,
+                        catch_up_mode,
+                    )?;
+                    // Some checks are skipped when catching up, so only the
+                    // outputs of normal execution are reused.
+                    if !catch_up_mode {
+                        self.output_cache
+                            .insert(cache_key, Arc::new(output.clone()));
+                    }
+                    output
+                }
             };
-
-            // trace_code_block!("executor::process_vm_outputs", {"block",
-            // block_id});
-            let status: Vec<_> = vm_outputs
-                .iter()
-                .map(TransactionOutput::status)
-                .cloned()
-                .collect();
-            if !status.is_empty() {
-                diem_trace!("Execution status: {:?}", status);
-            }
-
-            let (account_to_state, account_to_proof) = state_view.into();
-
-            let output = self
-                .process_vm_outputs(
-                    account_to_state,
-                    account_to_proof,
-                    &transactions,
-                    vm_outputs,
-                    &parent_block_executed_trees,
-                    &parent_block_id,
-                    catch_up_mode,
-                )
-                .map_err(|err| {
-                    format_err!("Failed to execute block: {}", err)
-                })?;
 
             let parent_accu = parent_block_executed_trees.txn_accumulator();
 
@@ -1121,6 +1444,11 @@ impl<V: VMExecutor> BlockExecutor for Executor<V> {
     ) -> Result<(Vec<Transaction>, Vec<ContractEvent>), Error>
     {
         let _timer = DIEM_EXECUTOR_COMMIT_BLOCKS_SECONDS.start_timer();
+        verify_commit_signatures(
+            &ledger_info_with_sigs,
+            self.commit_verifier(ledger_info_with_sigs.ledger_info().epoch())
+                .as_ref(),
+        )?;
         let mut pos_state_to_commit = self
             .get_executed_trees(
                 ledger_info_with_sigs.ledger_info().consensus_block_id(),
@@ -1131,7 +1459,7 @@ impl<V: VMExecutor> BlockExecutor for Executor<V> {
         // TODO(lpl): Implement force_retire better?
         // Process pos_state to apply force_retire.
         if ledger_info_with_sigs.ledger_info().ends_epoch()
-            && ledger_info_with_sigs.ledger_info().epoch() != 0
+            && !is_genesis_commit(ledger_info_with_sigs.ledger_info())
         {
             let ending_block =
                 ledger_info_with_sigs.ledger_info().consensus_block_id();
@@ -1249,12 +1577,7 @@ impl<V: VMExecutor> BlockExecutor for Executor<V> {
             .num_leaves();
 
         if num_txns_in_li < num_persistent_txns {
-            return Err(Error::InternalError {
-                error: format!(
-                    "Try to commit stale transactions with the last version as {}",
-                    version
-                ),
-            });
+            return Err(ExecutorError::StaleCommit(version).into());
         }
 
         // All transactions that need to go to storage. In the above example,
@@ -1267,9 +1590,10 @@ impl<V: VMExecutor> BlockExecutor for Executor<V> {
             .map(|id| self.db_with_cache.get_block(id))
             .collect::<Result<Vec<_>, Error>>()?;
         let blocks = arc_blocks.iter().map(|b| b.lock()).collect::<Vec<_>>();
+        let last_block = blocks.last().ok_or(ExecutorError::EmptyBlockBatch)?;
         let mut committed_blocks = Vec::new();
         let mut signatures_vec = Vec::new();
-        if ledger_info_with_sigs.ledger_info().epoch() != 0 {
+        if !is_genesis_commit(ledger_info_with_sigs.ledger_info()) {
             for (i, b) in blocks.iter().enumerate() {
                 let ledger_block = self
                     .consensus_db
@@ -1302,9 +1626,11 @@ impl<V: VMExecutor> BlockExecutor for Executor<V> {
                     ));
                 }
             }
-            let last_block = blocks.last().expect("not empty").id();
-            if let Some(qc) = self.consensus_db.get_qc_for_block(&last_block)? {
-                signatures_vec.push((last_block, qc.ledger_info().clone()));
+            let last_block_id = last_block.id();
+            if let Some(qc) =
+                self.consensus_db.get_qc_for_block(&last_block_id)?
+            {
+                signatures_vec.push((last_block_id, qc.ledger_info().clone()));
             } else {
                 // If we are catching up, all QCs come from retrieved blocks, so
                 // we cannot get the QC that votes for the last
@@ -1315,7 +1641,7 @@ impl<V: VMExecutor> BlockExecutor for Executor<V> {
         } else {
             committed_blocks.push(CommittedBlock {
                 hash: ledger_info_with_sigs.ledger_info().consensus_block_id(),
-                epoch: 0,
+                epoch: ledger_info_with_sigs.ledger_info().epoch(),
                 round: 0,
                 miner: None,
                 parent_hash: HashValue::default(),
@@ -1350,10 +1676,6 @@ impl<V: VMExecutor> BlockExecutor for Executor<V> {
             }
         }
 
-        let last_block = blocks
-            .last()
-            .ok_or_else(|| format_err!("CommittableBlockBatch is empty"))?;
-
         // Check that the version in ledger info (computed by consensus) matches
         // the version computed by us.
         let num_txns_in_speculative_accumulator = last_block
@@ -1519,3 +1841,1017 @@ fn update_account_state(
         WriteOp::Deletion => account_state.remove(&path),
     };
 }
+
+/// The gas used by all transactions in `vm_outputs`.
+fn total_gas_used(vm_outputs: &[TransactionOutput]) -> u64 {
+    vm_outputs.iter().map(TransactionOutput::gas_used).sum()
+}
+
+/// The pivot decision of a block whose transactions decide none, e.g. a block
+/// with only `BlockMetadata`. It keeps the one of its parent, and it can not
+/// have staking transactions since they are only packed with a new pivot
+/// decision. Just as for other blocks, a new epoch state comes from the PoS
+/// view change alone.
+fn inherit_pivot_decision(
+    vm_outputs: &[TransactionOutput], parent_pivot_decision: PivotBlockDecision,
+) -> Result<PivotBlockDecision, ExecutorError> {
+    let retire_event_key = RetireEvent::event_key();
+    let update_voting_power_event_key = UpdateVotingPowerEvent::event_key();
+    if vm_outputs.iter().any(|output| {
+        output.events().iter().any(|event| {
+            *event.key() == retire_event_key
+                || *event.key() == update_voting_power_event_key
+        })
+    }) {
+        return Err(ExecutorError::UnexpectedStakingTransactions);
+    }
+    Ok(parent_pivot_decision)
+}
+
+/// Check that a new pivot decision is higher than the last one, if any. The
+/// genesis block has no last pivot decision.
+fn check_pivot_decision_advances(
+    last_pivot_decision: Option<&PivotBlockDecision>,
+    pivot_decision: &PivotBlockDecision,
+) -> Result<(), ExecutorError>
+{
+    match last_pivot_decision {
+        Some(last) if pivot_decision.height <= last.height => {
+            Err(ExecutorError::PivotDecisionNotAdvanced {
+                last_height: last.height,
+                height: pivot_decision.height,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check a packed staking transaction against the next PoW staking event with
+/// `matches`. A PoW event that cannot be decoded is only logged.
+fn match_next_staking_event<'a>(
+    staking_events_iter: &mut impl Iterator<Item = &'a StakingEvent>,
+    matches: impl FnOnce(&StakingEvent) -> Result<bool>,
+) -> Result<(), ExecutorError>
+{
+    let staking_event = staking_events_iter
+        .next()
+        .ok_or(ExecutorError::TooManyStakingTransactions)?;
+    match matches(staking_event) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ExecutorError::UnmatchedStakingTransaction),
+        Err(e) => {
+            diem_error!("error decoding pow events: err={:?}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Returns whether `ledger_info` commits the genesis block. It is built by
+/// `calculate_genesis` without signatures, in epoch 0 on a new chain or in the
+/// epoch read from the state when an existing chain is bootstrapped again.
+fn is_genesis_commit(ledger_info: &LedgerInfo) -> bool {
+    ledger_info.epoch() == 0
+        || (ledger_info.consensus_block_id() == genesis_block_id()
+            && ledger_info.round() == GENESIS_ROUND)
+}
+
+/// Verify the signatures of the ledger info to commit against `verifier` of
+/// its epoch. The genesis ledger info is committed without signatures, and
+/// any other one is rejected if the validators of its epoch are unknown.
+fn verify_commit_signatures(
+    ledger_info_with_sigs: &LedgerInfoWithSignatures,
+    verifier: Option<&ValidatorVerifier>,
+) -> Result<(), ExecutorError>
+{
+    if is_genesis_commit(ledger_info_with_sigs.ledger_info()) {
+        return Ok(());
+    }
+    let epoch = ledger_info_with_sigs.ledger_info().epoch();
+    let verifier = verifier.ok_or(ExecutorError::UnknownCommitEpoch(epoch))?;
+    ledger_info_with_sigs
+        .verify_signatures(verifier)
+        .map_err(|e| ExecutorError::InvalidCommitSignatures(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::FakeVM;
+    use cached_diemdb::SpeculationCache;
+    use consensus_types::{block::Block, quorum_cert::QuorumCert};
+    use diem_crypto::{
+        hash::SPARSE_MERKLE_PLACEHOLDER_HASH, PrivateKey, Uniform,
+    };
+    use diem_infallible::Mutex;
+    use diem_types::{
+        account_address::from_consensus_public_key,
+        block_info::{GENESIS_EPOCH, GENESIS_TIMESTAMP_USECS},
+        block_metadata::BlockMetadata,
+        term_state::{pos_state_config::POS_STATE_CONFIG, NodeID, UnlockEvent},
+        transaction::{ChangeSet, WriteSetPayload},
+        validator_config::{ConsensusPrivateKey, ConsensusVRFPrivateKey},
+        validator_signer::ValidatorSigner,
+        validator_verifier::ValidatorConsensusInfo,
+        vm_status::KeptVMStatus,
+    };
+    use pow_types::FakePowHandler;
+    use rand::{rngs::StdRng, SeedableRng};
+    use storage_interface::{
+        mock::{MockDbReader, MockDbWriter, MockEpochReader},
+        DbReader, DbReaderWriter, TreeState,
+    };
+
+    fn retire_events() -> (RetireEvent, StakingEvent) {
+        let node_id = AccountAddress::new([1; AccountAddress::LENGTH]);
+        let staking_event =
+            StakingEvent::Retire(H256::from_slice(node_id.as_ref()), 10);
+        (RetireEvent::new(node_id, 10), staking_event)
+    }
+
+    #[test]
+    fn test_match_next_staking_event() {
+        let (retire_event, staking_event) = retire_events();
+        let matches = |e: &StakingEvent| retire_event.matches_staking_event(e);
+        let staking_events = vec![staking_event];
+
+        let mut iter = staking_events.iter();
+        assert_eq!(match_next_staking_event(&mut iter, matches), Ok(()));
+        assert_eq!(
+            match_next_staking_event(&mut iter, matches),
+            Err(ExecutorError::TooManyStakingTransactions)
+        );
+
+        let other = RetireEvent::new(retire_event.node_id, 11);
+        let mut iter = staking_events.iter();
+        assert_eq!(
+            match_next_staking_event(&mut iter, |e| other
+                .matches_staking_event(e)),
+            Err(ExecutorError::UnmatchedStakingTransaction)
+        );
+
+        // A PoW event that fails to decode does not reject the block.
+        let mut iter = staking_events.iter();
+        let undecodable =
+            |_: &StakingEvent| -> Result<bool> { bail!("undecodable") };
+        assert_eq!(match_next_staking_event(&mut iter, undecodable), Ok(()));
+    }
+
+    fn pivot_decision(height: u64) -> PivotBlockDecision {
+        PivotBlockDecision {
+            block_hash: H256::from_low_u64_be(height),
+            height,
+        }
+    }
+
+    #[test]
+    fn test_check_pivot_decision_advances() {
+        let last = pivot_decision(120);
+        assert_eq!(
+            check_pivot_decision_advances(Some(&last), &pivot_decision(180)),
+            Ok(())
+        );
+        for height in &[120, 60] {
+            assert_eq!(
+                check_pivot_decision_advances(
+                    Some(&last),
+                    &pivot_decision(*height)
+                ),
+                Err(ExecutorError::PivotDecisionNotAdvanced {
+                    last_height: 120,
+                    height: *height,
+                })
+            );
+        }
+        // Genesis has no last pivot decision.
+        assert_eq!(
+            check_pivot_decision_advances(None, &pivot_decision(0)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_metadata_only_block() {
+        let (executor, _writer) = new_test_executor(&[pos_signer(1)]);
+        let (block_id, transactions) = metadata_block(1);
+        let result = executor
+            .execute_block(
+                (block_id, transactions),
+                executor.committed_block_id(),
+                false, /* catch_up_mode */
+            )
+            .unwrap();
+        assert_eq!(result.pivot_decision(), &Some(pivot_decision(0)));
+        assert!(!result.has_reconfiguration());
+
+        let block = executor.db_with_cache.get_block(&block_id).unwrap();
+        let block = block.lock();
+        let output = block.output();
+        // The block neither changes the pivot decision nor ends the epoch.
+        assert_eq!(output.pivot_block(), &Some(pivot_decision(0)));
+        assert_eq!(
+            output.executed_trees().pos_state().pivot_decision(),
+            &pivot_decision(0)
+        );
+        assert_eq!(output.epoch_state(), &None);
+        assert_eq!(Executor::<FakeVM>::next_epoch_validators(output), None);
+
+        // Staking transactions need a new pivot decision.
+        let (retire_event, _) = retire_events();
+        let retire_output = TransactionOutput::new(
+            WriteSet::default(),
+            vec![ContractEvent::new(
+                RetireEvent::event_key(),
+                bcs::to_bytes(&retire_event).unwrap(),
+            )],
+            0,
+            TransactionStatus::Keep(KeptVMStatus::Executed),
+        );
+        assert_eq!(
+            inherit_pivot_decision(&[retire_output], pivot_decision(120)),
+            Err(ExecutorError::UnexpectedStakingTransactions)
+        );
+    }
+
+    #[test]
+    fn test_total_gas_used() {
+        let vm_outputs: Vec<_> = [3, 0, 5]
+            .iter()
+            .map(|gas_used| {
+                TransactionOutput::new(
+                    WriteSet::default(),
+                    vec![],
+                    *gas_used,
+                    TransactionStatus::Keep(KeptVMStatus::Executed),
+                )
+            })
+            .collect();
+        assert_eq!(total_gas_used(&vm_outputs), 8);
+
+        let mut output = ProcessedVMOutput::new(
+            vec![],
+            ExecutedTrees::new_empty(),
+            None,
+            None,
+        );
+        assert_eq!(output.compute_result(vec![], 0).total_gas_used(), Some(0));
+        output.set_total_gas_used(total_gas_used(&vm_outputs));
+        assert_eq!(output.total_gas_used(), 8);
+        assert_eq!(output.compute_result(vec![], 0).total_gas_used(), Some(8));
+    }
+
+    #[test]
+    fn test_transaction_receipt() {
+        let (retire_event, _) = retire_events();
+        let events = vec![ContractEvent::new(
+            RetireEvent::event_key(),
+            bcs::to_bytes(&retire_event).unwrap(),
+        )];
+        let output = TransactionOutput::new(
+            WriteSet::default(),
+            events.clone(),
+            7,
+            TransactionStatus::Keep(KeptVMStatus::Executed),
+        );
+        let receipt = TransactionReceipt::from(&output);
+        assert_eq!(
+            receipt.status(),
+            &TransactionStatus::Keep(KeptVMStatus::Executed)
+        );
+        assert_eq!(receipt.gas_used(), 7);
+        assert_eq!(receipt.events(), &events[..]);
+
+        let output = TransactionOutput::new(
+            WriteSet::default(),
+            vec![],
+            0,
+            TransactionStatus::Retry,
+        );
+        let receipt = TransactionReceipt::from(&output);
+        assert_eq!(receipt.status(), &TransactionStatus::Retry);
+        assert_eq!(receipt.gas_used(), 0);
+        assert!(receipt.events().is_empty());
+    }
+
+    #[test]
+    fn test_block_event_summary_add_event() {
+        let keys = PosEventKeys::new();
+        let cases: Vec<(EventKey, fn(&mut BlockEventSummary))> = vec![
+            (keys.pivot_select, |s| s.has_pivot_decision = true),
+            (keys.election, |s| s.has_election = true),
+            (keys.retire, |s| s.has_retire = true),
+            (keys.register, |s| s.has_register = true),
+            (keys.update_voting_power, |s| s.has_update_voting_power = true),
+            (keys.dispute, |s| s.has_dispute = true),
+        ];
+        for (key, set_flag) in cases {
+            let mut expected = BlockEventSummary::default();
+            set_flag(&mut expected);
+            let mut summary = BlockEventSummary::default();
+            assert!(summary.add_event(&keys, &key));
+            assert_eq!(summary, expected);
+            assert_eq!(
+                summary.changes_validator_set(),
+                key != keys.pivot_select
+            );
+        }
+
+        let mut summary = BlockEventSummary::default();
+        for key in &[
+            UnlockEvent::event_key(),
+            on_chain_config::new_epoch_event_key(),
+        ] {
+            assert!(!summary.add_event(&keys, key));
+        }
+        assert_eq!(summary, BlockEventSummary::default());
+    }
+
+    #[test]
+    fn test_block_event_summary_stop_at_first_event() {
+        let output = |keys: Vec<EventKey>| {
+            TransactionOutput::new(
+                WriteSet::default(),
+                keys.into_iter()
+                    .map(|key| ContractEvent::new(key, vec![]))
+                    .collect(),
+                0,
+                TransactionStatus::Keep(KeptVMStatus::Executed),
+            )
+        };
+        let outputs = vec![
+            output(vec![
+                on_chain_config::new_epoch_event_key(),
+                ElectionEvent::event_key(),
+            ]),
+            output(vec![RetireEvent::event_key()]),
+        ];
+
+        let first = BlockEventSummary {
+            has_election: true,
+            ..Default::default()
+        };
+        assert_eq!(BlockEventSummary::from_outputs(&outputs, true), first);
+        assert_eq!(
+            BlockEventSummary::from_outputs(&outputs, false),
+            BlockEventSummary {
+                has_retire: true,
+                ..first
+            }
+        );
+        assert_eq!(
+            BlockEventSummary::from_outputs(&outputs[..0], true),
+            BlockEventSummary::default()
+        );
+    }
+
+    #[test]
+    fn test_executor_error_conversion() {
+        let error: Error = ExecutorError::StaleCommit(5).into();
+        assert!(matches!(
+            error,
+            Error::ExecutorError(ExecutorError::StaleCommit(5))
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Try to commit stale transactions with the last version as 5"
+        );
+
+        // Callers using `anyhow` can still recover the variant.
+        let error = anyhow::Error::from(Error::from(
+            ExecutorError::MissingStakingTransactions,
+        ));
+        assert_eq!(
+            error.downcast_ref::<Error>(),
+            Some(&Error::ExecutorError(
+                ExecutorError::MissingStakingTransactions
+            ))
+        );
+
+        // Other failures of `process_vm_outputs` stay internal errors.
+        let error: Error = format_err!("ValidatorSet does not exist").into();
+        assert!(matches!(error, Error::InternalError { .. }));
+    }
+
+    fn validator_verifier(ids: &[u8]) -> ValidatorVerifier {
+        let validators = ids
+            .iter()
+            .map(|id| {
+                let signer = ValidatorSigner::from_int(*id);
+                let info = ValidatorConsensusInfo::new(
+                    signer.public_key(),
+                    signer.vrf_public_key(),
+                    1,
+                );
+                (signer.author(), info)
+            })
+            .collect();
+        ValidatorVerifier::new(validators)
+    }
+
+    #[test]
+    fn test_validator_set_diff() {
+        let address = |id| ValidatorSigner::from_int(id).author();
+        // Validator 2 leaves and validator 4 joins.
+        let from = validator_verifier(&[1, 2, 3]);
+        let to = validator_verifier(&[1, 3, 4]);
+        assert_eq!(ValidatorSetDiff::new(&from, &to), ValidatorSetDiff {
+            added: vec![address(4)],
+            removed: vec![address(2)],
+        });
+        assert_eq!(ValidatorSetDiff::new(&to, &from), ValidatorSetDiff {
+            added: vec![address(2)],
+            removed: vec![address(4)],
+        });
+        assert_eq!(
+            ValidatorSetDiff::new(&from, &from),
+            ValidatorSetDiff::default()
+        );
+    }
+
+    #[test]
+    fn test_executor_validator_set_diff() {
+        // The ledger info ending `epoch` sets the validators of the next one.
+        let ending = |epoch: u64, ids: &[u8]| {
+            let next_epoch_state = EpochState {
+                epoch: epoch + 1,
+                verifier: validator_verifier(ids),
+                vrf_seed: vec![],
+            };
+            LedgerInfoWithSignatures::new(
+                LedgerInfo::new(
+                    BlockInfo::new(
+                        epoch,
+                        1, /* round */
+                        HashValue::zero(),
+                        HashValue::zero(),
+                        0, /* version */
+                        0, /* timestamp_usecs */
+                        Some(next_epoch_state),
+                        None, /* pivot */
+                    ),
+                    HashValue::zero(),
+                ),
+                BTreeMap::new(),
+            )
+        };
+        let reader = MockEpochReader::new(vec![
+            ending(0, &[1, 2, 3]),
+            ending(1, &[1, 3, 4]),
+            ending(2, &[1, 3, 4]),
+        ]);
+        let (executor, _writer) = new_test_executor_with(
+            &[pos_signer(1)],
+            Arc::new(reader),
+            Arc::new(FakePowHandler {}),
+        );
+        let address = |id| ValidatorSigner::from_int(id).author();
+
+        let diff = ValidatorSetDiff {
+            added: vec![address(4)],
+            removed: vec![address(2)],
+        };
+        assert_eq!(executor.validator_set_diff(1, 2).unwrap(), diff);
+        assert_eq!(executor.validator_set_diff(1, 3).unwrap(), diff);
+        assert_eq!(
+            executor.validator_set_diff(2, 3).unwrap(),
+            ValidatorSetDiff::default()
+        );
+        assert_eq!(
+            executor.validator_set_diff(2, 2).unwrap(),
+            ValidatorSetDiff::default()
+        );
+
+        // Epoch 0 has no validators, and the range must not be reversed.
+        for (from_epoch, to_epoch) in &[(0, 2), (3, 1)] {
+            let error = executor
+                .validator_set_diff(*from_epoch, *to_epoch)
+                .unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                format!("Bad epoch range [{}, {}]", from_epoch, to_epoch)
+            );
+        }
+        // Epoch 3 has not ended, so the validators of epoch 4 are unknown.
+        assert_eq!(
+            executor.validator_set_diff(1, 4).unwrap_err().to_string(),
+            "No ledger info ends epoch 3"
+        );
+    }
+
+    #[test]
+    fn test_epoch_boundary() {
+        let output = |epoch_state| {
+            ProcessedVMOutput::new(
+                vec![],
+                ExecutedTrees::new_empty(),
+                epoch_state,
+                None, /* pivot_block */
+            )
+        };
+
+        let same_epoch = output(None);
+        assert!(!Executor::<FakeVM>::is_epoch_boundary(&same_epoch));
+        assert_eq!(
+            Executor::<FakeVM>::next_epoch_validators(&same_epoch),
+            None
+        );
+
+        let verifier = validator_verifier(&[1, 2, 3]);
+        let reconfiguration = output(Some(EpochState {
+            epoch: 2,
+            verifier: verifier.clone(),
+            vrf_seed: vec![],
+        }));
+        assert!(Executor::<FakeVM>::is_epoch_boundary(&reconfiguration));
+        assert_eq!(
+            Executor::<FakeVM>::next_epoch_validators(&reconfiguration),
+            Some(ValidatorSet::from(&verifier))
+        );
+    }
+
+    #[test]
+    fn test_next_membership_id() {
+        let compute_result = |epoch_state| {
+            ProcessedVMOutput::new(
+                vec![],
+                ExecutedTrees::new_empty(),
+                epoch_state,
+                None, /* pivot_block */
+            )
+            .compute_result(vec![], 0)
+        };
+
+        assert_eq!(compute_result(None).next_membership_id(), None);
+        let reconfiguration = compute_result(Some(EpochState {
+            epoch: 3,
+            verifier: validator_verifier(&[1, 2]),
+            vrf_seed: vec![],
+        }));
+        assert!(reconfiguration.has_reconfiguration());
+        assert_eq!(reconfiguration.next_membership_id(), Some(3));
+    }
+
+    #[test]
+    fn test_verify_commit_signatures() {
+        let verifier = validator_verifier(&[1, 2, 3, 4]);
+        let ledger_info = LedgerInfo::new(
+            BlockInfo::new(
+                1, /* epoch */
+                3, /* round */
+                HashValue::zero(),
+                HashValue::zero(),
+                0, /* version */
+                0, /* timestamp_usecs */
+                None, /* next_epoch_state */
+                None, /* pivot */
+            ),
+            HashValue::zero(),
+        );
+        let signed_by = |ids: &[u8]| {
+            let signatures = ids
+                .iter()
+                .map(|id| {
+                    let signer = ValidatorSigner::from_int(*id);
+                    (signer.author(), signer.sign(&ledger_info))
+                })
+                .collect();
+            LedgerInfoWithSignatures::new(ledger_info.clone(), signatures)
+        };
+
+        // Three of the four validators form a quorum.
+        let signed = signed_by(&[1, 2, 4]);
+        assert_eq!(verify_commit_signatures(&signed, Some(&verifier)), Ok(()));
+
+        let under_signed = signed_by(&[1, 2]);
+        assert!(matches!(
+            verify_commit_signatures(&under_signed, Some(&verifier)),
+            Err(ExecutorError::InvalidCommitSignatures(_))
+        ));
+        // An epoch without known validators can not be committed.
+        assert_eq!(
+            verify_commit_signatures(&signed, None),
+            Err(ExecutorError::UnknownCommitEpoch(1))
+        );
+
+        let genesis = LedgerInfoWithSignatures::genesis(
+            HashValue::zero(),
+            ValidatorSet::empty(),
+        );
+        assert!(genesis.signatures().is_empty());
+        assert_eq!(verify_commit_signatures(&genesis, Some(&verifier)), Ok(()));
+    }
+
+    /// A validator whose address is derived from its keys, as a PoS committee
+    /// member's is.
+    fn pos_signer(id: u8) -> ValidatorSigner {
+        let mut rng = StdRng::from_seed([id; 32]);
+        let private_key = ConsensusPrivateKey::generate(&mut rng);
+        let vrf_private_key = ConsensusVRFPrivateKey::generate(&mut rng);
+        let author = from_consensus_public_key(
+            &private_key.public_key(),
+            &vrf_private_key.public_key(),
+        );
+        ValidatorSigner::new(author, private_key, Some(vrf_private_key))
+    }
+
+    /// Returns the same round-0 block for every block id.
+    struct MockLedgerBlockDB {
+        block: Block,
+    }
+
+    impl LedgerBlockRW for MockLedgerBlockDB {
+        fn get_ledger_block(
+            &self, _block_id: &HashValue,
+        ) -> anyhow::Result<Option<Block>> {
+            Ok(Some(self.block.clone()))
+        }
+
+        fn get_qc_for_block(
+            &self, _block_id: &HashValue,
+        ) -> anyhow::Result<Option<QuorumCert>> {
+            Ok(Some(self.block.quorum_cert().clone()))
+        }
+    }
+
+    /// Accepts the proposed pivot decisions if `valid_pivot_decision` is set,
+    /// and returns `staking_events` as the PoW staking events between them.
+    struct MockPowHandler {
+        valid_pivot_decision: bool,
+        staking_events: fn() -> Vec<StakingEvent>,
+    }
+
+    #[async_trait::async_trait]
+    impl PowInterface for MockPowHandler {
+        async fn next_pivot_decision(
+            &self, _parent_decision: H256,
+        ) -> Option<(u64, H256)> {
+            unimplemented!()
+        }
+
+        fn validate_proposal_pivot_decision(
+            &self, _parent_decision: H256, _me_decision: H256,
+        ) -> bool {
+            self.valid_pivot_decision
+        }
+
+        fn get_staking_events(
+            &self, _parent_height: u64, _me_height: u64,
+            _parent_decision: H256, _me_decision: H256,
+        ) -> anyhow::Result<Vec<StakingEvent>>
+        {
+            Ok((self.staking_events)())
+        }
+
+        async fn wait_for_initialization(&self, _last_decision: H256) {
+            unimplemented!()
+        }
+    }
+
+    /// An executor with nothing committed yet, whose committed PoS state is in
+    /// epoch 1 with `signers` as the committee, each with one vote.
+    fn new_test_executor(
+        signers: &[ValidatorSigner],
+    ) -> (Executor<FakeVM>, Arc<MockDbWriter>) {
+        new_test_executor_with(
+            signers,
+            Arc::new(MockDbReader),
+            Arc::new(FakePowHandler {}),
+        )
+    }
+
+    /// Like `new_test_executor`, but reads the persistent data from `reader`
+    /// and checks the pivot decisions and staking events with `pow_handler`.
+    fn new_test_executor_with(
+        signers: &[ValidatorSigner], reader: Arc<dyn DbReader>,
+        pow_handler: Arc<dyn PowInterface>,
+    ) -> (Executor<FakeVM>, Arc<MockDbWriter>)
+    {
+        POS_STATE_CONFIG.get_or_init(Default::default);
+        let nodes = signers
+            .iter()
+            .map(|signer| {
+                let node_id = NodeID::new(
+                    signer.public_key(),
+                    signer.vrf_public_key().unwrap(),
+                );
+                (node_id, 1)
+            })
+            .collect();
+        let committee =
+            signers.iter().map(|signer| (signer.author(), 1)).collect();
+        let mut pos_state =
+            PosState::new(vec![], nodes, committee, pivot_decision(0));
+        pos_state.next_view().unwrap();
+
+        let writer = Arc::new(MockDbWriter::default());
+        let db_with_cache = CachedDiemDB {
+            db: DbReaderWriter {
+                reader,
+                writer: writer.clone(),
+            },
+            cache: Mutex::new(SpeculationCache::new_for_db_bootstrapping(
+                TreeState::new(0, vec![], *SPARSE_MERKLE_PLACEHOLDER_HASH),
+                pos_state,
+            )),
+        };
+        // Move the root off the pre-genesis block so that the executed blocks
+        // inherit the committed pivot decision.
+        let root_ledger_info =
+            LedgerInfo::new(BlockInfo::empty(), HashValue::zero());
+        let committed_trees =
+            db_with_cache.cache.lock().committed_trees().clone();
+        db_with_cache.update_block_tree_root(
+            committed_trees,
+            &root_ledger_info,
+            vec![],
+            vec![],
+        );
+        let consensus_db = MockLedgerBlockDB {
+            block: Block::make_genesis_block_from_ledger_info(
+                &root_ledger_info,
+            ),
+        };
+        let executor = Executor::new(
+            Arc::new(db_with_cache),
+            pow_handler,
+            Arc::new(consensus_db),
+        );
+        (executor, writer)
+    }
+
+    fn metadata_block(id: u8) -> (HashValue, Vec<Transaction>) {
+        let metadata = BlockMetadata::new(
+            HashValue::zero(),
+            1, /* round */
+            0, /* timestamp */
+            vec![],
+            AccountAddress::random(),
+        );
+        (
+            HashValue::new([id; HashValue::LENGTH]),
+            vec![Transaction::BlockMetadata(metadata)],
+        )
+    }
+
+    /// A block whose transaction selects `pivot_decisions`.
+    fn pivot_decision_block(
+        id: u8, pivot_decisions: &[PivotBlockDecision],
+    ) -> (HashValue, Vec<Transaction>) {
+        let events = pivot_decisions
+            .iter()
+            .map(PivotBlockDecision::to_event)
+            .collect();
+        let change_set = ChangeSet::new(WriteSet::default(), events);
+        (
+            HashValue::new([id; HashValue::LENGTH]),
+            vec![Transaction::GenesisTransaction(WriteSetPayload::Direct(
+                change_set,
+            ))],
+        )
+    }
+
+    /// A ledger info of epoch 1 committing `block_id` at `version`, signed by
+    /// `signers`.
+    fn commit_ledger_info(
+        block_id: HashValue, version: Version,
+        pivot_decision: Option<PivotBlockDecision>,
+        signers: &[ValidatorSigner],
+    ) -> LedgerInfoWithSignatures
+    {
+        let ledger_info = LedgerInfo::new(
+            BlockInfo::new(
+                1, /* epoch */
+                1, /* round */
+                block_id,
+                HashValue::zero(),
+                version,
+                0,    /* timestamp_usecs */
+                None, /* next_epoch_state */
+                pivot_decision,
+            ),
+            HashValue::zero(),
+        );
+        let signatures = signers
+            .iter()
+            .map(|signer| (signer.author(), signer.sign(&ledger_info)))
+            .collect();
+        LedgerInfoWithSignatures::new(ledger_info, signatures)
+    }
+
+    #[test]
+    fn test_execute_block_pivot_decision_errors() {
+        let execute = |pow_handler: MockPowHandler, pivot_decisions: &[_]| {
+            let (executor, _writer) = new_test_executor_with(
+                &[pos_signer(1)],
+                Arc::new(MockDbReader),
+                Arc::new(pow_handler),
+            );
+            let parent_block_id = executor.committed_block_id();
+            executor.execute_block(
+                pivot_decision_block(1, pivot_decisions),
+                parent_block_id,
+                false, /* catch_up_mode */
+            )
+        };
+        let valid = || MockPowHandler {
+            valid_pivot_decision: true,
+            staking_events: Vec::new,
+        };
+
+        assert!(execute(valid(), &[pivot_decision(1)]).is_ok());
+        assert_eq!(
+            execute(valid(), &[pivot_decision(1), pivot_decision(2)]),
+            Err(Error::ExecutorError(ExecutorError::MultiplePivotDecisions))
+        );
+        // The PoW side does not accept the pivot decision.
+        let invalid = MockPowHandler {
+            valid_pivot_decision: false,
+            staking_events: Vec::new,
+        };
+        assert_eq!(
+            execute(invalid, &[pivot_decision(1)]),
+            Err(Error::ExecutorError(ExecutorError::InvalidPivotDecision))
+        );
+        // A staking event before the pivot decision is not packed.
+        let with_staking_event = MockPowHandler {
+            valid_pivot_decision: true,
+            staking_events: || vec![retire_events().1],
+        };
+        assert_eq!(
+            execute(with_staking_event, &[pivot_decision(1)]),
+            Err(Error::ExecutorError(
+                ExecutorError::MissingStakingTransactions
+            ))
+        );
+    }
+
+    #[test]
+    fn test_commit_blocks_errors() {
+        let signers = vec![pos_signer(1)];
+        let (executor, writer) = new_test_executor(&signers);
+        assert_eq!(
+            executor.commit_blocks(
+                vec![],
+                commit_ledger_info(
+                    executor.committed_block_id(),
+                    0, /* version */
+                    Some(pivot_decision(0)),
+                    &signers,
+                ),
+            ),
+            Err(Error::ExecutorError(ExecutorError::EmptyBlockBatch))
+        );
+
+        // Commit two blocks with a transaction each, i.e. up to version 1.
+        let mut parent_block_id = executor.committed_block_id();
+        let mut last_result = None;
+        for id in 1..=2 {
+            let (block_id, transactions) = metadata_block(id);
+            let result = executor
+                .execute_block(
+                    (block_id, transactions),
+                    parent_block_id,
+                    false, /* catch_up_mode */
+                )
+                .unwrap();
+            parent_block_id = block_id;
+            last_result = Some(result);
+        }
+        let last_result = last_result.unwrap();
+        assert_eq!(last_result.version(), 1);
+        let committed = commit_ledger_info(
+            parent_block_id,
+            last_result.version(),
+            last_result.pivot_decision().clone(),
+            &signers,
+        );
+        executor
+            .commit_blocks(
+                vec![metadata_block(1).0, metadata_block(2).0],
+                committed,
+            )
+            .unwrap();
+        assert_eq!(writer.commits(), 1);
+
+        // The transaction at version 0 is already committed.
+        let stale = commit_ledger_info(
+            parent_block_id,
+            0, /* version */
+            last_result.pivot_decision().clone(),
+            &signers,
+        );
+        assert_eq!(
+            executor.commit_blocks(vec![parent_block_id], stale),
+            Err(Error::ExecutorError(ExecutorError::StaleCommit(0)))
+        );
+        assert_eq!(writer.commits(), 1);
+    }
+
+    #[test]
+    fn test_execute_block_reuses_cached_output() {
+        let (executor, _writer) = new_test_executor(&[pos_signer(1)]);
+        let parent_block_id = executor.committed_block_id();
+        let block = metadata_block(1);
+
+        let result = executor
+            .execute_block(block.clone(), parent_block_id, false)
+            .unwrap();
+        assert_eq!(executor.output_cache_hits(), 0);
+        // The same block on the same parent is not executed again.
+        assert_eq!(
+            executor
+                .execute_block(block, parent_block_id, false)
+                .unwrap(),
+            result
+        );
+        assert_eq!(executor.output_cache_hits(), 1);
+    }
+
+    #[test]
+    fn test_commit_blocks_signatures() {
+        let signers: Vec<_> = (1..=4).map(pos_signer).collect();
+        let (executor, writer) = new_test_executor(&signers);
+        let (block_id, transactions) = metadata_block(1);
+        let result = executor
+            .execute_block(
+                (block_id, transactions),
+                executor.committed_block_id(),
+                false, /* catch_up_mode */
+            )
+            .unwrap();
+        let ledger_info = |epoch| {
+            LedgerInfo::new(
+                BlockInfo::new(
+                    epoch,
+                    1, /* round */
+                    block_id,
+                    result.root_hash(),
+                    result.version(),
+                    0, /* timestamp_usecs */
+                    None, /* next_epoch_state */
+                    result.pivot_decision().clone(),
+                ),
+                HashValue::zero(),
+            )
+        };
+        let signed_by = |ledger_info: LedgerInfo, signers: &[ValidatorSigner]| {
+            let signatures = signers
+                .iter()
+                .map(|signer| (signer.author(), signer.sign(&ledger_info)))
+                .collect();
+            LedgerInfoWithSignatures::new(ledger_info, signatures)
+        };
+        let commit = |ledger_info_with_sigs| {
+            executor.commit_blocks(vec![block_id], ledger_info_with_sigs)
+        };
+
+        // Two of the four validators are not a quorum.
+        assert!(matches!(
+            commit(signed_by(ledger_info(1), &signers[..2])),
+            Err(Error::ExecutorError(ExecutorError::InvalidCommitSignatures(
+                _
+            )))
+        ));
+        // The validators of epoch 2 are unknown, however it is signed.
+        assert!(matches!(
+            commit(signed_by(ledger_info(2), &signers)),
+            Err(Error::ExecutorError(ExecutorError::UnknownCommitEpoch(2)))
+        ));
+        assert_eq!(writer.commits(), 0);
+
+        assert!(commit(signed_by(ledger_info(1), &signers[..3])).is_ok());
+        assert_eq!(writer.commits(), 1);
+        assert_eq!(executor.committed_block_id(), block_id);
+    }
+
+    #[test]
+    fn test_commit_genesis_without_signatures() {
+        let (executor, writer) = new_test_executor(&[pos_signer(1)]);
+        let (block_id, transactions) = metadata_block(1);
+        let result = executor
+            .execute_block(
+                (block_id, transactions),
+                executor.committed_block_id(),
+                false, /* catch_up_mode */
+            )
+            .unwrap();
+        let genesis = LedgerInfoWithSignatures::new(
+            LedgerInfo::new(
+                BlockInfo::new(
+                    GENESIS_EPOCH,
+                    GENESIS_ROUND,
+                    block_id,
+                    result.root_hash(),
+                    result.version(),
+                    GENESIS_TIMESTAMP_USECS,
+                    None, /* next_epoch_state */
+                    Some(pivot_decision(0)),
+                ),
+                HashValue::zero(),
+            ),
+            BTreeMap::new(),
+        );
+
+        assert!(executor.commit_blocks(vec![block_id], genesis).is_ok());
+        assert_eq!(writer.commits(), 1);
+    }
+}
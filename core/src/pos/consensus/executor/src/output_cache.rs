@@ -0,0 +1,108 @@
+// Copyright 2021 Conflux Foundation. All rights reserved.
+// Conflux is free software and distributed under GNU General Public License.
+// See http://www.gnu.org/licenses/
+
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use diem_crypto::HashValue;
+use diem_infallible::Mutex;
+use executor_types::ProcessedVMOutput;
+use lru_cache::LruCache;
+
+/// The number of block outputs kept by `Executor` for re-execution.
+pub const DEFAULT_OUTPUT_CACHE_CAPACITY: usize = 32;
+
+/// Identifies one execution of a block. The same block executed on another
+/// parent or in another PoS term produces a different output.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct OutputCacheKey {
+    pub parent_id: HashValue,
+    pub id: HashValue,
+    pub membership_id: u64,
+}
+
+/// A bounded cache of executed block outputs. The least recently used output
+/// is evicted when the cache is full.
+pub struct OutputCache {
+    outputs: Mutex<LruCache<OutputCacheKey, Arc<ProcessedVMOutput>>>,
+    hits: AtomicU64,
+}
+
+impl OutputCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            outputs: Mutex::new(LruCache::new(capacity)),
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, key: &OutputCacheKey) -> Option<Arc<ProcessedVMOutput>> {
+        let output = self.outputs.lock().get_mut(key).cloned();
+        if output.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+        output
+    }
+
+    pub fn insert(&self, key: OutputCacheKey, output: Arc<ProcessedVMOutput>) {
+        self.outputs.lock().insert(key, output);
+    }
+
+    /// The number of lookups that found a cached output.
+    pub fn hits(&self) -> u64 { self.hits.load(Ordering::Relaxed) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use executor_types::ExecutedTrees;
+
+    fn key(id: u8, membership_id: u64) -> OutputCacheKey {
+        OutputCacheKey {
+            parent_id: HashValue::zero(),
+            id: HashValue::new([id; HashValue::LENGTH]),
+            membership_id,
+        }
+    }
+
+    fn output() -> Arc<ProcessedVMOutput> {
+        Arc::new(ProcessedVMOutput::new(
+            vec![],
+            ExecutedTrees::new_empty(),
+            None,
+            None,
+        ))
+    }
+
+    #[test]
+    fn test_output_cache_hit() {
+        let cache = OutputCache::new(DEFAULT_OUTPUT_CACHE_CAPACITY);
+        assert!(cache.get(&key(1, 1)).is_none());
+
+        cache.insert(key(1, 1), output());
+        assert!(cache.get(&key(1, 1)).is_some());
+        assert!(cache.get(&key(1, 1)).is_some());
+        assert_eq!(cache.hits(), 2);
+
+        // The same block in another term is executed again.
+        assert!(cache.get(&key(1, 2)).is_none());
+        assert_eq!(cache.hits(), 2);
+    }
+
+    #[test]
+    fn test_output_cache_eviction() {
+        let cache = OutputCache::new(2);
+        cache.insert(key(1, 1), output());
+        cache.insert(key(2, 1), output());
+        // Use the first block so the second one is the oldest.
+        assert!(cache.get(&key(1, 1)).is_some());
+        cache.insert(key(3, 1), output());
+
+        assert!(cache.get(&key(2, 1)).is_none());
+        assert!(cache.get(&key(1, 1)).is_some());
+        assert!(cache.get(&key(3, 1)).is_some());
+    }
+}
@@ -5,9 +5,14 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use std::{mem::Discriminant, time::Duration};
+use std::{
+    collections::HashMap, mem::Discriminant, pin::Pin, sync::Arc,
+    time::Duration,
+};
 
 use anyhow::{anyhow, bail, ensure, format_err};
+use futures::{Future, FutureExt};
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 
 use channel::{self, diem_channel, message_queues::QueueStyle};
@@ -38,7 +43,10 @@ use crate::{
     },
 };
 
-use super::counters;
+use super::{
+    counters,
+    util::time_service::{ScheduledTask, TimeService},
+};
 
 /// Network type for consensus
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -86,12 +94,94 @@ pub struct NetworkReceivers {
         diem_channel::Receiver<AccountAddress, IncomingBlockRetrievalRequest>,
 }
 
+/// The delays of the outbound messages to some peers, injected by
+/// `TestCommand::DelayMessagesTo` to construct partition and latency cases.
+///
+/// A delayed message is handed to `time_service` with `run_after` and sent
+/// when the task runs, so the consensus thread is never blocked. With
+/// `ClockTimeService` the task is spawned on the runtime and sleeps for the
+/// delay. With `SimulatedTimeService` the message is sent only when the time
+/// limit is advanced past its deadline. Clearing the delays does not cancel
+/// the messages scheduled already.
+#[derive(Clone)]
+pub struct MessageDelays {
+    delays: Arc<RwLock<HashMap<Author, Duration>>>,
+    time_service: Arc<dyn TimeService>,
+}
+
+impl MessageDelays {
+    /// Creates the delays with no peer delayed.
+    pub fn new(time_service: Arc<dyn TimeService>) -> Self {
+        MessageDelays {
+            delays: Default::default(),
+            time_service,
+        }
+    }
+
+    /// Delays the messages sent to `peer` from now on by `delay`.
+    pub fn set(&self, peer: Author, delay: Duration) {
+        self.delays.write().insert(peer, delay);
+    }
+
+    /// Sends the later messages to all peers without delay.
+    pub fn clear(&self) { self.delays.write().clear() }
+
+    /// The peers whose messages are delayed.
+    pub fn delayed_peers(&self) -> Vec<Author> {
+        self.delays.read().keys().cloned().collect()
+    }
+
+    /// Calls `send` now if the messages to `peer` are not delayed, or
+    /// schedules it after the delay otherwise. The error of a scheduled
+    /// `send` is only logged.
+    pub fn send_to<F>(&self, peer: Author, send: F) -> anyhow::Result<()>
+    where F: FnOnce() -> anyhow::Result<()> + Send + 'static {
+        let delay = self.delays.read().get(&peer).cloned();
+        match delay {
+            Some(delay) => {
+                self.time_service.run_after(
+                    delay,
+                    Box::new(DelayedSendTask {
+                        peer,
+                        send: Some(Box::new(send)),
+                    }),
+                );
+                Ok(())
+            }
+            None => send(),
+        }
+    }
+}
+
+/// The task scheduled by `MessageDelays::send_to` for a delayed message.
+struct DelayedSendTask {
+    peer: Author,
+    send: Option<Box<dyn FnOnce() -> anyhow::Result<()> + Send>>,
+}
+
+impl ScheduledTask for DelayedSendTask {
+    fn run(&mut self) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        if let Some(send) = self.send.take() {
+            if let Err(e) = send() {
+                diem_error!(
+                    remote_peer = self.peer,
+                    error = ?e, "Failed to send a delayed message to peer",
+                );
+            }
+        }
+        async {}.boxed()
+    }
+}
+
 /// Implements the actual networking support for all consensus messaging.
 #[derive(Clone)]
 pub struct ConsensusNetworkSender {
     pub author: Author,
     network_sender: NetworkSender,
     validators: ValidatorVerifier,
+    /// Set by `EpochManager` so that the direct-send messages can be delayed
+    /// by test commands. RPCs are never delayed.
+    message_delays: Option<MessageDelays>,
 }
 
 impl ConsensusNetworkSender {
@@ -104,11 +194,29 @@ impl ConsensusNetworkSender {
             author,
             network_sender,
             validators,
+            message_delays: None,
         }
     }
 
     pub fn network_sender(&self) -> &NetworkSender { &self.network_sender }
 
+    /// Delays the messages to peers according to `message_delays`.
+    pub fn set_message_delays(&mut self, message_delays: MessageDelays) {
+        self.message_delays = Some(message_delays);
+    }
+
+    /// Sends `msg` to `recipient`, after the delay set for it if any.
+    fn send_to(
+        &self, recipient: Author, msg: ConsensusMsg,
+    ) -> anyhow::Result<()> {
+        let mut network_sender = self.network_sender.clone();
+        let send = move || network_sender.send_to(recipient, &msg);
+        match &self.message_delays {
+            Some(message_delays) => message_delays.send_to(recipient, send),
+            None => send(),
+        }
+    }
+
     /// Tries to retrieve num of blocks backwards starting from id from the
     /// given peer: the function returns a future that is fulfilled with
     /// BlockRetrievalResponse.
@@ -222,11 +330,29 @@ impl ConsensusNetworkSender {
             diem_error!(error = ?err, "Error broadcasting message");
         }
          */
+        // The delayed peers are sent to one by one after their delays.
+        let delayed_peers: Vec<_> = self
+            .message_delays
+            .as_ref()
+            .map_or(vec![], MessageDelays::delayed_peers)
+            .into_iter()
+            .filter(|peer| *peer != self.author && !exclude.contains(peer))
+            .collect();
+        let mut exclude = exclude;
+        exclude.extend(delayed_peers.iter().cloned());
         // TODO(lpl): It may be sufficient to broadcast some messages to only
         // validators.
         if let Err(err) = self.network_sender.send_to_others(&msg, &exclude) {
             diem_error!(error = ?err, "Error broadcasting message");
         }
+        for peer in delayed_peers {
+            if let Err(err) = self.send_to(peer, msg.clone()) {
+                diem_error!(
+                    remote_peer = peer,
+                    error = ?err, "Error broadcasting message to peer",
+                );
+            }
+        }
     }
 
     /// Sends the vote to the chosen recipients (typically that would be the
@@ -240,7 +366,7 @@ impl ConsensusNetworkSender {
     /// about when the message is delivered to the recipients, as well as
     /// there is no indication about the network failures.
     pub async fn send_vote(&self, vote_msg: VoteMsg, recipients: Vec<Author>) {
-        let mut network_sender = self.network_sender.clone();
+        let network_sender = self.network_sender.clone();
         let msg = ConsensusMsg::VoteMsg(Box::new(vote_msg));
         for peer in recipients {
             if self.author == peer {
@@ -251,7 +377,7 @@ impl ConsensusNetworkSender {
                 }
                 continue;
             }
-            if let Err(e) = network_sender.send_to(peer, &msg) {
+            if let Err(e) = self.send_to(peer, msg.clone()) {
                 diem_error!(
                     remote_peer = peer,
                     error = ?e, "Failed to send a vote to peer",
@@ -266,8 +392,7 @@ impl ConsensusNetworkSender {
     /// or sent out).
     pub fn send_sync_info(&self, sync_info: SyncInfo, recipient: Author) {
         let msg = ConsensusMsg::SyncInfo(Box::new(sync_info));
-        let mut network_sender = self.network_sender.clone();
-        if let Err(e) = network_sender.send_to(recipient, &msg) {
+        if let Err(e) = self.send_to(recipient, msg) {
             diem_warn!(
                 remote_peer = recipient,
                 error = "Failed to send a sync info msg to peer {:?}",
@@ -319,3 +444,46 @@ impl NetworkTask {
     /// start
     pub async fn start(self) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::MessageDelays;
+    use crate::pos::consensus::util::mock_time_service::SimulatedTimeService;
+    use diem_types::account_address::AccountAddress;
+    use parking_lot::Mutex;
+    use std::{sync::Arc, time::Duration};
+
+    #[test]
+    fn test_message_delays() {
+        let mut time_service = SimulatedTimeService::new();
+        let delays = MessageDelays::new(Arc::new(time_service.clone()));
+        let delayed_peer = AccountAddress::random();
+        let other_peer = AccountAddress::random();
+        delays.set(delayed_peer, Duration::from_millis(100));
+        assert_eq!(delays.delayed_peers(), vec![delayed_peer]);
+
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let send_to = |peer| {
+            let sent = sent.clone();
+            delays
+                .send_to(peer, move || {
+                    sent.lock().push(peer);
+                    Ok(())
+                })
+                .unwrap();
+        };
+        send_to(delayed_peer);
+        send_to(other_peer);
+        assert_eq!(*sent.lock(), vec![other_peer]);
+
+        time_service.update_auto_advance_limit(Duration::from_millis(99));
+        assert_eq!(*sent.lock(), vec![other_peer]);
+        time_service.update_auto_advance_limit(Duration::from_millis(1));
+        assert_eq!(*sent.lock(), vec![other_peer, delayed_peer]);
+
+        delays.clear();
+        assert!(delays.delayed_peers().is_empty());
+        send_to(delayed_peer);
+        assert_eq!(*sent.lock(), vec![other_peer, delayed_peer, delayed_peer]);
+    }
+}
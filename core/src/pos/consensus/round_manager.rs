@@ -5,7 +5,7 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use std::{sync::Arc, time::Duration};
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
 
 use anyhow::{bail, ensure, Context, Result};
 use fail::fail_point;
@@ -229,6 +229,9 @@ pub struct RoundManager {
         oneshot::Sender<anyhow::Result<SubmissionStatus>>,
     )>,
     chain_id: ChainId,
+    /// The rounds in which the node does not send its own vote, set by
+    /// `drop_vote` in tests.
+    dropped_vote_rounds: BTreeSet<Round>,
 }
 
 impl RoundManager {
@@ -263,6 +266,7 @@ impl RoundManager {
             sync_only,
             tx_sender,
             chain_id,
+            dropped_vote_rounds: BTreeSet::new(),
         }
     }
 
@@ -288,6 +292,8 @@ impl RoundManager {
         &mut self, new_round_event: NewRoundEvent,
     ) -> anyhow::Result<()> {
         counters::CURRENT_ROUND.set(new_round_event.round as i64);
+        self.dropped_vote_rounds =
+            self.dropped_vote_rounds.split_off(&new_round_event.round);
         counters::ROUND_TIMEOUT_MS
             .set(new_round_event.timeout.as_millis() as i64);
         match new_round_event.reason {
@@ -724,6 +730,18 @@ impl RoundManager {
             return Ok(());
         }
 
+        if self.broadcast_timeout_vote(round).await? {
+            bail!("Round {} timeout, broadcast to all peers", round);
+        }
+        Ok(())
+    }
+
+    /// Broadcast the sync info and, if no certificate has been formed for
+    /// `round`, a timeout vote as described in `process_local_timeout`.
+    /// Return `Ok(true)` if the timeout vote is broadcasted.
+    async fn broadcast_timeout_vote(
+        &mut self, round: Round,
+    ) -> anyhow::Result<bool> {
         self.network
             .broadcast(
                 ConsensusMsg::SyncInfo(Box::new(self.block_store.sync_info())),
@@ -738,7 +756,7 @@ impl RoundManager {
             VoteReceptionResult::NewQuorumCertificate(_)
             | VoteReceptionResult::NewTimeoutCertificate(_) => {
                 // Certificate formed, so do not send timeout vote.
-                return Ok(());
+                return Ok(false);
             }
             _ => {
                 // No certificate formed, so enter normal timeout processing.
@@ -746,7 +764,7 @@ impl RoundManager {
         }
 
         if !self.is_validator() {
-            return Ok(());
+            return Ok(false);
         }
 
         let (use_last_vote, mut timeout_vote) =
@@ -792,7 +810,7 @@ impl RoundManager {
             voted = use_last_vote,
             event = LogEvent::Timeout,
         );
-        bail!("Round {} timeout, broadcast to all peers", round);
+        Ok(true)
     }
 
     pub async fn process_proposal_timeout(
@@ -804,7 +822,8 @@ impl RoundManager {
         }
         if let Some(proposal) = self.proposer_election.choose_proposal_to_vote()
         {
-            if self.is_validator() {
+            if self.is_validator() && !self.dropped_vote_rounds.contains(&round)
+            {
                 // Vote for proposal
                 let vote = self
                     .execute_and_vote(proposal)
@@ -822,7 +841,8 @@ impl RoundManager {
                     .await;
                 Ok(())
             } else {
-                // Not a validator, just execute the block and wait for votes.
+                // Not a validator or the vote is dropped, just execute the
+                // block and wait for votes.
                 self.block_store
                     .execute_and_insert_block(proposal, false, false)
                     .context(
@@ -909,6 +929,15 @@ impl RoundManager {
             }
         } else {
             let proposal_round = proposal.round();
+            if self.dropped_vote_rounds.contains(&proposal_round) {
+                diem_debug!("Drop the vote in round {}", proposal_round);
+                self.block_store
+                    .execute_and_insert_block(proposal, false, false)
+                    .context(
+                        "[RoundManager] Failed to execute_and_insert the block",
+                    )?;
+                return Ok(false);
+            }
             let vote = self
                 .execute_and_vote(proposal)
                 .await
@@ -1363,6 +1392,38 @@ impl RoundManager {
         Ok(())
     }
 
+    /// Force the node to broadcast a timeout for the current round `round`
+    /// without waiting for its round timer. The round timer is not reset.
+    pub async fn force_timeout(&mut self, round: Round) -> Result<()> {
+        ensure!(
+            round == self.round_state.current_round(),
+            "force_timeout: round {} is not the current round {}",
+            round,
+            self.round_state.current_round()
+        );
+        let broadcasted = self.broadcast_timeout_vote(round).await?;
+        diem_debug!(
+            "force_timeout: round={}, broadcasted={}",
+            round,
+            broadcasted
+        );
+        Ok(())
+    }
+
+    /// Make the node not send its own vote in `round`. It has no effect on a
+    /// vote that has been sent.
+    pub fn drop_vote(&mut self, round: Round) -> Result<()> {
+        ensure!(
+            round >= self.round_state.current_round(),
+            "drop_vote: round {} is before the current round {}",
+            round,
+            self.round_state.current_round()
+        );
+        diem_debug!("drop_vote: round={}", round);
+        self.dropped_vote_rounds.insert(round);
+        Ok(())
+    }
+
     pub async fn force_sign_pivot_decision(
         &mut self, pivot_decision: PivotBlockDecision,
     ) -> anyhow::Result<()> {
@@ -5,7 +5,10 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use consensus_types::{block::Block, common::Round};
+use consensus_types::{
+    block::Block,
+    common::{Author, Round},
+};
 use diem_crypto::HashValue;
 use diem_types::{
     block_info::PivotBlockDecision, transaction::TransactionPayload,
@@ -41,6 +44,36 @@ pub enum TestCommand {
     LocalTimeout,
     /// Trigger new_round_timeout
     NewRoundTimeout,
+    /// Make the node sign and broadcast a timeout for the given round without
+    /// waiting for its round timer. The round must be the current round. The
+    /// timeout is attached to the vote of this round if the node has voted,
+    /// otherwise to a vote for a NIL block. It does nothing if a QC or TC has
+    /// already been formed for the round.
+    ForceTimeout {
+        /// The round to time out.
+        round: Round,
+    },
+    /// Make the node not send its own vote for proposals in the given round.
+    /// It has no effect if the node has already voted in this round. Timeout
+    /// votes are still sent, and the setting is dropped once the node enters
+    /// a later round.
+    DropVote {
+        /// The round in which the node does not vote.
+        round: Round,
+    },
+    /// Delay the messages sent to the given peer. The messages are scheduled
+    /// on the time service of the node instead of being sent in place, so
+    /// they are not sent before the simulated time is advanced if a
+    /// `SimulatedTimeService` is used. RPCs are not delayed.
+    DelayMessagesTo {
+        /// The peer whose messages are delayed.
+        peer: Author,
+        /// The delay in milliseconds, which replaces the previous one.
+        millis: u64,
+    },
+    /// Stop delaying the later messages to all peers. The messages delayed
+    /// already are still sent after their delays.
+    ClearDelays,
     /// Sign and broadcast a pivot decision transaction
     BroadcastPivotDecision(PivotBlockDecision),
     /// Sign and broadcast an election transaction with a target term
@@ -148,6 +148,16 @@ pub enum Error {
 
     #[error("Serialization error: {0}")]
     SerializationError(String),
+
+    #[error(
+        "The reader is at version {} behind the requested version {}",
+        reader_version,
+        requested_version
+    )]
+    StaleReader {
+        requested_version: Version,
+        reader_version: Version,
+    },
 }
 
 impl From<anyhow::Error> for Error {
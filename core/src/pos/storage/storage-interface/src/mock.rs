@@ -5,22 +5,29 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-//! This module provides mock dbreader for tests.
+//! This module provides mock dbreader and dbwriter for tests.
 
-use crate::{DBReaderForPoW, DbReader, StartupInfo, TreeState};
-use anyhow::Result;
-use diem_crypto::HashValue;
+use crate::{DBReaderForPoW, DbReader, DbWriter, StartupInfo, TreeState};
+use anyhow::{bail, Result};
+use diem_crypto::{hash::CryptoHash, HashValue};
 use diem_types::{
-    account_address::AccountAddress,
+    account_address::{AccountAddress, HashAccountAddress},
     account_state_blob::{AccountStateBlob, AccountStateWithProof},
     committed_block::CommittedBlock,
     contract_event::ContractEvent,
     epoch_change::EpochChangeProof,
     ledger_info::LedgerInfoWithSignatures,
-    proof::{AccumulatorConsistencyProof, SparseMerkleProof},
+    proof::{
+        AccumulatorConsistencyProof, SparseMerkleLeafNode, SparseMerkleProof,
+    },
     reward_distribution_event::RewardDistributionEvent,
-    transaction::{TransactionListWithProof, TransactionWithProof, Version},
+    term_state::PosState,
+    transaction::{
+        TransactionListWithProof, TransactionToCommit, TransactionWithProof,
+        Version,
+    },
 };
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// This is a mock of the dbreader in tests.
 pub struct MockDbReader;
@@ -164,3 +171,313 @@ impl DBReaderForPoW for MockDbReader {
         todo!()
     }
 }
+
+/// This is a mock of the dbreader whose persistent state only has the account
+/// `address`. The proofs it returns verify against `root_hash`, and it counts
+/// the account reads.
+pub struct MockAccountReader {
+    address: AccountAddress,
+    blob: AccountStateBlob,
+    latest_version: Version,
+    reads: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl MockAccountReader {
+    pub fn new(address: AccountAddress, blob: AccountStateBlob) -> Self {
+        MockAccountReader {
+            address,
+            blob,
+            latest_version: 0,
+            reads: AtomicUsize::new(0),
+            failures: AtomicUsize::new(0),
+        }
+    }
+
+    /// Make the first `failures` calls to
+    /// `get_account_state_with_proof_by_version` return an error.
+    pub fn with_failures(self, failures: usize) -> Self {
+        self.failures.store(failures, Ordering::SeqCst);
+        self
+    }
+
+    /// Set the version returned by `get_latest_state_root`, which is 0 by
+    /// default.
+    pub fn with_latest_version(mut self, latest_version: Version) -> Self {
+        self.latest_version = latest_version;
+        self
+    }
+
+    /// The only account in the persistent state.
+    pub fn address(&self) -> AccountAddress { self.address }
+
+    /// The state root hash of the tree with the only account.
+    pub fn root_hash(&self) -> HashValue { self.leaf().hash() }
+
+    /// The number of calls to `get_account_state_with_proof_by_version`.
+    pub fn reads(&self) -> usize { self.reads.load(Ordering::SeqCst) }
+
+    fn leaf(&self) -> SparseMerkleLeafNode {
+        SparseMerkleLeafNode::new(self.address.hash(), self.blob.hash())
+    }
+}
+
+impl DbReader for MockAccountReader {
+    fn get_epoch_ending_ledger_infos(
+        &self, _start_epoch: u64, _end_epoch: u64,
+    ) -> Result<EpochChangeProof> {
+        unimplemented!()
+    }
+
+    fn get_transactions(
+        &self, _start_version: Version, _batch_size: u64,
+        _ledger_version: Version, _fetch_events: bool,
+    ) -> Result<TransactionListWithProof>
+    {
+        unimplemented!()
+    }
+
+    fn get_block_timestamp(&self, _version: u64) -> Result<u64> {
+        unimplemented!()
+    }
+
+    fn get_latest_account_state(
+        &self, _address: AccountAddress,
+    ) -> Result<Option<AccountStateBlob>> {
+        unimplemented!()
+    }
+
+    /// Returns the latest ledger info.
+    fn get_latest_ledger_info(&self) -> Result<LedgerInfoWithSignatures> {
+        unimplemented!()
+    }
+
+    fn get_startup_info(
+        &self, _need_pos_state: bool,
+    ) -> Result<Option<StartupInfo>> {
+        unimplemented!()
+    }
+
+    fn get_txn_by_account(
+        &self, _address: AccountAddress, _seq_num: u64,
+        _ledger_version: Version, _fetch_events: bool,
+    ) -> Result<Option<TransactionWithProof>>
+    {
+        unimplemented!()
+    }
+
+    fn get_state_proof_with_ledger_info(
+        &self, _known_version: u64, _ledger_info: LedgerInfoWithSignatures,
+    ) -> Result<(EpochChangeProof, AccumulatorConsistencyProof)> {
+        unimplemented!()
+    }
+
+    fn get_state_proof(
+        &self, _known_version: u64,
+    ) -> Result<(
+        LedgerInfoWithSignatures,
+        EpochChangeProof,
+        AccumulatorConsistencyProof,
+    )> {
+        unimplemented!()
+    }
+
+    fn get_account_state_with_proof(
+        &self, _address: AccountAddress, _version: Version,
+        _ledger_version: Version,
+    ) -> Result<AccountStateWithProof>
+    {
+        unimplemented!()
+    }
+
+    fn get_account_state_with_proof_by_version(
+        &self, address: AccountAddress, _version: Version,
+    ) -> Result<(
+        Option<AccountStateBlob>,
+        SparseMerkleProof<AccountStateBlob>,
+    )> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        if self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |failures| {
+                failures.checked_sub(1)
+            })
+            .is_ok()
+        {
+            bail!("Injected read failure");
+        }
+        let blob = if address == self.address {
+            Some(self.blob.clone())
+        } else {
+            None
+        };
+        // The tree has only one leaf, so the proof has no siblings.
+        Ok((blob, SparseMerkleProof::new(Some(self.leaf()), vec![])))
+    }
+
+    fn get_latest_state_root(&self) -> Result<(Version, HashValue)> {
+        Ok((self.latest_version, self.root_hash()))
+    }
+
+    fn get_latest_tree_state(&self) -> Result<TreeState> { unimplemented!() }
+
+    fn get_epoch_ending_ledger_info(
+        &self, _known_version: u64,
+    ) -> Result<LedgerInfoWithSignatures> {
+        unimplemented!()
+    }
+}
+
+/// This is a mock of the dbreader which only has the ledger infos ending
+/// epochs, e.g. to read the validator sets of past epochs.
+pub struct MockEpochReader {
+    epoch_ending_ledger_infos: Vec<LedgerInfoWithSignatures>,
+}
+
+impl MockEpochReader {
+    pub fn new(
+        epoch_ending_ledger_infos: Vec<LedgerInfoWithSignatures>,
+    ) -> Self {
+        MockEpochReader {
+            epoch_ending_ledger_infos,
+        }
+    }
+}
+
+impl DbReader for MockEpochReader {
+    /// Returns the ledger infos ending the epochs in
+    /// `[start_epoch, end_epoch)`.
+    fn get_epoch_ending_ledger_infos(
+        &self, start_epoch: u64, end_epoch: u64,
+    ) -> Result<EpochChangeProof> {
+        let ledger_infos = self
+            .epoch_ending_ledger_infos
+            .iter()
+            .filter(|ledger_info| {
+                let epoch = ledger_info.ledger_info().epoch();
+                epoch >= start_epoch && epoch < end_epoch
+            })
+            .cloned()
+            .collect();
+        Ok(EpochChangeProof::new(ledger_infos, false /* more */))
+    }
+
+    fn get_transactions(
+        &self, _start_version: Version, _batch_size: u64,
+        _ledger_version: Version, _fetch_events: bool,
+    ) -> Result<TransactionListWithProof>
+    {
+        unimplemented!()
+    }
+
+    fn get_block_timestamp(&self, _version: u64) -> Result<u64> {
+        unimplemented!()
+    }
+
+    fn get_latest_account_state(
+        &self, _address: AccountAddress,
+    ) -> Result<Option<AccountStateBlob>> {
+        unimplemented!()
+    }
+
+    /// Returns the latest ledger info.
+    fn get_latest_ledger_info(&self) -> Result<LedgerInfoWithSignatures> {
+        unimplemented!()
+    }
+
+    fn get_startup_info(
+        &self, _need_pos_state: bool,
+    ) -> Result<Option<StartupInfo>> {
+        unimplemented!()
+    }
+
+    fn get_txn_by_account(
+        &self, _address: AccountAddress, _seq_num: u64,
+        _ledger_version: Version, _fetch_events: bool,
+    ) -> Result<Option<TransactionWithProof>>
+    {
+        unimplemented!()
+    }
+
+    fn get_state_proof_with_ledger_info(
+        &self, _known_version: u64, _ledger_info: LedgerInfoWithSignatures,
+    ) -> Result<(EpochChangeProof, AccumulatorConsistencyProof)> {
+        unimplemented!()
+    }
+
+    fn get_state_proof(
+        &self, _known_version: u64,
+    ) -> Result<(
+        LedgerInfoWithSignatures,
+        EpochChangeProof,
+        AccumulatorConsistencyProof,
+    )> {
+        unimplemented!()
+    }
+
+    fn get_account_state_with_proof(
+        &self, _address: AccountAddress, _version: Version,
+        _ledger_version: Version,
+    ) -> Result<AccountStateWithProof>
+    {
+        unimplemented!()
+    }
+
+    fn get_account_state_with_proof_by_version(
+        &self, _address: AccountAddress, _version: Version,
+    ) -> Result<(
+        Option<AccountStateBlob>,
+        SparseMerkleProof<AccountStateBlob>,
+    )> {
+        unimplemented!()
+    }
+
+    fn get_latest_state_root(&self) -> Result<(Version, HashValue)> {
+        unimplemented!()
+    }
+
+    fn get_latest_tree_state(&self) -> Result<TreeState> { unimplemented!() }
+
+    fn get_epoch_ending_ledger_info(
+        &self, _known_version: u64,
+    ) -> Result<LedgerInfoWithSignatures> {
+        unimplemented!()
+    }
+}
+
+/// This is a mock of the dbwriter in tests. It only counts the commits.
+#[derive(Default)]
+pub struct MockDbWriter {
+    commits: AtomicUsize,
+}
+
+impl MockDbWriter {
+    /// The number of calls to `save_transactions`.
+    pub fn commits(&self) -> usize { self.commits.load(Ordering::SeqCst) }
+}
+
+impl DbWriter for MockDbWriter {
+    fn save_transactions(
+        &self, _txns_to_commit: &[TransactionToCommit], _first_version: Version,
+        _ledger_info_with_sigs: Option<&LedgerInfoWithSignatures>,
+        _pos_state: Option<PosState>, _committed_blocks: Vec<CommittedBlock>,
+        _ledger_infos_with_voted_block: Vec<(
+            HashValue,
+            LedgerInfoWithSignatures,
+        )>,
+    ) -> Result<()>
+    {
+        self.commits.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn save_reward_event(
+        &self, _epoch: u64, _event: &RewardDistributionEvent,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    fn delete_pos_state_by_block(&self, _block_id: &HashValue) -> Result<()> {
+        Ok(())
+    }
+}
@@ -5,8 +5,8 @@
 // Conflux is free software and distributed under GNU General Public License.
 // See http://www.gnu.org/licenses/
 
-use crate::DbReader;
-use anyhow::{format_err, Result};
+use crate::{DbReader, Error};
+use anyhow::{bail, Result};
 use diem_crypto::{hash::SPARSE_MERKLE_PLACEHOLDER_HASH, HashValue};
 use diem_state_view::{StateView, StateViewId};
 use diem_types::{
@@ -18,14 +18,26 @@ use diem_types::{
     term_state::PosState,
     transaction::{Version, PRE_GENESIS_VERSION},
 };
-use parking_lot::RwLock;
+use lru_cache::LruCache;
+use parking_lot::Mutex;
+use rayon::prelude::*;
 use scratchpad::{AccountStatus, SparseMerkleTree};
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     convert::TryInto,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
 };
 
+/// The state of an account cached by [`VerifiedStateView`] together with its
+/// proof, which is `None` if the account is read from `speculative_state`.
+type CachedAccount =
+    (AccountState, Option<SparseMerkleProof<AccountStateBlob>>);
+
 /// `VerifiedStateView` is like a snapshot of the global state comprised of
 /// state view at two levels, persistent storage and memory.
 pub struct VerifiedStateView<'a> {
@@ -74,7 +86,7 @@ pub struct VerifiedStateView<'a> {
     ///                                    |                     |
     ///        +---------------------------+---------------------+-------+
     ///        | +-------------------------+---------------------+-----+ |
-    ///        | |    account_to_state_cache, account_to_proof_cache   | |
+    ///        | |                    account_cache                    | |
     ///        | +---------------^---------------------------^---------+ |
     ///        |                 |                           |           |
     ///        |     account state blob only        account state blob   |
@@ -85,24 +97,66 @@ pub struct VerifiedStateView<'a> {
     ///        | +------------------------------+ +--------------------+ |
     ///        +---------------------------------------------------------+
     /// ```
-    account_to_state_cache: RwLock<HashMap<AccountAddress, AccountState>>,
-    account_to_proof_cache:
-        RwLock<HashMap<HashValue, SparseMerkleProof<AccountStateBlob>>>,
+    /// The cache evicts the least recently used accounts beyond the capacity
+    /// given to `new`, each with both its state and its proof, so an account
+    /// whose state is cached always has its proof. An evicted account is
+    /// fetched and verified again when it's read next time.
+    account_cache: Mutex<LruCache<AccountAddress, CachedAccount>>,
+
+    /// The number of concurrent tasks `multi_get` splits the account fetches
+    /// from `reader` into.
+    fetch_parallelism: usize,
+
+    /// If set, an account whose proof fails to verify is reported as
+    /// [`Error::StaleReader`] when `reader` is behind
+    /// `latest_persistent_version`, e.g. a lagging RPC client.
+    strict_version: bool,
+
+    /// How many more times an account fetch from `reader` is tried after it
+    /// fails, waiting `retry_backoff` before the first retry and twice as long
+    /// before each next one. An account whose proof fails to verify is never
+    /// fetched again.
+    max_read_retries: usize,
+    retry_backoff: Duration,
+
+    /// How the account reads have been served, see [`CacheStats`].
+    cache_hits: AtomicU64,
+    scratchpad_hits: AtomicU64,
+    db_reads: AtomicU64,
 
     pos_state: PosState,
 }
 
+/// The counters of how a [`VerifiedStateView`] has served the account reads.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The reads served by `account_cache`.
+    pub cache_hits: u64,
+    /// The accounts loaded from the speculative state.
+    pub scratchpad_hits: u64,
+    /// The accounts fetched from persistent storage.
+    pub db_reads: u64,
+}
+
+/// The default of [`VerifiedStateView::set_fetch_parallelism`].
+pub const DEFAULT_FETCH_PARALLELISM: usize = 8;
+
 impl<'a> VerifiedStateView<'a> {
     /// Constructs a [`VerifiedStateView`] with persistent state view
     /// represented by `latest_persistent_state_root` plus a storage reader,
     /// and the in-memory speculative state on top of it represented by
-    /// `speculative_state`.
+    /// `speculative_state`. If `cache_capacity` is set, the cache keeps at
+    /// most that many accounts. The scratchpad needs the proofs of all
+    /// accounts written by the VM, so a view whose cache is converted for it
+    /// should not be bounded. `strict_version` is meant for readers which
+    /// may lag behind `latest_persistent_version`.
     pub fn new(
         id: StateViewId, reader: Arc<dyn DbReader>,
         latest_persistent_version: Option<Version>,
         latest_persistent_state_root: HashValue,
         speculative_state: &'a SparseMerkleTree<AccountStateBlob>,
-        pos_state: PosState,
+        pos_state: PosState, cache_capacity: Option<usize>,
+        strict_version: bool,
     ) -> Self
     {
         // Hack: When there's no transaction in the db but state tree root hash
@@ -118,17 +172,338 @@ impl<'a> VerifiedStateView<'a> {
                     None
                 }
             });
+        let capacity = cache_capacity.unwrap_or(usize::max_value());
         Self {
             id,
             reader,
             latest_persistent_version,
             latest_persistent_state_root,
             speculative_state,
-            account_to_state_cache: RwLock::new(HashMap::new()),
-            account_to_proof_cache: RwLock::new(HashMap::new()),
+            account_cache: Mutex::new(LruCache::new(capacity)),
+            fetch_parallelism: DEFAULT_FETCH_PARALLELISM,
+            strict_version,
+            max_read_retries: 0,
+            retry_backoff: Duration::default(),
+            cache_hits: AtomicU64::new(0),
+            scratchpad_hits: AtomicU64::new(0),
+            db_reads: AtomicU64::new(0),
             pos_state,
         }
     }
+
+    /// Sets how many accounts `multi_get` may fetch from `reader` at the same
+    /// time. `1` fetches them one by one.
+    pub fn set_fetch_parallelism(&mut self, parallelism: usize) {
+        self.fetch_parallelism = parallelism.max(1);
+    }
+
+    /// Sets how many times a failed account fetch from `reader` is retried,
+    /// with exponential backoff starting at `backoff`. `0`, the default,
+    /// returns the first failure.
+    pub fn set_read_retries(&mut self, max_retries: usize, backoff: Duration) {
+        self.max_read_retries = max_retries;
+        self.retry_backoff = backoff;
+    }
+
+    /// Load the accounts in `addresses` into `account_cache`, so that later
+    /// reads of them don't go to `reader`. The accounts are fetched in
+    /// parallel like in `multi_get`, and this returns after all of them are
+    /// verified and cached. Accounts that are cached already are skipped.
+    pub fn prefetch(&self, addresses: &[AccountAddress]) -> Result<()> {
+        let missing: HashSet<_> = {
+            let mut account_cache = self.account_cache.lock();
+            addresses
+                .iter()
+                .filter(|address| !account_cache.contains_key(*address))
+                .cloned()
+                .collect()
+        };
+        if !missing.is_empty() {
+            let accounts = self.load_accounts(missing)?;
+            self.cache_accounts(accounts);
+        }
+        Ok(())
+    }
+
+    /// Returns how the account reads of this view have been served so far.
+    pub fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            scratchpad_hits: self.scratchpad_hits.load(Ordering::Relaxed),
+            db_reads: self.db_reads.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns whether this view is operating over pre-genesis state, i.e.
+    /// `new` found no transaction in the db but a non-placeholder state root.
+    pub fn has_pre_genesis_state(&self) -> bool {
+        self.latest_persistent_version == Some(PRE_GENESIS_VERSION)
+    }
+
+    /// Like `multi_get`, but also returns the proof of the account of each
+    /// access path. The values are read from persistent storage and every
+    /// proof is verified against `latest_persistent_state_root`, so the
+    /// speculative state is not visible here. Each account is fetched and
+    /// verified only once no matter how many of its paths are requested.
+    pub fn multi_get_with_proofs(
+        &self, access_paths: &[AccessPath],
+    ) -> Result<Vec<(Option<Vec<u8>>, SparseMerkleProof<AccountStateBlob>)>>
+    {
+        let mut accounts = HashMap::new();
+        for access_path in access_paths {
+            if let Entry::Vacant(vacant) = accounts.entry(access_path.address)
+            {
+                let (blob, proof) =
+                    self.get_persistent_account(access_path.address)?;
+                let account_state: AccountState = blob
+                    .as_ref()
+                    .map(TryInto::try_into)
+                    .transpose()?
+                    .unwrap_or_default();
+                vacant.insert((account_state, proof));
+            }
+        }
+        Ok(access_paths
+            .iter()
+            .map(|access_path| {
+                let (account_state, proof) = &accounts[&access_path.address];
+                (account_state.get(&access_path.path).cloned(), proof.clone())
+            })
+            .collect())
+    }
+
+    /// Returns the value of each of `paths` under `address`. Like `get`, the
+    /// account is fetched and verified if it's not cached, but that is done
+    /// once for all the paths.
+    pub fn get_account_paths(
+        &self, address: AccountAddress, paths: &[&[u8]],
+    ) -> Result<Vec<Option<Vec<u8>>>> {
+        self.read_account(address, |account_state| {
+            paths
+                .iter()
+                .map(|path| account_state.get(path).cloned())
+                .collect()
+        })
+    }
+
+    /// Apply `read` to the state of `address`. The state is taken from
+    /// `account_cache`, or loaded from `speculative_state` or persistent
+    /// storage and then cached with its proof.
+    fn read_account<T>(
+        &self, address: AccountAddress, read: impl FnOnce(&AccountState) -> T,
+    ) -> Result<T> {
+        if let Some((contents, _)) =
+            self.account_cache.lock().get_mut(&address)
+        {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(read(contents));
+        }
+
+        // Do most of the work outside the write lock.
+        let (account_blob_option, proof) =
+            match self.speculative_state.get(address.hash()) {
+                AccountStatus::ExistsInScratchPad(blob) => {
+                    self.scratchpad_hits.fetch_add(1, Ordering::Relaxed);
+                    (Some(blob), None)
+                }
+                AccountStatus::DoesNotExist => {
+                    self.scratchpad_hits.fetch_add(1, Ordering::Relaxed);
+                    (None, None)
+                }
+                // Nothing is persisted before genesis, so the account is
+                // absent and its proof is the empty one, which needs no
+                // verification.
+                AccountStatus::ExistsInDB | AccountStatus::Unknown
+                    if self.is_genesis() =>
+                {
+                    (None, Some(SparseMerkleProof::new(None, vec![])))
+                }
+                // No matter it is in db or unknown, we have to query from db
+                // since even the former case, we don't have the blob data but
+                // only its hash.
+                AccountStatus::ExistsInDB | AccountStatus::Unknown => {
+                    let (blob, proof) = self.get_persistent_account(address)?;
+                    (blob, Some(proof))
+                }
+            };
+
+        // Now enter the locked region, and write if still empty.
+        let new_account_blob = account_blob_option
+            .as_ref()
+            .map(TryInto::try_into)
+            .transpose()?
+            .unwrap_or_default();
+
+        // Another call may have cached the account in the meantime. Both
+        // proofs are verified, so either one is fine.
+        let mut account_cache = self.account_cache.lock();
+        if let Some((contents, _)) = account_cache.get_mut(&address) {
+            return Ok(read(contents));
+        }
+        let value = read(&new_account_blob);
+        account_cache.insert(address, (new_account_blob, proof));
+        Ok(value)
+    }
+
+    /// Read the account blob of `address` from persistent storage with its
+    /// proof verified against `latest_persistent_state_root`.
+    fn get_persistent_account(
+        &self, address: AccountAddress,
+    ) -> Result<(
+        Option<AccountStateBlob>,
+        SparseMerkleProof<AccountStateBlob>,
+    )> {
+        self.db_reads.fetch_add(1, Ordering::Relaxed);
+        fetch_persistent_account(
+            &*self.reader,
+            self.latest_persistent_version,
+            self.latest_persistent_state_root,
+            address,
+            self.strict_version,
+            self.max_read_retries,
+            self.retry_backoff,
+        )
+    }
+
+    /// Load the states of `addresses` from `speculative_state`, or from
+    /// persistent storage in up to `fetch_parallelism` concurrent tasks. The
+    /// proofs of the accounts read from storage are verified by each task
+    /// and returned with the states.
+    fn load_accounts(
+        &self, addresses: HashSet<AccountAddress>,
+    ) -> Result<HashMap<AccountAddress, CachedAccount>> {
+        let mut blobs = HashMap::new();
+        let mut to_fetch = Vec::new();
+        let mut scratchpad_hits = 0;
+        for address in addresses {
+            match self.speculative_state.get(address.hash()) {
+                AccountStatus::ExistsInScratchPad(blob) => {
+                    scratchpad_hits += 1;
+                    blobs.insert(address, (Some(blob), None));
+                }
+                AccountStatus::DoesNotExist => {
+                    scratchpad_hits += 1;
+                    blobs.insert(address, (None, None));
+                }
+                AccountStatus::ExistsInDB | AccountStatus::Unknown => {
+                    to_fetch.push(address)
+                }
+            }
+        }
+
+        self.scratchpad_hits
+            .fetch_add(scratchpad_hits, Ordering::Relaxed);
+        if !to_fetch.is_empty() {
+            self.db_reads
+                .fetch_add(to_fetch.len() as u64, Ordering::Relaxed);
+            let reader = &*self.reader;
+            let version = self.latest_persistent_version;
+            let state_root = self.latest_persistent_state_root;
+            let strict_version = self.strict_version;
+            let max_read_retries = self.max_read_retries;
+            let retry_backoff = self.retry_backoff;
+            let chunk_size = (to_fetch.len() + self.fetch_parallelism - 1)
+                / self.fetch_parallelism;
+            let fetched = to_fetch
+                .par_chunks(chunk_size)
+                .map(|chunk| {
+                    chunk
+                        .iter()
+                        .map(|address| {
+                            let (blob, proof) = fetch_persistent_account(
+                                reader,
+                                version,
+                                state_root,
+                                *address,
+                                strict_version,
+                                max_read_retries,
+                                retry_backoff,
+                            )?;
+                            Ok((*address, blob, proof))
+                        })
+                        .collect::<Result<Vec<_>>>()
+                })
+                .collect::<Result<Vec<_>>>()?;
+
+            for (address, blob, proof) in fetched.into_iter().flatten() {
+                blobs.insert(address, (blob, Some(proof)));
+            }
+        }
+
+        blobs
+            .into_iter()
+            .map(|(address, (blob, proof))| {
+                let account_state = blob
+                    .as_ref()
+                    .map(TryInto::try_into)
+                    .transpose()?
+                    .unwrap_or_default();
+                Ok((address, (account_state, proof)))
+            })
+            .collect()
+    }
+
+    /// Put the loaded `accounts` in `account_cache`. Like `get`, the accounts
+    /// cached by other calls in the meantime are kept.
+    fn cache_accounts(&self, accounts: HashMap<AccountAddress, CachedAccount>) {
+        let mut account_cache = self.account_cache.lock();
+        for (address, account) in accounts {
+            if !account_cache.contains_key(&address) {
+                account_cache.insert(address, account);
+            }
+        }
+    }
+}
+
+/// Read the account blob of `address` from `reader` at `version` with its proof
+/// verified against `state_root`. If the proof is invalid and `strict_version`
+/// is set, the latest version of `reader` is checked to tell a stale reader
+/// from a corrupted proof. A failed read from `reader` is retried up to
+/// `max_retries` times, but an invalid proof is not since it won't change.
+fn fetch_persistent_account(
+    reader: &dyn DbReader, version: Option<Version>, state_root: HashValue,
+    address: AccountAddress, strict_version: bool, max_retries: usize,
+    backoff: Duration,
+) -> Result<(Option<AccountStateBlob>, SparseMerkleProof<AccountStateBlob>)>
+{
+    let (blob, proof) = match version {
+        Some(version) => {
+            let mut backoff = backoff;
+            let mut retries = 0;
+            loop {
+                match reader
+                    .get_account_state_with_proof_by_version(address, version)
+                {
+                    Err(_) if retries < max_retries => {
+                        thread::sleep(backoff);
+                        backoff *= 2;
+                        retries += 1;
+                    }
+                    result => break result?,
+                }
+            }
+        }
+        None => (None, SparseMerkleProof::new(None, vec![])),
+    };
+    if let Err(err) = proof.verify(state_root, address.hash(), blob.as_ref()) {
+        if let (true, Some(version)) = (strict_version, version) {
+            let (reader_version, _) = reader.get_latest_state_root()?;
+            if reader_version < version {
+                return Err(Error::StaleReader {
+                    requested_version: version,
+                    reader_version,
+                }
+                .into());
+            }
+        }
+        bail!(
+            "Proof is invalid for address {:?} with state root hash {:?}: {}",
+            address,
+            state_root,
+            err
+        );
+    }
+    Ok((blob, proof))
 }
 
 impl<'a> From<VerifiedStateView<'a>>
@@ -138,10 +513,17 @@ impl<'a> From<VerifiedStateView<'a>>
     )
 {
     fn from(view: VerifiedStateView<'a>) -> Self {
-        (
-            view.account_to_state_cache.into_inner(),
-            view.account_to_proof_cache.into_inner(),
-        )
+        let mut states = HashMap::new();
+        let mut proofs = HashMap::new();
+        for (address, (account_state, proof)) in
+            view.account_cache.into_inner().into_iter()
+        {
+            states.insert(address, account_state);
+            if let Some(proof) = proof {
+                proofs.insert(address.hash(), proof);
+            }
+        }
+        (states, proofs)
     }
 }
 
@@ -149,78 +531,624 @@ impl<'a> StateView for VerifiedStateView<'a> {
     fn id(&self) -> StateViewId { self.id }
 
     fn get(&self, access_path: &AccessPath) -> Result<Option<Vec<u8>>> {
-        let address = access_path.address;
-        let path = &access_path.path;
-
-        // Lock for read first:
-        if let Some(contents) = self.account_to_state_cache.read().get(&address)
-        {
-            return Ok(contents.get(path).cloned());
-        }
+        self.read_account(access_path.address, |account_state| {
+            account_state.get(&access_path.path).cloned()
+        })
+    }
 
-        // Do most of the work outside the write lock.
-        let address_hash = address.hash();
-        let account_blob_option = match self.speculative_state.get(address_hash)
+    fn multi_get(
+        &self, access_paths: &[AccessPath],
+    ) -> Result<Vec<Option<Vec<u8>>>> {
+        // Read what's cached first, and remember the distinct accounts that
+        // need to be loaded.
+        let mut values = Vec::with_capacity(access_paths.len());
+        let mut missing = HashSet::new();
         {
-            AccountStatus::ExistsInScratchPad(blob) => Some(blob),
-            AccountStatus::DoesNotExist => None,
-            // No matter it is in db or unknown, we have to query from db since
-            // even the former case, we don't have the blob data but
-            // only its hash.
-            AccountStatus::ExistsInDB | AccountStatus::Unknown => {
-                let (blob, proof) = match self.latest_persistent_version {
-                    Some(version) => {
-                        self.reader.get_account_state_with_proof_by_version(
-                            address, version,
-                        )?
+            let mut account_cache = self.account_cache.lock();
+            for access_path in access_paths {
+                match account_cache.get_mut(&access_path.address) {
+                    Some((contents, _)) => {
+                        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+                        values.push(Some(
+                            contents.get(&access_path.path).cloned(),
+                        ));
                     }
-                    None => (None, SparseMerkleProof::new(None, vec![])),
-                };
-                proof
-                    .verify(
-                        self.latest_persistent_state_root,
-                        address.hash(),
-                        blob.as_ref(),
-                    )
-                    .map_err(|err| {
-                        format_err!(
-                            "Proof is invalid for address {:?} with state root hash {:?}: {}",
-                            address,
-                            self.latest_persistent_state_root,
-                            err
-                        )
-                    })?;
-                assert!(self
-                    .account_to_proof_cache
-                    .write()
-                    .insert(address_hash, proof)
-                    .is_none());
-                blob
+                    None => {
+                        missing.insert(access_path.address);
+                        values.push(None);
+                    }
+                }
             }
+        }
+        if missing.is_empty() {
+            return Ok(values.into_iter().map(Option::unwrap).collect());
+        }
+
+        let accounts = self.load_accounts(missing)?;
+        let values = access_paths
+            .iter()
+            .zip(values)
+            .map(|(access_path, value)| {
+                value.unwrap_or_else(|| {
+                    accounts[&access_path.address]
+                        .0
+                        .get(&access_path.path)
+                        .cloned()
+                })
+            })
+            .collect();
+
+        self.cache_accounts(accounts);
+        Ok(values)
+    }
+
+    fn is_genesis(&self) -> bool { self.latest_persistent_version.is_none() }
+
+    fn pos_state(&self) -> &PosState { &self.pos_state }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CacheStats, VerifiedStateView};
+    use crate::{mock::MockAccountReader, Error};
+    use diem_state_view::{StateView, StateViewId};
+    use diem_types::{
+        access_path::AccessPath,
+        account_address::{AccountAddress, HashAccountAddress},
+        account_state::AccountState,
+        account_state_blob::AccountStateBlob,
+        proof::SparseMerkleProof,
+        term_state::PosState,
+    };
+    use diem_crypto::{hash::SPARSE_MERKLE_PLACEHOLDER_HASH, HashValue};
+    use scratchpad::{ProofRead, SparseMerkleTree};
+    use std::{
+        collections::HashMap, convert::TryFrom, sync::Arc, time::Duration,
+    };
+
+    /// Updating an empty tree reads no proof.
+    struct EmptyProofReader;
+
+    impl ProofRead<AccountStateBlob> for EmptyProofReader {
+        fn get_proof(
+            &self, _key: HashValue,
+        ) -> Option<&SparseMerkleProof<AccountStateBlob>> {
+            None
+        }
+    }
+
+    fn new_test_reader() -> Arc<MockAccountReader> {
+        let mut account_state = AccountState::default();
+        account_state.insert(b"path".to_vec(), b"value".to_vec());
+        Arc::new(MockAccountReader::new(
+            AccountAddress::random(),
+            AccountStateBlob::try_from(&account_state).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn test_cache_eviction() {
+        let reader = new_test_reader();
+        let address = reader.address();
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let view = VerifiedStateView::new(
+            StateViewId::Miscellaneous,
+            reader.clone(),
+            Some(0),
+            reader.root_hash(),
+            &speculative_state,
+            PosState::new_empty(),
+            Some(1), /* cache_capacity */
+            false, /* strict_version */
+        );
+        let path = AccessPath::new(address, b"path".to_vec());
+        let other_path =
+            AccessPath::new(AccountAddress::random(), b"path".to_vec());
+
+        assert_eq!(view.get(&path).unwrap(), Some(b"value".to_vec()));
+        assert_eq!(view.get(&path).unwrap(), Some(b"value".to_vec()));
+        assert_eq!(reader.reads(), 1);
+
+        assert_eq!(view.get(&other_path).unwrap(), None);
+        assert_eq!(reader.reads(), 2);
+
+        // `address` is evicted, so it's fetched and verified again.
+        assert_eq!(view.get(&path).unwrap(), Some(b"value".to_vec()));
+        assert_eq!(reader.reads(), 3);
+
+        let (states, proofs): (
+            HashMap<AccountAddress, AccountState>,
+            HashMap<_, SparseMerkleProof<AccountStateBlob>>,
+        ) = view.into();
+        assert_eq!(states.len(), 1);
+        assert!(states.contains_key(&address));
+        assert_eq!(proofs.len(), 1);
+        assert!(proofs.contains_key(&address.hash()));
+    }
+
+    #[test]
+    fn test_cache_eviction_keeps_proofs() {
+        let reader = new_test_reader();
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let view = VerifiedStateView::new(
+            StateViewId::Miscellaneous,
+            reader.clone(),
+            Some(0),
+            reader.root_hash(),
+            &speculative_state,
+            PosState::new_empty(),
+            Some(2), /* cache_capacity */
+            false, /* strict_version */
+        );
+        let a = reader.address();
+        let b = AccountAddress::random();
+        let c = AccountAddress::random();
+        let get = |address| {
+            view.get(&AccessPath::new(address, b"path".to_vec())).unwrap()
         };
 
-        // Now enter the locked region, and write if still empty.
-        let new_account_blob = account_blob_option
-            .as_ref()
-            .map(TryInto::try_into)
-            .transpose()?
-            .unwrap_or_default();
+        get(a);
+        get(b);
+        // Reading `a` again keeps both its state and its proof, so `b` is
+        // the one evicted by `c`.
+        get(a);
+        get(c);
+        assert_eq!(reader.reads(), 3);
+        assert_eq!(get(a), Some(b"value".to_vec()));
+        assert_eq!(reader.reads(), 3);
+
+        let (states, proofs): (
+            HashMap<AccountAddress, AccountState>,
+            HashMap<_, SparseMerkleProof<AccountStateBlob>>,
+        ) = view.into();
+        let mut cached: Vec<_> = states.keys().cloned().collect();
+        cached.sort();
+        let mut expected = vec![a, c];
+        expected.sort();
+        assert_eq!(cached, expected);
+        assert_eq!(proofs.len(), 2);
+        assert!(proofs.contains_key(&a.hash()));
+        assert!(proofs.contains_key(&c.hash()));
+    }
+
+    #[test]
+    fn test_parallel_multi_get() {
+        let reader = new_test_reader();
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let new_view = |parallelism| {
+            let mut view = VerifiedStateView::new(
+                StateViewId::Miscellaneous,
+                reader.clone(),
+                Some(0),
+                reader.root_hash(),
+                &speculative_state,
+                PosState::new_empty(),
+                None, /* cache_capacity */
+                false, /* strict_version */
+            );
+            view.set_fetch_parallelism(parallelism);
+            view
+        };
 
-        match self.account_to_state_cache.write().entry(address) {
-            Entry::Occupied(occupied) => Ok(occupied.get().get(path).cloned()),
-            Entry::Vacant(vacant) => {
-                Ok(vacant.insert(new_account_blob).get(path).cloned())
+        let mut addresses: Vec<_> =
+            (0..100).map(|_| AccountAddress::random()).collect();
+        addresses.push(reader.address());
+        let mut access_paths = Vec::new();
+        for path in &[b"path".to_vec(), b"other".to_vec()] {
+            for address in &addresses {
+                access_paths.push(AccessPath::new(*address, path.clone()));
             }
         }
+
+        let serial = new_view(1).multi_get(&access_paths).unwrap();
+        assert_eq!(reader.reads(), addresses.len());
+        assert_eq!(serial.iter().filter(|value| value.is_some()).count(), 1);
+        assert_eq!(serial[addresses.len() - 1], Some(b"value".to_vec()));
+
+        let parallel_view = new_view(8);
+        let parallel = parallel_view.multi_get(&access_paths).unwrap();
+        assert_eq!(parallel, serial);
+        assert_eq!(reader.reads(), 2 * addresses.len());
+
+        // All the accounts are cached now.
+        assert_eq!(parallel_view.multi_get(&access_paths).unwrap(), serial);
+        assert_eq!(reader.reads(), 2 * addresses.len());
+
+        let (states, proofs): (
+            HashMap<AccountAddress, AccountState>,
+            HashMap<_, SparseMerkleProof<AccountStateBlob>>,
+        ) = parallel_view.into();
+        assert_eq!(states.len(), addresses.len());
+        assert_eq!(proofs.len(), addresses.len());
     }
 
-    fn multi_get(
-        &self, _access_paths: &[AccessPath],
-    ) -> Result<Vec<Option<Vec<u8>>>> {
-        unimplemented!();
+    #[test]
+    fn test_multi_get_order() {
+        let reader = new_test_reader();
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let view = VerifiedStateView::new(
+            StateViewId::Miscellaneous,
+            reader.clone(),
+            Some(0),
+            reader.root_hash(),
+            &speculative_state,
+            PosState::new_empty(),
+            None, /* cache_capacity */
+            false, /* strict_version */
+        );
+        let a = reader.address();
+        let b = AccountAddress::random();
+        let access_paths = vec![
+            AccessPath::new(a, b"path".to_vec()),
+            AccessPath::new(b, b"path".to_vec()),
+            AccessPath::new(a, b"path".to_vec()),
+            AccessPath::new(a, b"other".to_vec()),
+            AccessPath::new(b, b"path".to_vec()),
+        ];
+        let expected = vec![
+            Some(b"value".to_vec()),
+            None,
+            Some(b"value".to_vec()),
+            None,
+            None,
+        ];
+
+        // The values follow the order of the paths, duplicates included,
+        // and each account is read once.
+        assert_eq!(view.multi_get(&access_paths).unwrap(), expected);
+        assert_eq!(view.cache_stats(), CacheStats {
+            cache_hits: 0,
+            scratchpad_hits: 0,
+            db_reads: 2,
+        });
+        assert_eq!(view.multi_get(&access_paths).unwrap(), expected);
+        assert_eq!(view.cache_stats(), CacheStats {
+            cache_hits: 5,
+            scratchpad_hits: 0,
+            db_reads: 2,
+        });
+
+        // Cached and loaded accounts are mixed in order too.
+        let c = AccountAddress::random();
+        let access_paths = vec![
+            AccessPath::new(c, b"path".to_vec()),
+            AccessPath::new(a, b"path".to_vec()),
+            AccessPath::new(c, b"path".to_vec()),
+        ];
+        assert_eq!(view.multi_get(&access_paths).unwrap(), vec![
+            None,
+            Some(b"value".to_vec()),
+            None,
+        ]);
+        assert_eq!(reader.reads(), 3);
     }
 
-    fn is_genesis(&self) -> bool { self.latest_persistent_version.is_none() }
+    #[test]
+    fn test_prefetch() {
+        let reader = new_test_reader();
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let view = VerifiedStateView::new(
+            StateViewId::Miscellaneous,
+            reader.clone(),
+            Some(0),
+            reader.root_hash(),
+            &speculative_state,
+            PosState::new_empty(),
+            None, /* cache_capacity */
+            false, /* strict_version */
+        );
+        let mut addresses: Vec<_> =
+            (0..10).map(|_| AccountAddress::random()).collect();
+        addresses.push(reader.address());
 
-    fn pos_state(&self) -> &PosState { &self.pos_state }
+        view.prefetch(&addresses).unwrap();
+        assert_eq!(reader.reads(), addresses.len());
+        // Cached accounts are not fetched again.
+        view.prefetch(&addresses).unwrap();
+        assert_eq!(reader.reads(), addresses.len());
+
+        for address in &addresses {
+            let value =
+                view.get(&AccessPath::new(*address, b"path".to_vec())).unwrap();
+            if *address == reader.address() {
+                assert_eq!(value, Some(b"value".to_vec()));
+            } else {
+                assert_eq!(value, None);
+            }
+        }
+        assert_eq!(reader.reads(), addresses.len());
+
+        let (_, proofs): (
+            HashMap<AccountAddress, AccountState>,
+            HashMap<_, SparseMerkleProof<AccountStateBlob>>,
+        ) = view.into();
+        assert_eq!(proofs.len(), addresses.len());
+    }
+
+    #[test]
+    fn test_genesis_get() {
+        let reader = new_test_reader();
+        let address = reader.address();
+        let path = AccessPath::new(address, b"path".to_vec());
+        let new_view = |speculative_state| {
+            VerifiedStateView::new(
+                StateViewId::Miscellaneous,
+                reader.clone(),
+                None, /* latest_persistent_version */
+                *SPARSE_MERKLE_PLACEHOLDER_HASH,
+                speculative_state,
+                PosState::new_empty(),
+                None, /* cache_capacity */
+                false, /* strict_version */
+            )
+        };
+
+        // The account is only known by its hash in the speculative state.
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let view = new_view(&speculative_state);
+        assert!(view.is_genesis());
+        assert_eq!(view.get(&path).unwrap(), None);
+        assert_eq!(view.get(&path).unwrap(), None);
+        assert_eq!(reader.reads(), 0);
+        assert_eq!(view.cache_stats(), CacheStats {
+            cache_hits: 1,
+            scratchpad_hits: 0,
+            db_reads: 0,
+        });
+        let (states, proofs): (
+            HashMap<AccountAddress, AccountState>,
+            HashMap<_, SparseMerkleProof<AccountStateBlob>>,
+        ) = view.into();
+        assert_eq!(states.len(), 1);
+        assert_eq!(
+            proofs.get(&address.hash()),
+            Some(&SparseMerkleProof::new(None, vec![]))
+        );
+
+        // Accounts written before genesis are read from the scratchpad.
+        let mut account_state = AccountState::default();
+        account_state.insert(b"path".to_vec(), b"genesis".to_vec());
+        let speculative_state = SparseMerkleTree::default()
+            .update(
+                vec![(
+                    address.hash(),
+                    AccountStateBlob::try_from(&account_state).unwrap(),
+                )],
+                &EmptyProofReader,
+            )
+            .unwrap();
+        let view = new_view(&speculative_state);
+        assert_eq!(view.get(&path).unwrap(), Some(b"genesis".to_vec()));
+        assert_eq!(
+            view.get(&AccessPath::new(AccountAddress::random(), vec![]))
+                .unwrap(),
+            None
+        );
+        assert_eq!(reader.reads(), 0);
+    }
+
+    #[test]
+    fn test_cache_stats() {
+        let reader = new_test_reader();
+        let address = reader.address();
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let view = VerifiedStateView::new(
+            StateViewId::Miscellaneous,
+            reader.clone(),
+            Some(0),
+            reader.root_hash(),
+            &speculative_state,
+            PosState::new_empty(),
+            None, /* cache_capacity */
+            false, /* strict_version */
+        );
+        let path = AccessPath::new(address, b"path".to_vec());
+
+        view.get(&path).unwrap();
+        assert_eq!(view.cache_stats(), CacheStats {
+            cache_hits: 0,
+            scratchpad_hits: 0,
+            db_reads: 1,
+        });
+        view.get(&path).unwrap();
+        assert_eq!(view.cache_stats(), CacheStats {
+            cache_hits: 1,
+            scratchpad_hits: 0,
+            db_reads: 1,
+        });
+    }
+
+    #[test]
+    fn test_get_account_paths() {
+        let mut account_state = AccountState::default();
+        account_state.insert(b"first".to_vec(), b"1".to_vec());
+        account_state.insert(b"second".to_vec(), b"2".to_vec());
+        let reader = Arc::new(MockAccountReader::new(
+            AccountAddress::random(),
+            AccountStateBlob::try_from(&account_state).unwrap(),
+        ));
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let view = VerifiedStateView::new(
+            StateViewId::Miscellaneous,
+            reader.clone(),
+            Some(0),
+            reader.root_hash(),
+            &speculative_state,
+            PosState::new_empty(),
+            None, /* cache_capacity */
+            false, /* strict_version */
+        );
+
+        let paths: &[&[u8]] = &[b"first", b"missing", b"second"];
+        assert_eq!(
+            view.get_account_paths(reader.address(), paths).unwrap(),
+            vec![Some(b"1".to_vec()), None, Some(b"2".to_vec())]
+        );
+        assert_eq!(reader.reads(), 1);
+        assert_eq!(view.cache_stats(), CacheStats {
+            cache_hits: 0,
+            scratchpad_hits: 0,
+            db_reads: 1,
+        });
+
+        // The account is cached for `get` as well.
+        assert_eq!(
+            view.get(&AccessPath::new(reader.address(), b"second".to_vec()))
+                .unwrap(),
+            Some(b"2".to_vec())
+        );
+        assert_eq!(reader.reads(), 1);
+    }
+
+    #[test]
+    fn test_multi_get_with_proofs() {
+        let mut account_state = AccountState::default();
+        account_state.insert(b"first".to_vec(), b"1".to_vec());
+        account_state.insert(b"second".to_vec(), b"2".to_vec());
+        let blob = AccountStateBlob::try_from(&account_state).unwrap();
+        let reader = Arc::new(MockAccountReader::new(
+            AccountAddress::random(),
+            blob.clone(),
+        ));
+        let speculative_state = SparseMerkleTree::new(reader.root_hash());
+        let view = VerifiedStateView::new(
+            StateViewId::Miscellaneous,
+            reader.clone(),
+            Some(0),
+            reader.root_hash(),
+            &speculative_state,
+            PosState::new_empty(),
+            None, /* cache_capacity */
+            false, /* strict_version */
+        );
+        let a = reader.address();
+        let b = AccountAddress::random();
+        let access_paths = vec![
+            AccessPath::new(a, b"first".to_vec()),
+            AccessPath::new(b, b"first".to_vec()),
+            AccessPath::new(a, b"second".to_vec()),
+            AccessPath::new(b, b"second".to_vec()),
+            AccessPath::new(a, b"missing".to_vec()),
+        ];
+
+        let results = view.multi_get_with_proofs(&access_paths).unwrap();
+        // One read per account, not per path.
+        assert_eq!(reader.reads(), 2);
+        let values: Vec<_> =
+            results.iter().map(|(value, _)| value.clone()).collect();
+        assert_eq!(values, vec![
+            Some(b"1".to_vec()),
+            None,
+            Some(b"2".to_vec()),
+            None,
+            None,
+        ]);
+        // Each path comes with the proof of its own account: an inclusion
+        // proof of `blob` for `a` and a non-inclusion proof for `b`.
+        for (access_path, (_, proof)) in access_paths.iter().zip(&results) {
+            let expected_blob = if access_path.address == a {
+                Some(&blob)
+            } else {
+                None
+            };
+            proof
+                .verify(
+                    reader.root_hash(),
+                    access_path.address.hash(),
+                    expected_blob,
+                )
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_strict_version() {
+        let mut account_state = AccountState::default();
+        account_state.insert(b"path".to_vec(), b"value".to_vec());
+        let blob = AccountStateBlob::try_from(&account_state).unwrap();
+        let address = AccountAddress::random();
+        // The state root of a later version, which the proofs returned by the
+        // readers do not verify against.
+        let state_root = HashValue::new([1; HashValue::LENGTH]);
+        let speculative_state = SparseMerkleTree::new(state_root);
+        let path = AccessPath::new(address, b"path".to_vec());
+        let get = |reader_version, strict_version| {
+            let reader = Arc::new(
+                MockAccountReader::new(address, blob.clone())
+                    .with_latest_version(reader_version),
+            );
+            VerifiedStateView::new(
+                StateViewId::Miscellaneous,
+                reader,
+                Some(5),
+                state_root,
+                &speculative_state,
+                PosState::new_empty(),
+                None, /* cache_capacity */
+                strict_version,
+            )
+            .get(&path)
+            .unwrap_err()
+        };
+
+        let err = get(3, true);
+        assert_eq!(
+            err.downcast_ref::<Error>(),
+            Some(&Error::StaleReader {
+                requested_version: 5,
+                reader_version: 3,
+            })
+        );
+        // The proof is reported as invalid if the reader is not behind or
+        // the version is not checked.
+        for (reader_version, strict_version) in &[(5, true), (3, false)] {
+            let err = get(*reader_version, *strict_version);
+            assert!(err.downcast_ref::<Error>().is_none());
+            assert!(err.to_string().contains("Proof is invalid"));
+        }
+    }
+
+    #[test]
+    fn test_read_retries() {
+        let mut account_state = AccountState::default();
+        account_state.insert(b"path".to_vec(), b"value".to_vec());
+        let blob = AccountStateBlob::try_from(&account_state).unwrap();
+        let address = AccountAddress::random();
+        let path = AccessPath::new(address, b"path".to_vec());
+        let get = |state_root, max_retries| {
+            let reader = Arc::new(
+                MockAccountReader::new(address, blob.clone()).with_failures(2),
+            );
+            let speculative_state = SparseMerkleTree::new(state_root);
+            let mut view = VerifiedStateView::new(
+                StateViewId::Miscellaneous,
+                reader.clone(),
+                Some(0),
+                state_root,
+                &speculative_state,
+                PosState::new_empty(),
+                None, /* cache_capacity */
+                false, /* strict_version */
+            );
+            view.set_read_retries(max_retries, Duration::from_millis(1));
+            (view.get(&path), reader.reads())
+        };
+        let root_hash =
+            MockAccountReader::new(address, blob.clone()).root_hash();
+
+        let (value, reads) = get(root_hash, 2);
+        assert_eq!(value.unwrap(), Some(b"value".to_vec()));
+        assert_eq!(reads, 3);
+
+        // The first failure is returned without retries.
+        let (value, reads) = get(root_hash, 0);
+        assert!(value.is_err());
+        assert_eq!(reads, 1);
+
+        // The account is not fetched again after the two failures once its
+        // proof turns out invalid.
+        let (value, reads) = get(HashValue::new([1; HashValue::LENGTH]), 3);
+        assert!(value.unwrap_err().to_string().contains("Proof is invalid"));
+        assert_eq!(reads, 3);
+    }
 }
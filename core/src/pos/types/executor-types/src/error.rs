@@ -20,6 +20,50 @@ pub enum Error {
 
     #[error("Serialization error: {0}")]
     SerializationError(String),
+
+    #[error(transparent)]
+    ExecutorError(#[from] ExecutorError),
+}
+
+#[derive(Debug, Deserialize, Error, PartialEq, Serialize)]
+/// Reasons for the executor to reject a block, so callers can tell an invalid
+/// proposal from an internal failure.
+pub enum ExecutorError {
+    #[error("Multiple pivot decisions in one block")]
+    MultiplePivotDecisions,
+
+    #[error("Invalid pivot decision for block")]
+    InvalidPivotDecision,
+
+    #[error(
+        "Pivot decision height {height} does not advance from the last pivot \
+         decision height {last_height}"
+    )]
+    PivotDecisionNotAdvanced { last_height: u64, height: u64 },
+
+    #[error("More staking transactions packed than actual PoW events")]
+    TooManyStakingTransactions,
+
+    #[error("Packed staking transactions unmatch PoW events")]
+    UnmatchedStakingTransaction,
+
+    #[error("Not all PoW staking events are packed")]
+    MissingStakingTransactions,
+
+    #[error("Should not pack staking related transactions")]
+    UnexpectedStakingTransactions,
+
+    #[error("Try to commit stale transactions with the last version as {0}")]
+    StaleCommit(u64),
+
+    #[error("CommittableBlockBatch is empty")]
+    EmptyBlockBatch,
+
+    #[error("Invalid signatures on the ledger info to commit: {0}")]
+    InvalidCommitSignatures(String),
+
+    #[error("No validators of epoch {0} to verify the ledger info to commit")]
+    UnknownCommitEpoch(u64),
 }
 
 impl From<anyhow::Error> for Error {
@@ -30,11 +30,13 @@ use diem_types::{
     },
     validator_config::ConsensusSignature,
 };
-pub use error::Error;
+pub use error::{Error, ExecutorError};
 use scratchpad::ProofRead;
 use storage_interface::TreeState;
 
-pub use self::processed_vm_output::{ProcessedVMOutput, TransactionData};
+pub use self::processed_vm_output::{
+    ProcessedVMOutput, TransactionData, TransactionReceipt,
+};
 
 mod error;
 mod processed_vm_output;
@@ -144,6 +146,9 @@ pub struct StateComputeResult {
 
     /// Tracks the last pivot selection of a proposed block
     pivot_decision: Option<PivotBlockDecision>,
+
+    /// The gas used by all transactions of the block, if it is known.
+    total_gas_used: Option<u64>,
 }
 
 impl StateComputeResult {
@@ -167,6 +172,7 @@ impl StateComputeResult {
             transaction_info_hashes,
             signature: None,
             pivot_decision,
+            total_gas_used: None,
         }
     }
 }
@@ -218,8 +224,20 @@ impl StateComputeResult {
 
     pub fn has_reconfiguration(&self) -> bool { self.epoch_state.is_some() }
 
+    /// The epoch that takes effect if this block is committed. The PoS epoch
+    /// number is the membership id, and it comes with the new epoch state.
+    pub fn next_membership_id(&self) -> Option<u64> {
+        self.epoch_state.as_ref().map(|epoch_state| epoch_state.epoch)
+    }
+
     pub fn signature(&self) -> &Option<ConsensusSignature> { &self.signature }
 
+    pub fn total_gas_used(&self) -> Option<u64> { self.total_gas_used }
+
+    pub fn set_total_gas_used(&mut self, total_gas_used: u64) {
+        self.total_gas_used = Some(total_gas_used);
+    }
+
     pub fn set_signature(&mut self, sig: ConsensusSignature) {
         self.signature = Some(sig);
     }
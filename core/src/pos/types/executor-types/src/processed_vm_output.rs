@@ -17,7 +17,7 @@ use diem_types::{
     epoch_state::EpochState,
     proof::accumulator::InMemoryAccumulator,
     term_state::PosState,
-    transaction::{TransactionStatus, Version},
+    transaction::{TransactionOutput, TransactionStatus, Version},
 };
 use std::{collections::HashMap, sync::Arc};
 
@@ -91,6 +91,38 @@ impl TransactionData {
     pub fn txn_info_hash(&self) -> Option<HashValue> { self.txn_info_hash }
 }
 
+/// The receipt of a transaction included in a block, so it can be served
+/// without executing the transaction again.
+#[derive(Clone, Debug)]
+pub struct TransactionReceipt {
+    /// The execution status set by the VM.
+    status: TransactionStatus,
+
+    /// The amount of gas used.
+    gas_used: u64,
+
+    /// The list of events emitted during this transaction.
+    events: Vec<ContractEvent>,
+}
+
+impl TransactionReceipt {
+    pub fn status(&self) -> &TransactionStatus { &self.status }
+
+    pub fn gas_used(&self) -> u64 { self.gas_used }
+
+    pub fn events(&self) -> &[ContractEvent] { &self.events }
+}
+
+impl From<&TransactionOutput> for TransactionReceipt {
+    fn from(output: &TransactionOutput) -> Self {
+        TransactionReceipt {
+            status: output.status().clone(),
+            gas_used: output.gas_used(),
+            events: output.events().to_vec(),
+        }
+    }
+}
+
 /// The output of Processing the vm output of a series of transactions to the
 /// parent in-memory state merkle tree and accumulator.
 #[derive(Debug, Clone)]
@@ -108,6 +140,13 @@ pub struct ProcessedVMOutput {
 
     /// If set, this is the selected pivot block in current transaction.
     pivot_block: Option<PivotBlockDecision>,
+
+    /// The receipt of each transaction in the block, in the same order as
+    /// the transactions.
+    receipts: Vec<TransactionReceipt>,
+
+    /// The gas used by all transactions in the block.
+    total_gas_used: u64,
 }
 
 impl ProcessedVMOutput {
@@ -122,6 +161,8 @@ impl ProcessedVMOutput {
             executed_trees,
             epoch_state,
             pivot_block,
+            receipts: vec![],
+            total_gas_used: 0,
         }
     }
 
@@ -141,6 +182,10 @@ impl ProcessedVMOutput {
         &self.pivot_block
     }
 
+    pub fn receipts(&self) -> &[TransactionReceipt] { &self.receipts }
+
+    pub fn total_gas_used(&self) -> u64 { self.total_gas_used }
+
     pub fn has_reconfiguration(&self) -> bool { self.epoch_state.is_some() }
 
     pub fn compute_result(
@@ -153,7 +198,7 @@ impl ProcessedVMOutput {
         // response to consensus.
         // TODO: The VM will support a special transaction to set the validators
         // for the next epoch that is part of a block execution.
-        StateComputeResult::new(
+        let mut result = StateComputeResult::new(
             if parent_num_leaves == 0 {
                 self.accu_root()
             } else {
@@ -174,13 +219,23 @@ impl ProcessedVMOutput {
                 .filter_map(|x| x.txn_info_hash())
                 .collect(),
             self.pivot_block().clone(),
-        )
+        );
+        result.set_total_gas_used(self.total_gas_used);
+        result
     }
 
     pub fn replace_pos_state(&mut self, new_pos_state: PosState) {
         self.executed_trees.pos_state = new_pos_state;
     }
 
+    pub fn set_receipts(&mut self, receipts: Vec<TransactionReceipt>) {
+        self.receipts = receipts;
+    }
+
+    pub fn set_total_gas_used(&mut self, total_gas_used: u64) {
+        self.total_gas_used = total_gas_used;
+    }
+
     pub fn set_pos_state_skipped(&mut self) {
         self.executed_trees.set_pos_state_skipped(true);
     }
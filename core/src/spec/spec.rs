@@ -67,6 +67,10 @@ pub struct TransitionsBlockNumber {
     pub cip78b: BlockNumber,
     /// CIP80: Ethereum compatible signature recover
     pub cip80: BlockNumber,
+    /// Query the PoS stake of an address through internal contract
+    pub pos_stake_query: BlockNumber,
+    /// Query the latest PoS pivot decision through internal contract
+    pub pivot_decision_query: BlockNumber,
 }
 
 #[derive(Default, Debug, Clone)]
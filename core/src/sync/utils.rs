@@ -173,14 +173,10 @@ pub fn initialize_data_manager(
     (data_man, genesis_block)
 }
 
-pub fn initialize_synchronization_graph_with_data_manager(
-    data_man: Arc<BlockDataManager>, beta: u64, h: u64, tcr: u64, tcb: u64,
-    era_epoch_count: u64, pow: Arc<PowComputer>, vm: VmFactory,
-) -> (Arc<SynchronizationGraph>, Arc<ConsensusGraph>)
-{
-    let machine = Arc::new(new_machine_with_builtin(Default::default(), vm));
+/// Create a `PosVerifier` without a PoS handler for tests.
+pub fn new_test_pos_verifier() -> Arc<PosVerifier> {
     let mut rng = StdRng::from_seed([0u8; 32]);
-    let pos_verifier = Arc::new(PosVerifier::new(
+    Arc::new(PosVerifier::new(
         None,
         // These configurations will not be used.
         PosConfiguration {
@@ -193,7 +189,16 @@ pub fn initialize_synchronization_graph_with_data_manager(
             pos_state_config: Default::default(),
         },
         u64::MAX,
-    ));
+    ))
+}
+
+pub fn initialize_synchronization_graph_with_data_manager(
+    data_man: Arc<BlockDataManager>, beta: u64, h: u64, tcr: u64, tcb: u64,
+    era_epoch_count: u64, pow: Arc<PowComputer>, vm: VmFactory,
+) -> (Arc<SynchronizationGraph>, Arc<ConsensusGraph>)
+{
+    let machine = Arc::new(new_machine_with_builtin(Default::default(), vm));
+    let pos_verifier = new_test_pos_verifier();
 
     let verification_config = VerificationConfig::new(
         true, /* test_mode */
@@ -6,8 +6,8 @@ use cfx_types::Address;
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use std::{
     cmp::{Ord, Ordering, PartialEq, PartialOrd},
-    collections::HashMap,
-    ptr,
+    collections::{BinaryHeap, HashMap},
+    iter, ptr,
 };
 
 /// This is the internal node type of `GarbageCollector`.
@@ -48,7 +48,7 @@ impl PartialOrd for GarbageCollectorNode {
 
 /// The `GarbageCollector` maintain a priority queue of `GarbageCollectorNode`,
 /// the topmost node is the largest one.
-#[derive(Default, DeriveMallocSizeOf)]
+#[derive(Clone, Default, DeriveMallocSizeOf)]
 pub struct GarbageCollector {
     data: Vec<GarbageCollectorNode>,
     mapping: HashMap<Address, usize>,
@@ -80,12 +80,48 @@ impl GarbageCollector {
         Some(item)
     }
 
+    /// Remove the node of `sender` if it is in the collector.
+    pub fn remove(&mut self, sender: &Address) -> Option<GarbageCollectorNode> {
+        let index = self.mapping.remove(sender)?;
+        let item = self.data.swap_remove(index);
+        if index < self.data.len() {
+            // The last node is moved to `index`, where it may be either
+            // larger than its parent or smaller than its children.
+            let moved = self.data[index].sender;
+            self.sift_up(index);
+            let index = self.mapping[&moved];
+            self.sift_down(index);
+        }
+        self.gc_size -= item.count;
+        Some(item)
+    }
+
     pub fn clear(&mut self) {
         self.mapping.clear();
         self.data.clear();
         self.gc_size = 0;
     }
 
+    /// Iterate the nodes from the largest to the smallest without changing
+    /// the collector.
+    pub fn iter_by_priority(
+        &self,
+    ) -> impl Iterator<Item = &GarbageCollectorNode> + '_ {
+        let mut candidates = BinaryHeap::new();
+        if let Some(top) = self.data.get(0) {
+            candidates.push((*top, 0));
+        }
+        iter::from_fn(move || {
+            let (_, index) = candidates.pop()?;
+            for child in &[index * 2 + 1, index * 2 + 2] {
+                if let Some(node) = self.data.get(*child) {
+                    candidates.push((*node, *child));
+                }
+            }
+            Some(&self.data[index])
+        })
+    }
+
     pub fn get_timestamp(&self, sender: &Address) -> Option<u64> {
         self.mapping
             .get(sender)
@@ -194,7 +230,7 @@ mod garbage_collector_test {
     use cfx_types::Address;
     use rand::{RngCore, SeedableRng};
     use rand_xorshift::XorShiftRng;
-    use std::collections::HashMap;
+    use std::{collections::HashMap, iter};
 
     #[test]
     fn test_basic_operation() {
@@ -262,6 +298,28 @@ mod garbage_collector_test {
         assert!(gc.pop().is_none());
     }
 
+    #[test]
+    fn test_remove() {
+        let mut gc = GarbageCollector::default();
+        let addr: Vec<_> = (0..5).map(|_| Address::random()).collect();
+        for (i, address) in addr.iter().enumerate() {
+            gc.insert(address, i, 0);
+        }
+        assert!(gc.remove(&Address::random()).is_none());
+
+        let removed = gc.remove(&addr[1]).unwrap();
+        assert_eq!(removed.sender, addr[1]);
+        assert_eq!(gc.len(), 4);
+        assert_eq!(gc.gc_size(), 9);
+        assert_eq!(gc.get_timestamp(&addr[1]), None);
+        assert!(gc.remove(&addr[1]).is_none());
+
+        let popped: Vec<_> = iter::from_fn(|| gc.pop())
+            .map(|node| node.sender)
+            .collect();
+        assert_eq!(popped, vec![addr[4], addr[3], addr[2], addr[0]]);
+    }
+
     fn get_max(
         mapping: &HashMap<Address, GarbageCollectorNode>,
     ) -> Option<GarbageCollectorNode> {
@@ -14,7 +14,17 @@ mod transaction_pool_inner;
 
 extern crate rand;
 
-pub use self::{impls::TreapMap, transaction_pool_inner::TransactionStatus};
+#[cfg(feature = "testonly_code")]
+pub use self::nonce_pool::{NoncePool, TxWithReadyInfo};
+
+pub use self::{
+    impls::TreapMap,
+    transaction_pool_inner::{
+        BatchConflict, ContentPage, ContentSortOrder, GcEntry, GcStats,
+        GcStrategy, NonceReachability, PendingReason, PoolReport, PoolStats,
+        RemovalReason, SenderReport, TransactionReport, TransactionStatus,
+    },
+};
 use crate::{
     block_data_manager::BlockDataManager, consensus::BestInformation,
     machine::Machine, state::State, verification::VerificationConfig,
@@ -40,14 +50,17 @@ use primitives::{Account, SignedTransaction, TransactionWithSignature};
 use std::{
     cmp::{max, min},
     collections::hash_map::HashMap,
-    mem,
+    io, mem,
     ops::DerefMut,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        mpsc, Arc,
     },
 };
-use transaction_pool_inner::TransactionPoolInner;
+use transaction_pool_inner::{
+    PackingOrder, TransactionPoolInner, DEFAULT_GC_CHECK_COUNT,
+    FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET,
+};
 
 lazy_static! {
     static ref TX_POOL_DEFERRED_GAUGE: Arc<dyn Gauge<usize>> =
@@ -88,7 +101,54 @@ pub struct TxPoolConfig {
     pub max_tx_gas: RwLock<U256>,
     pub tx_weight_scaling: u64,
     pub tx_weight_exp: u8,
+    /// The weight a ready transaction gains for each second its sender has
+    /// been ready. `0` weights the transactions by gas price only.
+    pub tx_weight_aging_factor: u64,
     pub target_block_gas_limit: u64,
+    /// The number of recently packed transactions whose gas prices are kept
+    /// for `recent_packed_fee_percentile`.
+    pub packed_fee_window: usize,
+    /// The number of transactions by which forced insertions may exceed
+    /// `capacity`.
+    pub forced_insert_overflow_margin: usize,
+    /// The balance a sender must keep after paying for its transactions for
+    /// them to be packed.
+    pub min_balance_reserve: U256,
+    /// If set, a received transaction whose cost exceeds the balance of its
+    /// sender by more than this is rejected.
+    pub strict_balance_margin: Option<U256>,
+    /// The number of slots of `capacity` which transactions received from
+    /// other nodes can not take.
+    pub local_reserved_slots: usize,
+    /// The max number of transactions a sender can have in the pool.
+    pub max_txs_per_sender: usize,
+    /// The max total size in bytes of the transactions in the pool, in
+    /// addition to `capacity`.
+    pub memory_capacity: Option<usize>,
+    /// A transaction is rejected if its nonce is at least this far after the
+    /// state nonce of its sender.
+    pub furthest_future_nonce_offset: u32,
+    /// The percentage by which a transaction must raise the gas price of the
+    /// pooled one with the same sender and nonce to replace it. If it's 0,
+    /// any higher gas price is enough.
+    pub min_replace_bump_percent: u64,
+    /// The max number of senders checked to pick a garbage collection victim.
+    pub gc_check_count: usize,
+    pub gc_strategy: GcStrategy,
+    /// The number of latest insertions over which the garbage collection
+    /// evictions are counted. If it's 0, inserts are never rejected for
+    /// thrashing.
+    pub gc_thrashing_window: usize,
+    /// Non-local transactions are rejected while the evictions exceed this
+    /// percentage of the insertions in the window.
+    pub gc_thrashing_threshold_percent: u64,
+    /// A transaction whose gas limit exceeds this percentage of the block gas
+    /// limit is not packed.
+    pub max_single_tx_gas_percent: u64,
+    /// The pool rejects a transaction with a lower gas price before inserting
+    /// it, unless it is packed in a block. If it's 0, no transaction is
+    /// rejected for its gas price by the pool.
+    pub min_gas_price: u64,
 }
 
 impl MallocSizeOf for TxPoolConfig {
@@ -107,7 +167,24 @@ impl Default for TxPoolConfig {
             // weight.
             tx_weight_scaling: 1,
             tx_weight_exp: 1,
+            tx_weight_aging_factor: 0,
             target_block_gas_limit: DEFAULT_TARGET_BLOCK_GAS_LIMIT,
+            packed_fee_window: 1000,
+            forced_insert_overflow_margin: 0,
+            min_balance_reserve: U256::zero(),
+            strict_balance_margin: None,
+            local_reserved_slots: 0,
+            max_txs_per_sender: usize::max_value(),
+            memory_capacity: None,
+            furthest_future_nonce_offset:
+                FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET,
+            min_replace_bump_percent: 0,
+            gc_check_count: DEFAULT_GC_CHECK_COUNT,
+            gc_strategy: GcStrategy::default(),
+            gc_thrashing_window: 0,
+            gc_thrashing_threshold_percent: 90,
+            max_single_tx_gas_percent: 100,
+            min_gas_price: 0,
         }
     }
 }
@@ -159,11 +236,7 @@ impl TransactionPool {
     ) -> Self
     {
         let genesis_hash = data_man.true_genesis.hash();
-        let inner = TransactionPoolInner::new(
-            config.capacity,
-            config.tx_weight_scaling,
-            config.tx_weight_exp,
-        );
+        let inner = TransactionPoolInner::new(&config);
         let best_executed_state = Mutex::new(
             Self::best_executed_state(
                 &data_man,
@@ -221,6 +294,15 @@ impl TransactionPool {
         self.inner.read().get_next_nonce(address, state_nonce)
     }
 
+    pub fn nonce_reachability(
+        &self, address: &Address, nonce: U256,
+    ) -> NonceReachability {
+        let (state_nonce, _) = self
+            .get_state_account_info(address)
+            .unwrap_or((0.into(), 0.into()));
+        self.inner.read().nonce_reachability(address, nonce, state_nonce)
+    }
+
     pub fn get_account_pending_info(
         &self, address: &Address,
     ) -> Option<(U256, U256, U256, H256)> {
@@ -269,7 +351,28 @@ impl TransactionPool {
     /// cannot be inserted to the tx pool, it will be included in the returned
     /// `failure` and will not be propagated.
     pub fn insert_new_transactions(
-        &self, mut transactions: Vec<TransactionWithSignature>,
+        &self, transactions: Vec<TransactionWithSignature>,
+    ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, String>) {
+        self.insert_new_transactions_with_origin(
+            transactions,
+            false, /* is_local */
+        )
+    }
+
+    /// Like `insert_new_transactions`, but for transactions submitted through
+    /// the RPC of this node. They are spared by garbage collection unless the
+    /// pool is over its capacity.
+    pub fn insert_new_local_transactions(
+        &self, transactions: Vec<TransactionWithSignature>,
+    ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, String>) {
+        self.insert_new_transactions_with_origin(
+            transactions,
+            true, /* is_local */
+        )
+    }
+
+    fn insert_new_transactions_with_origin(
+        &self, mut transactions: Vec<TransactionWithSignature>, is_local: bool,
     ) -> (Vec<Arc<SignedTransaction>>, HashMap<H256, String>) {
         INSERT_TPS.mark(1);
         INSERT_TXS_TPS.mark(transactions.len());
@@ -337,6 +440,7 @@ impl TransactionPool {
                         tx.clone(),
                         false,
                         false,
+                        is_local,
                     ) {
                         debug!(
                             "tx {:?} fails to be inserted to pool, err={:?}",
@@ -455,6 +559,7 @@ impl TransactionPool {
                     tx.clone(),
                     false,
                     false,
+                    false,
                 ) {
                     debug!(
                         "tx {:?} fails to be inserted to pool, err={:?}",
@@ -538,9 +643,11 @@ impl TransactionPool {
     // Add transaction into deferred pool and maintain its readiness
     // the packed tag provided
     // if force tag is true, the replacement in nonce pool must be happened
+    // if is_local tag is true, the transaction is spared by garbage collection
     pub fn add_transaction_with_readiness_check(
         &self, inner: &mut TransactionPoolInner, account_cache: &AccountCache,
         transaction: Arc<SignedTransaction>, packed: bool, force: bool,
+        is_local: bool,
     ) -> Result<(), String>
     {
         inner.insert_transaction_with_readiness_check(
@@ -548,6 +655,7 @@ impl TransactionPool {
             transaction,
             packed,
             force,
+            is_local,
         )
     }
 
@@ -601,8 +709,68 @@ impl TransactionPool {
 
     pub fn pack_transactions<'a>(
         &self, num_txs: usize, block_gas_limit: U256, block_size_limit: usize,
-        mut best_epoch_height: u64, mut best_block_number: u64,
+        best_epoch_height: u64, best_block_number: u64,
     ) -> Vec<Arc<SignedTransaction>>
+    {
+        self.pack_transactions_impl(
+            num_txs,
+            block_gas_limit,
+            block_size_limit,
+            best_epoch_height,
+            best_block_number,
+            PackingOrder::Random,
+            false, /* with_rlp */
+        )
+        .0
+    }
+
+    /// Like `pack_transactions`, but take the ready transactions in
+    /// descending gas price order instead of sampling them randomly.
+    pub fn pack_transactions_by_price(
+        &self, num_txs: usize, block_gas_limit: U256, block_size_limit: usize,
+        best_epoch_height: u64, best_block_number: u64,
+    ) -> Vec<Arc<SignedTransaction>>
+    {
+        self.pack_transactions_impl(
+            num_txs,
+            block_gas_limit,
+            block_size_limit,
+            best_epoch_height,
+            best_block_number,
+            PackingOrder::GasPrice,
+            false, /* with_rlp */
+        )
+        .0
+    }
+
+    /// Like `pack_transactions`, but also return the rlp encoding of the
+    /// packed transaction list.
+    pub fn pack_transactions_with_rlp(
+        &self, num_txs: usize, block_gas_limit: U256, block_size_limit: usize,
+        best_epoch_height: u64, best_block_number: u64,
+    ) -> (Vec<Arc<SignedTransaction>>, Vec<u8>)
+    {
+        let (transactions, rlp_bytes) = self.pack_transactions_impl(
+            num_txs,
+            block_gas_limit,
+            block_size_limit,
+            best_epoch_height,
+            best_block_number,
+            PackingOrder::Random,
+            true, /* with_rlp */
+        );
+        (
+            transactions,
+            // `rlp_bytes` is `None` if `num_txs` is zero.
+            rlp_bytes.unwrap_or_else(|| rlp::EMPTY_LIST_RLP.to_vec()),
+        )
+    }
+
+    fn pack_transactions_impl(
+        &self, num_txs: usize, block_gas_limit: U256, block_size_limit: usize,
+        mut best_epoch_height: u64, mut best_block_number: u64,
+        order: PackingOrder, with_rlp: bool,
+    ) -> (Vec<Arc<SignedTransaction>>, Option<Vec<u8>>)
     {
         let mut inner = self.inner.write_with_metric(&PACK_TRANSACTION_LOCK);
         best_epoch_height += 1;
@@ -616,6 +784,8 @@ impl TransactionPool {
             best_block_number,
             &self.verification_config,
             &self.machine,
+            order,
+            with_rlp,
         )
     }
 
@@ -626,6 +796,20 @@ impl TransactionPool {
         inner.notify_modified_accounts(accounts_from_execution)
     }
 
+    /// Save all transactions in the pool to `w`, e.g. before shutdown.
+    pub fn save_to_writer<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        self.inner.read().save_to_writer(w)
+    }
+
+    /// Restore the transactions saved by `save_to_writer` against the best
+    /// executed state. Return the number of restored transactions.
+    pub fn load_from_reader<R: io::Read>(
+        &self, r: &mut R,
+    ) -> io::Result<usize> {
+        let account_cache = self.get_best_state_account_cache();
+        self.inner.write().load_from_reader(r, &account_cache)
+    }
+
     pub fn clear_tx_pool(&self) {
         let mut inner = self.inner.write();
         inner.clear()
@@ -646,22 +830,19 @@ impl TransactionPool {
         inner.total_received()
     }
 
-    pub fn total_unpacked(&self) -> usize {
+    pub fn lifetime_received(&self) -> usize {
         let inner = self.inner.read();
-        inner.total_unpacked()
+        inner.lifetime_received()
     }
 
-    /// stats retrieves the length of ready and deferred pool.
-    pub fn stats(&self) -> (usize, usize, usize, usize) {
+    pub fn total_unpacked(&self) -> usize {
         let inner = self.inner.read();
-        (
-            inner.total_ready_accounts(),
-            inner.total_deferred(),
-            inner.total_received(),
-            inner.total_unpacked(),
-        )
+        inner.total_unpacked()
     }
 
+    /// Return the sizes of the pool read under a single lock.
+    pub fn stats(&self) -> PoolStats { self.inner.read().stats() }
+
     /// content retrieves the ready and deferred transactions.
     pub fn content(
         &self, address: Option<Address>,
@@ -670,6 +851,208 @@ impl TransactionPool {
         inner.content(address)
     }
 
+    /// Like `content`, but only return a window of each list sorted by
+    /// `sort_by`, together with the total sizes.
+    pub fn content_paginated(
+        &self, address: Option<Address>, offset: usize, limit: usize,
+        sort_by: ContentSortOrder,
+    ) -> ContentPage
+    {
+        self.inner
+            .read()
+            .content_paginated(address, offset, limit, sort_by)
+    }
+
+    /// Return the sponsored gas and storage committed by `sponsor_contract`
+    /// for each sender.
+    pub fn sponsored_by_sender(
+        &self, sponsor_contract: &Address,
+    ) -> HashMap<Address, (U256, u64)> {
+        self.inner.read().sponsored_by_sender(sponsor_contract)
+    }
+
+    pub fn notify_sponsor_balance(
+        &self, sponsor_contract: Address, available_gas_balance: U256,
+        available_collateral: U256,
+    )
+    {
+        let mut inner = self.inner.write();
+        inner.notify_sponsor_balance(
+            sponsor_contract,
+            available_gas_balance,
+            available_collateral,
+        )
+    }
+
+    /// Return the unpacked transactions of the same sender that follow the
+    /// transaction `tx_hash` without a nonce gap.
+    pub fn dependents(&self, tx_hash: &H256) -> Vec<Arc<SignedTransaction>> {
+        self.inner.read().dependents(tx_hash)
+    }
+
+    /// Return the gas price that a new transaction has to exceed to evict a
+    /// transaction when the pool is full.
+    pub fn displacement_price(&self) -> Option<U256> {
+        self.inner.read().displacement_price()
+    }
+
+    /// Return the hashes of packed transactions that are already executed
+    /// according to the cached sender states.
+    pub fn confirmed_transactions(&self) -> Vec<H256> {
+        self.inner.read().confirmed_transactions()
+    }
+
+    /// Enable or disable adjusting the transaction weight scaling to the
+    /// observed gas prices of ready transactions.
+    pub fn set_auto_weight_scaling(&self, enabled: bool) {
+        self.inner.write().set_auto_weight_scaling(enabled)
+    }
+
+    /// Register `notifier` to receive the hash of every transaction that
+    /// leaves the pool together with the reason.
+    pub fn set_removal_notifier(
+        &self, notifier: mpsc::Sender<(H256, RemovalReason)>,
+    ) {
+        self.inner.write().set_removal_notifier(notifier)
+    }
+
+    /// Register `callback` to be called with each sender which turns from no
+    /// ready transaction to having one.
+    pub fn set_ready_callback(
+        &self, callback: impl Fn(&Address) + Send + Sync + 'static,
+    ) {
+        self.inner.write().set_ready_callback(callback)
+    }
+
+    /// Insert already verified transactions in a batch and return, for each
+    /// sender, the hashes of the transactions that became ready to be packed
+    /// because of the batch. See
+    /// `TransactionPoolInner::insert_transactions_batch`.
+    pub fn insert_transactions_batch(
+        &self, transactions: Vec<Arc<SignedTransaction>>, packed: bool,
+        force: bool,
+    ) -> (Vec<Result<(), String>>, HashMap<Address, Vec<H256>>)
+    {
+        let account_cache = self.get_best_state_account_cache();
+        self.inner.write().insert_transactions_batch(
+            &account_cache,
+            transactions,
+            packed,
+            force,
+        )
+    }
+
+    /// Estimate the bytes that would be freed by shrinking the pool to
+    /// `new_capacity` transactions.
+    pub fn estimate_shrink_savings(&self, new_capacity: usize) -> usize {
+        self.inner.read().estimate_shrink_savings(new_capacity)
+    }
+
+    /// Report the transactions in `txs` which conflict with each other or
+    /// with the packed transactions in the pool by sender and nonce.
+    pub fn validate_batch(
+        &self, txs: &[Arc<SignedTransaction>],
+    ) -> Vec<BatchConflict> {
+        self.inner.read().validate_batch(txs)
+    }
+
+    /// Return all transactions in the pool sorted by sender and then by
+    /// nonce.
+    pub fn all_ordered(&self) -> Vec<Arc<SignedTransaction>> {
+        self.inner.read().all_ordered()
+    }
+
+    /// Return the transactions in the pool which call `contract`.
+    pub fn transactions_to_contract(
+        &self, contract: &Address,
+    ) -> Vec<Arc<SignedTransaction>> {
+        self.inner.read().transactions_to_contract(contract)
+    }
+
+    /// Return at most `limit` transactions in the pool which call `callee`
+    /// with `selector` as the first 4 bytes of their data.
+    pub fn find_by_selector(
+        &self, callee: Address, selector: [u8; 4], limit: usize,
+    ) -> Vec<Arc<SignedTransaction>> {
+        self.inner.read().find_by_selector(callee, selector, limit)
+    }
+
+    /// Hold back a transaction and its dependents from packing without
+    /// removing them from the pool.
+    pub fn quarantine(&self, tx_hash: H256) -> bool {
+        self.inner.write().quarantine(tx_hash)
+    }
+
+    /// Allow a transaction held back by `quarantine` to be packed again.
+    pub fn release(&self, tx_hash: H256) -> bool {
+        self.inner.write().release(tx_hash)
+    }
+
+    /// Return at most `n` senders with the most transactions in the pool,
+    /// the largest first.
+    pub fn top_senders_by_count(&self, n: usize) -> Vec<(Address, usize)> {
+        self.inner.read().top_senders_by_count(n)
+    }
+
+    /// Set the nonce and balance of `addr` in the pool and recalculate its
+    /// readiness without reading the state.
+    pub fn set_account_state_and_recalculate(
+        &self, addr: &Address, nonce: U256, balance: U256,
+    ) {
+        self.inner
+            .write()
+            .set_account_state_and_recalculate(addr, nonce, balance);
+    }
+
+    /// Dump the state of every sender and transaction in the pool.
+    pub fn full_report(&self) -> PoolReport { self.inner.read().full_report() }
+
+    /// Return the senders tracked by the garbage collector in the order they
+    /// would be picked as victims.
+    pub fn gc_debug_snapshot(&self) -> Vec<GcEntry> {
+        self.inner.read().gc_debug_snapshot()
+    }
+
+    /// Return the hashes of the transactions which would be evicted to insert
+    /// `new_tx`, without changing the pool.
+    pub fn preview_eviction(&self, new_tx: &SignedTransaction) -> Vec<H256> {
+        self.inner.read().preview_eviction(new_tx)
+    }
+
+    /// Remove the unpacked transactions which have stayed in the pool for more
+    /// than `max_age_secs` seconds, and return their hashes.
+    pub fn expire_transactions(&self, max_age_secs: u64) -> Vec<H256> {
+        self.inner.write().expire_transactions(max_age_secs)
+    }
+
+    /// Return at most `limit` senders whose first unpacked transaction that
+    /// is not ready is pending for `reason_filter`, or any reason if it is
+    /// `None`.
+    pub fn get_all_pending(
+        &self, reason_filter: Option<PendingReason>, limit: usize,
+    ) -> Vec<(Address, Arc<SignedTransaction>, PendingReason)> {
+        self.inner.read().get_all_pending(reason_filter, limit)
+    }
+
+    pub fn gc_stats(&self) -> GcStats { self.inner.read().gc_stats() }
+
+    /// Return the ready transactions sorted by their packing priority.
+    pub fn ready_sorted_by_priority(&self) -> Vec<Arc<SignedTransaction>> {
+        self.inner.read().ready_sorted_by_priority()
+    }
+
+    /// Return the gas price at `percentile` (0 to 100) among the recently
+    /// packed transactions.
+    pub fn recent_packed_fee_percentile(&self, percentile: u8) -> Option<U256> {
+        self.inner.read().recent_packed_fee_percentile(percentile)
+    }
+
+    /// Return the gas price at `percentile` (0 to 100) among the ready
+    /// transactions, or zero if the pool is not full.
+    pub fn suggested_gas_price(&self, percentile: u8) -> U256 {
+        self.inner.read().suggested_gas_price(percentile)
+    }
+
     pub fn notify_new_best_info(
         &self, best_info: Arc<BestInformation>,
     ) -> StateDbResult<()> {
@@ -692,6 +1075,7 @@ impl TransactionPool {
                 tx,
                 true,
                 false,
+                false,
             )
             .ok();
         }
@@ -734,6 +1118,7 @@ impl TransactionPool {
                 tx,
                 false,
                 true,
+                false,
             )
             .ok();
         }
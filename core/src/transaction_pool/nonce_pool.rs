@@ -23,7 +23,12 @@ impl TxWithReadyInfo {
 
     pub fn get_arc_tx(&self) -> &Arc<SignedTransaction> { &self.transaction }
 
-    pub fn should_replace(&self, x: &Self, force: bool) -> bool {
+    /// Whether `self` can replace `x` with the same nonce. If
+    /// `min_bump_percent` is not zero, the gas price of `self` must be at
+    /// least that much higher than `x`'s.
+    pub fn should_replace(
+        &self, x: &Self, force: bool, min_bump_percent: u64,
+    ) -> bool {
         if force {
             return true;
         }
@@ -33,10 +38,31 @@ impl TxWithReadyInfo {
         if self.is_already_packed() {
             return true;
         }
+        if min_bump_percent > 0 {
+            return self.gas_price
+                >= x.min_replacement_gas_price(min_bump_percent);
+        }
         self.gas_price > x.gas_price
             || self.gas_price == x.gas_price
                 && self.epoch_height > x.epoch_height
     }
+
+    /// The lowest gas price to replace `self` if it must be increased by at
+    /// least `min_bump_percent` percent, rounded up.
+    fn min_replacement_gas_price(&self, min_bump_percent: u64) -> U256 {
+        let bumped = self.gas_price.saturating_mul(U256::from(
+            100u64.saturating_add(min_bump_percent),
+        ));
+        bumped.saturating_add(U256::from(99)) / U256::from(100)
+    }
+
+    fn replace_failure(&self, min_bump_percent: u64) -> InsertResult {
+        if min_bump_percent > 0 {
+            InsertResult::Failed(format!("Tx with same nonce already inserted. To replace it, you need to specify a gas price >= {} ({}% higher)", self.min_replacement_gas_price(min_bump_percent), min_bump_percent))
+        } else {
+            InsertResult::Failed(format!("Tx with same nonce already inserted. To replace it, you need to specify a gas price > {}", &self.gas_price))
+        }
+    }
 }
 
 impl Deref for TxWithReadyInfo {
@@ -158,7 +184,7 @@ impl NoncePoolNode {
     /// will replace with higher gas price transaction
     pub fn insert(
         node: &mut Option<Box<NoncePoolNode>>, tx: &TxWithReadyInfo,
-        priority: u64, force: bool,
+        priority: u64, force: bool, min_bump_percent: u64,
     ) -> InsertResult
     {
         if node.is_none() {
@@ -168,13 +194,17 @@ impl NoncePoolNode {
         let cmp = tx.nonce().cmp(&node.as_ref().unwrap().tx.nonce);
         if cmp == Ordering::Equal {
             let result = {
-                if tx.should_replace(&node.as_ref().unwrap().tx, force) {
+                if tx.should_replace(
+                    &node.as_ref().unwrap().tx,
+                    force,
+                    min_bump_percent,
+                ) {
                     InsertResult::Updated(mem::replace(
                         &mut node.as_mut().unwrap().tx,
                         tx.clone(),
                     ))
                 } else {
-                    InsertResult::Failed(format!("Tx with same nonce already inserted. To replace it, you need to specify a gas price > {}", &node.as_ref().unwrap().tx.gas_price))
+                    node.as_ref().unwrap().tx.replace_failure(min_bump_percent)
                 }
             };
             node.as_mut().unwrap().update();
@@ -186,6 +216,7 @@ impl NoncePoolNode {
                 tx,
                 priority,
                 force,
+                min_bump_percent,
             );
             if node.as_ref().unwrap().priority
                 < node.as_ref().unwrap().child[d].as_ref().unwrap().priority
@@ -339,6 +370,18 @@ impl NoncePoolNode {
         }
     }
 
+    /// append the transactions in the subtree to `txs` in nonce order
+    fn collect(
+        node: Option<Box<NoncePoolNode>>, txs: &mut Vec<TxWithReadyInfo>,
+    ) {
+        if let Some(node) = node {
+            let NoncePoolNode { tx, child: [left, right], .. } = *node;
+            NoncePoolNode::collect(left, txs);
+            txs.push(tx);
+            NoncePoolNode::collect(right, txs);
+        }
+    }
+
     /// return the size and the sum of balance of current subtree
     fn size(node: &Option<Box<NoncePoolNode>>) -> (u32, U256) {
         if node.is_none() {
@@ -352,21 +395,40 @@ impl NoncePoolNode {
     }
 }
 
+/// A sender with at most this many transactions keeps them in a sorted vector
+/// instead of a treap. The `nonce_pool` bench of this crate compares the two
+/// representations over senders of different sizes, to tune it with.
+const COMPACT_NONCE_POOL_MAX_SIZE: usize = 16;
+/// A treap shrinking to this size is turned back into a sorted vector. It is
+/// smaller than `COMPACT_NONCE_POOL_MAX_SIZE` so that a sender around the
+/// threshold does not convert on every insertion and removal.
+const COMPACT_NONCE_POOL_DEMOTE_SIZE: usize = COMPACT_NONCE_POOL_MAX_SIZE / 2;
+
+/// The storage of a `NoncePool`. Most senders only have a short chain of
+/// transactions, which is cheaper to keep in a vector sorted by nonce.
+enum NoncePoolRepr {
+    Compact(Vec<TxWithReadyInfo>),
+    Treap(Option<Box<NoncePoolNode>>),
+}
+
 pub struct NoncePool {
-    root: Option<Box<NoncePoolNode>>,
+    repr: NoncePoolRepr,
     rng: XorShiftRng,
 }
 
 impl MallocSizeOf for NoncePool {
     fn size_of(&self, ops: &mut MallocSizeOfOps) -> usize {
-        self.root.size_of(ops)
+        match &self.repr {
+            NoncePoolRepr::Compact(txs) => txs.size_of(ops),
+            NoncePoolRepr::Treap(root) => root.size_of(ops),
+        }
     }
 }
 
 impl NoncePool {
     pub fn new() -> Self {
         NoncePool {
-            root: None,
+            repr: NoncePoolRepr::Compact(Vec::new()),
             rng: XorShiftRng::from_entropy(),
         }
     }
@@ -374,25 +436,75 @@ impl NoncePool {
     // FIXME: later we should limit the number of txs from one sender.
     //  the FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET roughly doing this job
     pub fn insert(
-        &mut self, tx: &TxWithReadyInfo, force: bool,
+        &mut self, tx: &TxWithReadyInfo, force: bool, min_bump_percent: u64,
     ) -> InsertResult {
-        NoncePoolNode::insert(&mut self.root, tx, self.rng.next_u64(), force)
+        let result = match &mut self.repr {
+            NoncePoolRepr::Compact(txs) => {
+                match txs.binary_search_by(|x| x.nonce.cmp(&tx.nonce)) {
+                    Ok(i) => {
+                        if tx.should_replace(&txs[i], force, min_bump_percent) {
+                            InsertResult::Updated(mem::replace(
+                                &mut txs[i],
+                                tx.clone(),
+                            ))
+                        } else {
+                            txs[i].replace_failure(min_bump_percent)
+                        }
+                    }
+                    Err(i) => {
+                        txs.insert(i, tx.clone());
+                        InsertResult::NewAdded
+                    }
+                }
+            }
+            NoncePoolRepr::Treap(root) => {
+                NoncePoolNode::insert(
+                    root,
+                    tx,
+                    self.rng.next_u64(),
+                    force,
+                    min_bump_percent,
+                )
+            }
+        };
+        if let NoncePoolRepr::Compact(txs) = &self.repr {
+            if txs.len() > COMPACT_NONCE_POOL_MAX_SIZE {
+                self.promote();
+            }
+        }
+        result
     }
 
     pub fn get_tx_by_nonce(&self, nonce: U256) -> Option<TxWithReadyInfo> {
-        self.root
-            .as_ref()
-            .and_then(|node| node.get(&nonce).map(|x| x.clone()))
+        self.get(&nonce).map(|x| x.clone())
     }
 
     pub fn get_lowest_nonce_tx(&self) -> Option<&SignedTransaction> {
-        self.root
-            .as_ref()
-            .and_then(|node| node.leftmost().map(|x| x.transaction.as_ref()))
+        let lowest = match &self.repr {
+            NoncePoolRepr::Compact(txs) => txs.first(),
+            NoncePoolRepr::Treap(root) => {
+                root.as_ref().and_then(|node| node.leftmost())
+            }
+        };
+        lowest.map(|x| x.transaction.as_ref())
     }
 
     pub fn remove(&mut self, nonce: &U256) -> Option<TxWithReadyInfo> {
-        NoncePoolNode::remove(&mut self.root, nonce)
+        match &mut self.repr {
+            NoncePoolRepr::Compact(txs) => txs
+                .binary_search_by(|x| x.nonce.cmp(nonce))
+                .ok()
+                .map(|i| txs.remove(i)),
+            NoncePoolRepr::Treap(root) => {
+                let result = NoncePoolNode::remove(root, nonce);
+                if (NoncePoolNode::size(root).0 as usize)
+                    <= COMPACT_NONCE_POOL_DEMOTE_SIZE
+                {
+                    self.demote();
+                }
+                result
+            }
+        }
     }
 
     pub fn remove_lowest_nonce(&mut self) -> Option<TxWithReadyInfo> {
@@ -403,10 +515,7 @@ impl NoncePool {
     pub fn get_pending_info(
         &self, nonce: &U256,
     ) -> Option<(usize, Arc<SignedTransaction>)> {
-        let tx = self
-            .root
-            .as_ref()
-            .and_then(|node| node.succ(&nonce).map(|x| x.clone()));
+        let tx = self.succ(&nonce).map(|x| x.clone());
         if let Some(tx) = tx {
             let pending_count = self.count_from(&(nonce));
             Some((pending_count, tx.transaction))
@@ -420,19 +529,15 @@ impl NoncePool {
         &self, nonce: &U256,
     ) -> Vec<Arc<SignedTransaction>> {
         let mut pending_txs = Vec::new();
-        let mut maybe_tx_info = self
-            .root
-            .as_ref()
-            .and_then(|node| node.succ(&nonce).map(|x| x.clone()));
+        let mut maybe_tx_info = self.succ(&nonce).map(|x| x.clone());
         // TODO: More efficient traversal of Treap.
         while let Some(tx_info) = maybe_tx_info {
             if !tx_info.packed {
                 pending_txs.push(tx_info.transaction.clone());
             }
-            maybe_tx_info = self.root.as_ref().and_then(|node| {
-                node.succ(&(tx_info.transaction.nonce + U256::from(1)))
-                    .map(|x| x.clone())
-            });
+            maybe_tx_info = self
+                .succ(&(tx_info.transaction.nonce + U256::from(1)))
+                .map(|x| x.clone());
         }
         pending_txs
     }
@@ -443,13 +548,13 @@ impl NoncePool {
     pub fn recalculate_readiness_with_local_info(
         &self, nonce: U256, balance: U256,
     ) -> Option<Arc<SignedTransaction>> {
-        NoncePoolNode::query(&self.root, &nonce).filter(|x| {
+        self.query(&nonce).filter(|x| {
             let a = if nonce == U256::from(0) {
                 (0, U256::from(0))
             } else {
-                NoncePoolNode::rank(&self.root, &(nonce - 1))
+                self.rank(&(nonce - 1))
             };
-            let b = NoncePoolNode::rank(&self.root, &x.nonce);
+            let b = self.rank(&x.nonce);
             // 1. b.1 - a.1 means the sum of cost of transactions in `[nonce,
             // tx.nonce]`
             // 2. b.0 - a.0 means number of transactions in `[nonce, tx.nonce]`
@@ -465,9 +570,9 @@ impl NoncePool {
         let a = if nonce == U256::from(0) {
             (0, U256::from(0))
         } else {
-            NoncePoolNode::rank(&self.root, &(nonce - 1))
+            self.rank(&(nonce - 1))
         };
-        let b = NoncePoolNode::rank(&self.root, &pending_tx.nonce);
+        let b = self.rank(&pending_tx.nonce);
         // 1. b.1 - a.1 means the sum of cost of transactions in `[nonce,
         // tx.nonce]`
         // 2. b.0 - a.0 means number of transactions in `[nonce, tx.nonce]`
@@ -485,37 +590,141 @@ impl NoncePool {
         None
     }
 
-    pub fn is_empty(&self) -> bool { self.root.is_none() }
+    pub fn is_empty(&self) -> bool {
+        match &self.repr {
+            NoncePoolRepr::Compact(txs) => txs.is_empty(),
+            NoncePoolRepr::Treap(root) => root.is_none(),
+        }
+    }
 
     /// return the number of transactions whose nonce < `nonce`
     pub fn count_less(&self, nonce: &U256) -> usize {
         if *nonce == U256::from(0) {
             0
         } else {
-            NoncePoolNode::rank(&self.root, &(nonce - 1)).0 as usize
+            self.rank(&(nonce - 1)).0 as usize
         }
     }
 
     /// return the number of transactions whose nonce >= `nonce`
     pub fn count_from(&self, nonce: &U256) -> usize {
-        NoncePoolNode::size(&self.root).0 as usize - self.count_less(nonce)
+        self.len() - self.count_less(nonce)
     }
 
     pub fn check_nonce_exists(&self, nonce: &U256) -> bool {
-        self.root
-            .as_ref()
-            .and_then(|node| node.get(&nonce))
-            .is_some()
+        self.get(&nonce).is_some()
     }
 
     pub fn succ_nonce(&self, nonce: &U256) -> Option<U256> {
-        self.root.as_ref()?.succ(nonce).map(|tx| tx.nonce)
+        self.succ(nonce).map(|tx| tx.nonce)
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.repr {
+            NoncePoolRepr::Compact(txs) => txs.len(),
+            NoncePoolRepr::Treap(root) => NoncePoolNode::size(root).0 as usize,
+        }
+    }
+
+    fn get(&self, nonce: &U256) -> Option<&TxWithReadyInfo> {
+        match &self.repr {
+            NoncePoolRepr::Compact(txs) => txs
+                .binary_search_by(|x| x.nonce.cmp(nonce))
+                .ok()
+                .map(|i| &txs[i]),
+            NoncePoolRepr::Treap(root) => {
+                root.as_ref().and_then(|node| node.get(nonce))
+            }
+        }
+    }
+
+    /// return the transaction with the minimum nonce >= `nonce`
+    fn succ(&self, nonce: &U256) -> Option<&TxWithReadyInfo> {
+        match &self.repr {
+            NoncePoolRepr::Compact(txs) => {
+                txs.iter().find(|x| x.nonce >= *nonce)
+            }
+            NoncePoolRepr::Treap(root) => {
+                root.as_ref().and_then(|node| node.succ(nonce))
+            }
+        }
+    }
+
+    /// find number of transactions and sum of cost whose nonce <= `nonce`
+    fn rank(&self, nonce: &U256) -> (u32, U256) {
+        match &self.repr {
+            NoncePoolRepr::Compact(txs) => txs
+                .iter()
+                .take_while(|x| x.nonce <= *nonce)
+                .fold((0, 0.into()), |(count, cost), x| {
+                    (count + 1, cost + NoncePoolNode::calc_tx_cost(x))
+                }),
+            NoncePoolRepr::Treap(root) => NoncePoolNode::rank(root, nonce),
+        }
+    }
+
+    /// find an unpacked transaction `tx` where `tx.nonce >= nonce`
+    /// and `tx.nonce` is minimum
+    fn query(&self, nonce: &U256) -> Option<Arc<SignedTransaction>> {
+        match &self.repr {
+            NoncePoolRepr::Compact(txs) => txs
+                .iter()
+                .find(|x| x.nonce >= *nonce && !x.packed)
+                .map(|x| x.transaction.clone()),
+            NoncePoolRepr::Treap(root) => NoncePoolNode::query(root, nonce),
+        }
+    }
+
+    /// Move the transactions from the sorted vector to a treap.
+    fn promote(&mut self) {
+        if let NoncePoolRepr::Compact(txs) = &mut self.repr {
+            let mut root = None;
+            for tx in mem::take(txs) {
+                NoncePoolNode::insert(
+                    &mut root,
+                    &tx,
+                    self.rng.next_u64(),
+                    false, /* force */
+                    0,     /* min_bump_percent */
+                );
+            }
+            self.repr = NoncePoolRepr::Treap(root);
+        }
+    }
+
+    /// Move the transactions from the treap to a sorted vector.
+    fn demote(&mut self) {
+        if let NoncePoolRepr::Treap(root) = &mut self.repr {
+            let mut txs = Vec::with_capacity(COMPACT_NONCE_POOL_MAX_SIZE);
+            NoncePoolNode::collect(root.take(), &mut txs);
+            self.repr = NoncePoolRepr::Compact(txs);
+        }
+    }
+}
+
+#[cfg(any(test, feature = "testonly_code"))]
+impl NoncePool {
+    /// Build a pool of `txs`, which are sorted by nonce, in the compact or
+    /// the treap representation regardless of its size, so that the benches
+    /// can compare the two.
+    pub fn with_representation(
+        txs: Vec<TxWithReadyInfo>, compact: bool,
+    ) -> Self {
+        let mut nonce_pool = NoncePool::new();
+        nonce_pool.repr = NoncePoolRepr::Compact(txs);
+        if !compact {
+            nonce_pool.promote();
+        }
+        nonce_pool
     }
 }
 
 #[cfg(test)]
 mod nonce_pool_test {
-    use super::{InsertResult, NoncePool, TxWithReadyInfo};
+    use super::{
+        InsertResult, NoncePool, NoncePoolRepr, TxWithReadyInfo,
+        COMPACT_NONCE_POOL_DEMOTE_SIZE, COMPACT_NONCE_POOL_MAX_SIZE,
+    };
     use crate::transaction_pool::nonce_pool::NoncePoolNode;
     use cfx_parameters::staking::DRIPS_PER_STORAGE_COLLATERAL_UNIT;
     use cfx_types::{Address, U128, U256};
@@ -734,17 +943,25 @@ mod nonce_pool_test {
         assert_eq!(nonce_pool.is_empty(), true);
         for i in 0..10 {
             assert_eq!(
-                nonce_pool.insert(&tx1[i as usize], false /* force */),
+                nonce_pool.insert(
+                    &tx1[i as usize],
+                    false, /* force */
+                    0,     /* min_bump_percent */
+                ),
                 InsertResult::NewAdded
             );
             assert_eq!(
                 nonce_pool.get_tx_by_nonce(U256::from(i)),
                 Some(tx1[i].clone())
             );
-            assert_eq!(nonce_pool.insert(&tx2[i as usize], false /* force */),
+            assert_eq!(nonce_pool.insert(&tx2[i as usize], false /* force */, 0 /* min_bump_percent */),
                        InsertResult::Failed(format!("Tx with same nonce already inserted. To replace it, you need to specify a gas price > {}", &tx1[i as usize].gas_price)));
             assert_eq!(
-                nonce_pool.insert(&tx2[i as usize], true /* force */),
+                nonce_pool.insert(
+                    &tx2[i as usize],
+                    true,  /* force */
+                    0,     /* min_bump_percent */
+                ),
                 InsertResult::Updated(tx1[i as usize].clone())
             );
             assert_eq!(nonce_pool.is_empty(), false);
@@ -805,7 +1022,11 @@ mod nonce_pool_test {
 
         for i in vec![0, 1, 3, 4] {
             assert_eq!(
-                nonce_pool.insert(&tx[i], false /* force */),
+                nonce_pool.insert(
+                    &tx[i],
+                    false, /* force */
+                    0,     /* min_bump_percent */
+                ),
                 InsertResult::NewAdded
             );
             assert_eq!(
@@ -831,7 +1052,11 @@ mod nonce_pool_test {
             None
         );
         assert_eq!(
-            nonce_pool.insert(&tx[2], false /* force */),
+            nonce_pool.insert(
+                &tx[2],
+                false, /* force */
+                0,     /* min_bump_percent */
+            ),
             InsertResult::NewAdded
         );
         assert_eq!(
@@ -926,12 +1151,20 @@ mod nonce_pool_test {
             let nonce: usize = rng.next_u64() as usize % count;
             if mock_nonce_pool.contains_key(&nonce.into()) {
                 assert_eq!(
-                    nonce_pool.insert(&tx[nonce], true /* force */),
+                    nonce_pool.insert(
+                        &tx[nonce],
+                        true,  /* force */
+                        0,     /* min_bump_percent */
+                    ),
                     InsertResult::Updated(tx[nonce].clone())
                 );
             } else {
                 assert_eq!(
-                    nonce_pool.insert(&tx[nonce], false /* force */),
+                    nonce_pool.insert(
+                        &tx[nonce],
+                        false, /* force */
+                        0,     /* min_bump_percent */
+                    ),
                     InsertResult::NewAdded
                 );
                 mock_nonce_pool.insert(nonce.into(), tx[nonce].clone());
@@ -973,12 +1206,20 @@ mod nonce_pool_test {
             let nonce: usize = rng.next_u64() as usize % count;
             if mock_nonce_pool.contains_key(&nonce.into()) {
                 assert_eq!(
-                    nonce_pool.insert(&tx[nonce], true /* force */),
+                    nonce_pool.insert(
+                        &tx[nonce],
+                        true,  /* force */
+                        0,     /* min_bump_percent */
+                    ),
                     InsertResult::Updated(tx[nonce].clone())
                 );
             } else {
                 assert_eq!(
-                    nonce_pool.insert(&tx[nonce], false /* force */),
+                    nonce_pool.insert(
+                        &tx[nonce],
+                        false, /* force */
+                        0,     /* min_bump_percent */
+                    ),
                     InsertResult::NewAdded
                 );
                 mock_nonce_pool.insert(nonce.into(), tx[nonce].clone());
@@ -1006,4 +1247,73 @@ mod nonce_pool_test {
             );
         }
     }
+
+    #[test]
+    fn test_representation_switch() {
+        let me = Random.generate().unwrap();
+        let gas = U256::from(50000);
+        let gas_price = U256::from(10);
+        let value = U256::from(10000);
+        let mut nonce_pool = NoncePool::new();
+        let count = COMPACT_NONCE_POOL_MAX_SIZE + 4;
+        let txs: Vec<_> = (0..count)
+            .map(|i| {
+                new_test_tx_with_ready_info(
+                    &me,
+                    i.into(),
+                    gas,
+                    gas_price,
+                    value,
+                    0,
+                    i % 3 == 0,
+                )
+            })
+            .collect();
+        for tx in txs.iter().rev() {
+            assert_eq!(
+                nonce_pool.insert(
+                    tx,
+                    false, /* force */
+                    0,     /* min_bump_percent */
+                ),
+                InsertResult::NewAdded
+            );
+        }
+        assert!(matches!(nonce_pool.repr, NoncePoolRepr::Treap(_)));
+        assert_eq!(nonce_pool.count_from(&0.into()), count);
+        assert_eq!(
+            nonce_pool.get_lowest_nonce_tx(),
+            Some(txs[0].transaction.as_ref())
+        );
+        assert_eq!(
+            nonce_pool.recalculate_readiness_with_local_info(
+                0.into(),
+                U256::max_value(),
+            ),
+            Some(txs[1].transaction.clone())
+        );
+
+        while nonce_pool.count_from(&0.into())
+            > COMPACT_NONCE_POOL_DEMOTE_SIZE
+        {
+            assert!(matches!(nonce_pool.repr, NoncePoolRepr::Treap(_)));
+            nonce_pool.remove_lowest_nonce();
+        }
+        assert!(matches!(nonce_pool.repr, NoncePoolRepr::Compact(_)));
+        let lowest = count - COMPACT_NONCE_POOL_DEMOTE_SIZE;
+        assert_eq!(
+            nonce_pool.get_lowest_nonce_tx(),
+            Some(txs[lowest].transaction.as_ref())
+        );
+        assert_eq!(
+            nonce_pool.get_pending_transactions(&0.into()),
+            txs[lowest..]
+                .iter()
+                .filter(|tx| !tx.packed)
+                .map(|tx| tx.transaction.clone())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(nonce_pool.count_less(&(lowest + 2).into()), 2);
+        assert_eq!(nonce_pool.succ_nonce(&0.into()), Some(lowest.into()));
+    }
 }
@@ -1,8 +1,9 @@
 use super::{
     account_cache::AccountCache,
-    garbage_collector::GarbageCollector,
+    garbage_collector::{GarbageCollector, GarbageCollectorNode},
     impls::TreapMap,
     nonce_pool::{InsertResult, NoncePool, TxWithReadyInfo},
+    TxPoolConfig,
 };
 use crate::{
     machine::Machine,
@@ -11,30 +12,54 @@ use crate::{
 use cfx_parameters::staking::DRIPS_PER_STORAGE_COLLATERAL_UNIT;
 use cfx_statedb::Result as StateDbResult;
 use cfx_types::{address_util::AddressUtil, Address, H256, U128, U256, U512};
+use malloc_size_of::{new_malloc_size_ops, MallocSizeOf};
 use malloc_size_of_derive::MallocSizeOf as DeriveMallocSizeOf;
 use metrics::{
-    register_meter_with_group, Counter, CounterUsize, Meter, MeterTimer,
+    register_meter_with_group, Counter, CounterUsize, Histogram, Meter,
+    MeterTimer, Sample,
 };
+use parking_lot::Mutex;
 use primitives::{
     Account, Action, SignedTransaction, TransactionWithSignature,
 };
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use rlp::*;
 use serde::Serialize;
 use std::{
-    collections::HashMap,
-    sync::Arc,
+    cmp::{self, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    io,
+    sync::{mpsc, Arc},
     time::{SystemTime, UNIX_EPOCH},
 };
 
 type WeightType = u128;
+/// The ready transactions ordered by gas price, see
+/// `ReadyAccountPool::pop_by_price`.
+type PriceQueue = BinaryHeap<(U256, Reverse<Address>)>;
 lazy_static! {
     pub static ref MAX_WEIGHT: U256 = u128::max_value().into();
 }
 
-const FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET: u32 = 2000;
-/// The max number of senders we compare gas price with a new inserted
-/// transaction.
-const GC_CHECK_COUNT: usize = 5;
+/// The default of `furthest_future_nonce_offset`.
+pub const FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET: u32 = 2000;
+/// The default max number of senders `collect_garbage` checks to pick a
+/// victim.
+pub const DEFAULT_GC_CHECK_COUNT: usize = 5;
+/// With auto weight scaling, `tx_weight_scaling` is set to the median gas
+/// price of the ready transactions divided by this value.
+const AUTO_WEIGHT_SCALING_DIVISOR: u64 = 1000;
+/// With auto weight scaling, the base weight of a transaction whose gas price
+/// is below `tx_weight_scaling`, so that it is not starved with a zero weight.
+const MIN_AUTO_SCALED_WEIGHT: WeightType = 1;
+/// The granularity in seconds of the aging counted in the weights of the
+/// ready transactions. `refresh_aging` only re-weights the ready pool once
+/// this much time has passed since the last refresh.
+const TX_WEIGHT_AGING_GRANULARITY: u64 = 10;
+/// The interval in seconds between the updates of the auto weight scaling by
+/// `refresh_weight_scaling`, so that packing does not compute the median gas
+/// price and re-weight the ready pool for every block.
+const TX_WEIGHT_SCALING_INTERVAL: u64 = 10;
 
 lazy_static! {
     static ref TX_POOL_RECALCULATE: Arc<dyn Meter> =
@@ -56,17 +81,43 @@ lazy_static! {
         CounterUsize::register_with_group("txpool", "gc_ready");
     static ref GC_METER: Arc<dyn Meter> =
         register_meter_with_group("txpool", "gc_txs_tps");
+    static ref GC_VICTIM_GAS_PRICE: Arc<dyn Histogram> =
+        Sample::ExpDecay(0.015).register_with_group(
+            "txpool",
+            "gc_victim_gas_price",
+            1024
+        );
+    static ref GC_NO_VICTIM_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group("txpool", "gc_no_victim");
+    static ref GC_SPARED_SENDER_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group("txpool", "gc_spared_sender");
+    static ref GC_SPARED_LOCAL_COUNTER: Arc<dyn Counter<usize>> =
+        CounterUsize::register_with_group("txpool", "gc_spared_local");
+}
+
+#[cfg(test)]
+thread_local! {
+    /// The number of recalculations timed by `TX_POOL_RECALCULATE` in this
+    /// thread, since the timer is a noop when metrics are disabled.
+    static RECALCULATE_COUNT: std::cell::Cell<usize> = std::cell::Cell::new(0);
 }
 
 #[derive(DeriveMallocSizeOf)]
 struct DeferredPool {
     buckets: HashMap<Address, NoncePool>,
+    /// The max number of transactions a sender can have in the pool.
+    max_txs_per_sender: usize,
+    /// The percentage by which a transaction must raise the gas price of the
+    /// one with the same sender and nonce to replace it.
+    min_replace_bump_percent: u64,
 }
 
 impl DeferredPool {
-    fn new() -> Self {
+    fn new(max_txs_per_sender: usize, min_replace_bump_percent: u64) -> Self {
         DeferredPool {
             buckets: Default::default(),
+            max_txs_per_sender,
+            min_replace_bump_percent,
         }
     }
 
@@ -76,7 +127,7 @@ impl DeferredPool {
         // It's safe to create a new bucket, cause inserting to a empty bucket
         // will always be success
         let bucket = self.buckets.entry(tx.sender).or_insert(NoncePool::new());
-        bucket.insert(&tx, force)
+        bucket.insert(&tx, force, self.min_replace_bump_percent)
     }
 
     fn contain_address(&self, addr: &Address) -> bool {
@@ -93,6 +144,14 @@ impl DeferredPool {
         }
     }
 
+    /// Whether `sender` has `max_txs_per_sender` transactions already.
+    fn is_sender_full(&self, sender: &Address) -> bool {
+        self.buckets
+            .get(sender)
+            .map_or(0, |bucket| bucket.len())
+            >= self.max_txs_per_sender
+    }
+
     fn count_less(&self, sender: &Address, nonce: &U256) -> usize {
         if let Some(bucket) = self.buckets.get(sender) {
             bucket.count_less(nonce)
@@ -116,6 +175,17 @@ impl DeferredPool {
         }
     }
 
+    fn remove(
+        &mut self, addr: &Address, nonce: &U256,
+    ) -> Option<TxWithReadyInfo> {
+        let bucket = self.buckets.get_mut(addr)?;
+        let ret = bucket.remove(nonce);
+        if bucket.is_empty() {
+            self.buckets.remove(addr);
+        }
+        ret
+    }
+
     fn get_lowest_nonce(&self, addr: &Address) -> Option<&U256> {
         self.buckets
             .get(addr)
@@ -130,6 +200,16 @@ impl DeferredPool {
             .and_then(|bucket| bucket.get_lowest_nonce_tx())
     }
 
+    /// Return the transaction of `addr` with the smallest nonce which is not
+    /// less than `nonce`.
+    fn get_lowest_nonce_tx_from(
+        &self, addr: &Address, nonce: U256,
+    ) -> Option<Arc<SignedTransaction>> {
+        let bucket = self.buckets.get(addr)?;
+        let nonce = bucket.succ_nonce(&nonce)?;
+        bucket.get_tx_by_nonce(nonce).map(|tx| tx.transaction)
+    }
+
     fn recalculate_readiness_with_local_info(
         &mut self, addr: &Address, nonce: U256, balance: U256,
     ) -> Option<Arc<SignedTransaction>> {
@@ -171,6 +251,29 @@ impl DeferredPool {
         }
     }
 
+    /// Return the first unpacked transaction of `addr` from `nonce` which is
+    /// not ready and why it is pending.
+    fn first_pending_tx(
+        &self, addr: &Address, nonce: U256, balance: U256,
+    ) -> Option<(Arc<SignedTransaction>, PendingReason)> {
+        let bucket = self.buckets.get(addr)?;
+        let mut next_nonce = bucket.succ_nonce(&nonce);
+        while let Some(tx_nonce) = next_nonce {
+            let tx_info = bucket.get_tx_by_nonce(tx_nonce)?;
+            if !tx_info.is_already_packed() {
+                if let Some(reason) = bucket
+                    .check_pending_reason_with_local_info(
+                        nonce, balance, &tx_info,
+                    )
+                {
+                    return Some((tx_info.transaction, reason));
+                }
+            }
+            next_nonce = bucket.succ_nonce(&(tx_nonce + U256::from(1)));
+        }
+        None
+    }
+
     fn check_tx_packed(&self, addr: Address, nonce: U256) -> bool {
         if let Some(bucket) = self.buckets.get(&addr) {
             if let Some(tx_with_ready_info) = bucket.get_tx_by_nonce(nonce) {
@@ -183,6 +286,108 @@ impl DeferredPool {
         }
     }
 
+    /// Return all transactions of `addr` in nonce order.
+    fn all_txs(&self, addr: &Address) -> Vec<Arc<SignedTransaction>> {
+        let mut txs = Vec::new();
+        if let Some(bucket) = self.buckets.get(addr) {
+            let mut next_nonce = bucket.succ_nonce(&U256::zero());
+            while let Some(tx_nonce) = next_nonce {
+                if let Some(tx_info) = bucket.get_tx_by_nonce(tx_nonce) {
+                    txs.push(tx_info.transaction);
+                }
+                next_nonce = bucket.succ_nonce(&(tx_nonce + U256::from(1)));
+            }
+        }
+        txs
+    }
+
+    /// Return the packed transactions of `addr` whose nonces are less than
+    /// `nonce`, in nonce order.
+    fn packed_txs_below(
+        &self, addr: &Address, nonce: U256,
+    ) -> Vec<Arc<SignedTransaction>> {
+        let mut txs = Vec::new();
+        if let Some(bucket) = self.buckets.get(addr) {
+            let mut next_nonce = bucket.succ_nonce(&U256::zero());
+            while let Some(tx_nonce) = next_nonce.filter(|n| *n < nonce) {
+                if let Some(tx_info) = bucket.get_tx_by_nonce(tx_nonce) {
+                    if tx_info.is_already_packed() {
+                        txs.push(tx_info.transaction);
+                    }
+                }
+                next_nonce = bucket.succ_nonce(&(tx_nonce + U256::from(1)));
+            }
+        }
+        txs
+    }
+
+    /// Return the unpacked transactions of `addr` whose nonces are from
+    /// `from_nonce` to `to_nonce` inclusive, in nonce order.
+    fn unpacked_txs_in_range(
+        &self, addr: &Address, from_nonce: U256, to_nonce: U256,
+    ) -> Vec<Arc<SignedTransaction>> {
+        let mut txs = Vec::new();
+        if let Some(bucket) = self.buckets.get(addr) {
+            let mut next_nonce = bucket.succ_nonce(&from_nonce);
+            while let Some(tx_nonce) = next_nonce.filter(|n| *n <= to_nonce) {
+                if let Some(tx_info) = bucket.get_tx_by_nonce(tx_nonce) {
+                    if !tx_info.is_already_packed() {
+                        txs.push(tx_info.transaction);
+                    }
+                }
+                next_nonce = bucket.succ_nonce(&(tx_nonce + U256::from(1)));
+            }
+        }
+        txs
+    }
+
+    /// Return the unpacked transactions of `addr` with contiguous nonces
+    /// right after `nonce`, in nonce order.
+    fn contiguous_txs_after(
+        &self, addr: &Address, nonce: U256,
+    ) -> Vec<Arc<SignedTransaction>> {
+        let mut txs = Vec::new();
+        if let Some(bucket) = self.buckets.get(addr) {
+            let mut next_nonce = nonce + U256::from(1);
+            while let Some(tx_info) = bucket.get_tx_by_nonce(next_nonce) {
+                if !tx_info.is_already_packed() {
+                    txs.push(tx_info.transaction);
+                }
+                next_nonce += 1.into();
+            }
+        }
+        txs
+    }
+
+    /// Return the hashes of the unpacked transactions of `addr` that are
+    /// ready to be packed in sequence given the local `nonce` and `balance`,
+    /// in nonce order.
+    fn ready_chain(
+        &self, addr: &Address, nonce: U256, balance: U256,
+    ) -> Vec<H256> {
+        let mut hashes = Vec::new();
+        if let Some(bucket) = self.buckets.get(addr) {
+            let mut next_nonce = nonce;
+            while let Some(tx_info) = bucket.get_tx_by_nonce(next_nonce) {
+                if !tx_info.is_already_packed() {
+                    if bucket
+                        .check_pending_reason_with_local_info(
+                            nonce,
+                            balance,
+                            &tx_info.transaction,
+                        )
+                        .is_some()
+                    {
+                        break;
+                    }
+                    hashes.push(tx_info.transaction.hash());
+                }
+                next_nonce += 1.into();
+            }
+        }
+        hashes
+    }
+
     fn last_succ_nonce(&self, addr: Address, from_nonce: U256) -> Option<U256> {
         let bucket = self.buckets.get(&addr)?;
         let mut next_nonce = from_nonce;
@@ -198,21 +403,93 @@ impl DeferredPool {
         }
         Some(next_nonce)
     }
+
+    /// Return the nonces from `from_nonce` up to the highest nonce of `addr`
+    /// which have no transaction, in increasing order.
+    fn nonce_gaps(&self, addr: &Address, from_nonce: U256) -> Vec<U256> {
+        let mut gaps = Vec::new();
+        let bucket = match self.buckets.get(addr) {
+            Some(bucket) => bucket,
+            None => return gaps,
+        };
+        let mut next_nonce = from_nonce;
+        while let Some(tx_nonce) = bucket.succ_nonce(&next_nonce) {
+            while next_nonce < tx_nonce {
+                gaps.push(next_nonce);
+                next_nonce += 1.into();
+            }
+            next_nonce = self
+                .last_succ_nonce(*addr, tx_nonce)
+                .expect("bucket exists");
+        }
+        gaps
+    }
+}
+
+/// The source of the random values drawn by `ReadyAccountPool::pop`.
+enum PopRng {
+    /// The thread local generator, used unless a seed is set.
+    Thread,
+    /// A generator with a fixed seed, which makes the packing order
+    /// reproducible.
+    Seeded(StdRng),
+}
+
+impl PopRng {
+    fn next_weight(&mut self) -> WeightType {
+        match self {
+            PopRng::Thread => rand::random(),
+            PopRng::Seeded(rng) => rng.gen(),
+        }
+    }
 }
 
 #[derive(DeriveMallocSizeOf)]
 struct ReadyAccountPool {
     treap: TreapMap<Address, Arc<SignedTransaction>, WeightType>,
     tx_weight_scaling: u64,
+    /// The `tx_weight_scaling` in the config, which is restored when auto
+    /// weight scaling is disabled.
+    configured_weight_scaling: u64,
     tx_weight_exp: u8,
+    /// If set, `tx_weight_scaling` follows the gas prices of the ready
+    /// transactions. See `update_weight_scaling`.
+    auto_weight_scaling: bool,
+    /// The weight a ready transaction gains for each second its sender has
+    /// been ready, so that cheap transactions are not starved.
+    tx_weight_aging_factor: u64,
+    /// The timestamp since when each sender has had a ready transaction, in
+    /// seconds. It's kept while the transaction is popped for packing, until
+    /// the transaction is dropped instead of put back. It's not tracked if
+    /// aging is disabled.
+    ready_since: HashMap<Address, u64>,
+    /// The timestamp until when the aging of the weights in `treap` is
+    /// counted, see `refresh_aging`.
+    aged_at: u64,
+    /// The timestamp of the last `refresh_weight_scaling`.
+    scaled_at: u64,
+    #[ignore_malloc_size_of = "a random generator"]
+    rng: PopRng,
+    #[ignore_malloc_size_of = "a clock"]
+    time_source: Arc<dyn TimeSource>,
 }
 
 impl ReadyAccountPool {
-    fn new(tx_weight_scaling: u64, tx_weight_exp: u8) -> Self {
+    fn new(
+        tx_weight_scaling: u64, tx_weight_exp: u8, tx_weight_aging_factor: u64,
+    ) -> Self {
         ReadyAccountPool {
             treap: TreapMap::new(),
             tx_weight_scaling,
+            configured_weight_scaling: tx_weight_scaling,
             tx_weight_exp,
+            auto_weight_scaling: false,
+            tx_weight_aging_factor,
+            ready_since: HashMap::new(),
+            aged_at: 0,
+            scaled_at: 0,
+            rng: PopRng::Thread,
+            time_source: Arc::new(SystemTimeSource),
         }
     }
 
@@ -220,6 +497,7 @@ impl ReadyAccountPool {
         while self.len() != 0 {
             self.pop();
         }
+        self.ready_since.clear();
     }
 
     fn len(&self) -> usize { self.treap.len() }
@@ -229,9 +507,26 @@ impl ReadyAccountPool {
     }
 
     fn remove(&mut self, address: &Address) -> Option<Arc<SignedTransaction>> {
+        self.ready_since.remove(address);
         self.treap.remove(address)
     }
 
+    /// The sum of the weights of all ready transactions, which `pop` draws
+    /// from.
+    fn total_weight(&self) -> WeightType { self.treap.sum_weight() }
+
+    /// The sum of the gas limits of the ready transactions.
+    fn total_gas(&self) -> U256 {
+        self.treap
+            .iter()
+            .fold(U256::zero(), |total, (_, tx)| total + tx.gas)
+    }
+
+    /// The weight of the ready transaction of `address`, if any.
+    fn weight_of(&self, address: &Address) -> Option<WeightType> {
+        self.treap.get(address).map(|tx| self.weight(tx))
+    }
+
     fn update(
         &mut self, address: &Address, tx: Option<Arc<SignedTransaction>>,
     ) -> Option<Arc<SignedTransaction>> {
@@ -246,12 +541,47 @@ impl ReadyAccountPool {
         replaced
     }
 
+    /// Set `tx` as the ready transaction of its sender. The weight of `tx`
+    /// includes the aging of its sender until the last `refresh_aging`.
     fn insert(
         &mut self, tx: Arc<SignedTransaction>,
     ) -> Option<Arc<SignedTransaction>> {
+        if self.tx_weight_aging_factor != 0
+            && !self.ready_since.contains_key(&tx.sender())
+        {
+            let now = self.time_source.current_timestamp();
+            self.ready_since.insert(tx.sender(), now);
+        }
+        let weight = self.weight(&tx);
+        self.treap.insert(tx.sender(), tx.clone(), weight)
+    }
+
+    /// The weight of `tx` used for the weighted random selection in `pop`,
+    /// i.e. the weight of its gas price plus `tx_weight_aging_factor` for
+    /// each second its sender has been ready until `aged_at`.
+    fn weight(&self, tx: &SignedTransaction) -> WeightType {
+        if self.tx_weight_aging_factor == 0 {
+            return self.gas_price_weight(tx);
+        }
+        let ready_secs = self
+            .ready_since
+            .get(&tx.sender())
+            .map_or(0, |since| self.aged_at.saturating_sub(*since));
+        let aging_weight = (self.tx_weight_aging_factor as WeightType)
+            .saturating_mul(ready_secs as WeightType);
+        self.gas_price_weight(tx).saturating_add(aging_weight)
+    }
+
+    fn gas_price_weight(&self, tx: &SignedTransaction) -> WeightType {
         let scaled_weight = tx.gas_price / self.tx_weight_scaling;
         let base_weight = if scaled_weight == U256::zero() {
-            0
+            // With auto scaling, the scaling may exceed the gas price of a
+            // cheap transaction, which must not get a zero weight.
+            if self.auto_weight_scaling {
+                MIN_AUTO_SCALED_WEIGHT
+            } else {
+                0
+            }
         } else if scaled_weight >= *MAX_WEIGHT {
             u128::max_value()
         } else {
@@ -262,8 +592,99 @@ impl ReadyAccountPool {
         for _ in 0..self.tx_weight_exp {
             weight *= base_weight;
         }
+        weight
+    }
 
-        self.treap.insert(tx.sender(), tx.clone(), weight)
+    /// Set `tx_weight_scaling` to the median gas price of the ready
+    /// transactions divided by `AUTO_WEIGHT_SCALING_DIVISOR` and re-weight
+    /// the ready transactions. It does nothing if auto weight scaling is not
+    /// enabled.
+    fn update_weight_scaling(&mut self) {
+        if !self.auto_weight_scaling || self.treap.len() == 0 {
+            return;
+        }
+        let mut gas_prices: Vec<U256> =
+            self.treap.iter().map(|(_, tx)| tx.gas_price).collect();
+        let median_index = gas_prices.len() / 2;
+        let (_, median, _) = gas_prices.select_nth_unstable(median_index);
+        let scaling = *median / AUTO_WEIGHT_SCALING_DIVISOR;
+        let scaling = if scaling > U256::from(u64::max_value()) {
+            u64::max_value()
+        } else {
+            cmp::max(scaling.as_u64(), 1)
+        };
+        if scaling == self.tx_weight_scaling {
+            return;
+        }
+        debug!(
+            "Update tx weight scaling from {} to {}",
+            self.tx_weight_scaling, scaling
+        );
+        self.tx_weight_scaling = scaling;
+        self.reweight();
+    }
+
+    /// Like `update_weight_scaling`, but it does nothing if the scaling has
+    /// been refreshed within `TX_WEIGHT_SCALING_INTERVAL`.
+    fn refresh_weight_scaling(&mut self) {
+        if !self.auto_weight_scaling {
+            return;
+        }
+        let now = self.time_source.current_timestamp();
+        if now < self.scaled_at.saturating_add(TX_WEIGHT_SCALING_INTERVAL) {
+            return;
+        }
+        self.scaled_at = now;
+        self.update_weight_scaling();
+    }
+
+    /// Recompute the weights of all ready transactions. Their senders are
+    /// already in `ready_since`, so the clock is not read.
+    fn reweight(&mut self) {
+        let txs: Vec<_> = self.treap.iter().map(|(_, tx)| tx.clone()).collect();
+        for tx in txs {
+            let weight = self.weight(&tx);
+            self.treap.insert(tx.sender(), tx, weight);
+        }
+    }
+
+    /// Count the aging of the ready transactions until now and re-weight
+    /// them. It does nothing if aging is not enabled or the aging has been
+    /// refreshed within `TX_WEIGHT_AGING_GRANULARITY`.
+    fn refresh_aging(&mut self) {
+        if self.tx_weight_aging_factor == 0 {
+            return;
+        }
+        let now = self.time_source.current_timestamp();
+        if now < self.aged_at.saturating_add(TX_WEIGHT_AGING_GRANULARITY) {
+            return;
+        }
+        self.aged_at = now;
+        self.reweight();
+    }
+
+    /// The gas prices of the ready transactions in ascending order.
+    fn sorted_gas_prices(&self) -> Vec<U256> {
+        let mut gas_prices: Vec<U256> =
+            self.treap.iter().map(|(_, tx)| tx.gas_price).collect();
+        gas_prices.sort();
+        gas_prices
+    }
+
+    /// Return the ready transactions sorted by descending weight. Ties are
+    /// broken by the sender address.
+    fn sorted_by_weight(&self) -> Vec<Arc<SignedTransaction>> {
+        let mut weighted_txs: Vec<_> = self
+            .treap
+            .iter()
+            .map(|(_, tx)| (self.weight(tx), tx.clone()))
+            .collect();
+        weighted_txs.sort_by(|(weight_a, tx_a), (weight_b, tx_b)| {
+            weight_b
+                .cmp(weight_a)
+                .then_with(|| tx_a.sender().cmp(&tx_b.sender()))
+        });
+        weighted_txs.into_iter().map(|(_, tx)| tx).collect()
     }
 
     fn pop(&mut self) -> Option<Arc<SignedTransaction>> {
@@ -272,8 +693,7 @@ impl ReadyAccountPool {
         }
 
         let sum_gas_price = self.treap.sum_weight();
-        let mut rand_value = rand::random();
-        rand_value = rand_value % sum_gas_price;
+        let rand_value = self.rng.next_weight() % sum_gas_price;
 
         let tx = self
             .treap
@@ -282,11 +702,132 @@ impl ReadyAccountPool {
             .clone();
         trace!("Get transaction from ready pool. tx: {:?}", tx.clone());
 
-        self.remove(&tx.sender())
+        // The sender keeps aging if the transaction is put back.
+        self.treap.remove(&tx.sender())
+    }
+
+    /// Return the ready transactions whose gas prices are in `[min, max]`,
+    /// sorted by descending gas price. Ties are broken by the sender address.
+    fn in_price_range(
+        &self, min: U256, max: U256,
+    ) -> Vec<Arc<SignedTransaction>> {
+        let mut txs: Vec<_> = self
+            .treap
+            .iter()
+            .map(|(_, tx)| tx)
+            .filter(|tx| tx.gas_price >= min && tx.gas_price <= max)
+            .cloned()
+            .collect();
+        txs.sort_by(|tx_a, tx_b| {
+            tx_b.gas_price
+                .cmp(&tx_a.gas_price)
+                .then_with(|| tx_a.sender().cmp(&tx_b.sender()))
+        });
+        txs
+    }
+
+    /// Build a `PriceQueue` of the current ready transactions.
+    fn price_queue(&self) -> PriceQueue {
+        self.treap
+            .iter()
+            .map(|(sender, tx)| (tx.gas_price, Reverse(*sender)))
+            .collect()
+    }
+
+    /// Push the ready transaction of `sender`, if any, into `queue`.
+    fn requeue_by_price(&self, queue: &mut PriceQueue, sender: &Address) {
+        if let Some(tx) = self.get(sender) {
+            queue.push((tx.gas_price, Reverse(*sender)));
+        }
+    }
+
+    /// Pop the ready transaction with the highest gas price in `queue`, the
+    /// sender with the smaller address first on a tie. Entries which no
+    /// longer match the ready transaction of their sender are dropped.
+    fn pop_by_price(
+        &mut self, queue: &mut PriceQueue,
+    ) -> Option<Arc<SignedTransaction>> {
+        while let Some((gas_price, Reverse(sender))) = queue.pop() {
+            match self.get(&sender) {
+                Some(tx) if tx.gas_price == gas_price => {
+                    return self.treap.remove(&sender);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+/// The order in which `TransactionPoolInner::pack_transactions` takes the
+/// ready transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackingOrder {
+    /// Sample the senders randomly by the weight of their ready transactions.
+    Random,
+    /// Take the ready transaction with the highest gas price first, which
+    /// makes the packed list deterministic.
+    GasPrice,
+}
+
+/// The upper bound of the gas fee that a sponsor pays for `transaction`.
+fn estimated_gas_fee(transaction: &SignedTransaction) -> U256 {
+    let estimated_gas_u512 = transaction.gas.full_mul(transaction.gas_price);
+    // Normally, it is less than 2^128
+    if estimated_gas_u512 > U512::from(U128::max_value()) {
+        U256::from(U128::max_value())
+    } else {
+        transaction.gas * transaction.gas_price
     }
 }
 
-#[derive(Serialize)]
+/// The gas and the storage of `transaction` paid by the sponsor of the
+/// contract it calls, given the sponsor info in `account_cache`.
+fn sponsored_info(
+    account_cache: &AccountCache, transaction: &SignedTransaction,
+) -> Result<(U256, u64), String> {
+    let mut sponsored_gas = U256::from(0);
+    let mut sponsored_storage = 0;
+
+    if let Action::Call(callee) = &transaction.action {
+        // FIXME: This is a quick fix for performance issue.
+        if callee.maybe_contract_address() {
+            if let Some(sponsor_info) =
+                account_cache.get_sponsor_info(callee).map_err(|e| {
+                    format!("Failed to read account_cache from storage: {}", e)
+                })?
+            {
+                if account_cache
+                    .check_commission_privilege(&callee, &transaction.sender())
+                    .map_err(|e| {
+                        format!(
+                            "Failed to read account_cache from storage: {}",
+                            e
+                        )
+                    })?
+                {
+                    let estimated_gas = estimated_gas_fee(transaction);
+                    if estimated_gas <= sponsor_info.sponsor_gas_bound
+                        && estimated_gas <= sponsor_info.sponsor_balance_for_gas
+                    {
+                        sponsored_gas = transaction.gas;
+                    }
+                    let estimated_collateral =
+                        U256::from(transaction.storage_limit)
+                            * *DRIPS_PER_STORAGE_COLLATERAL_UNIT;
+                    if estimated_collateral
+                        <= sponsor_info.sponsor_balance_for_collateral
+                    {
+                        sponsored_storage = transaction.storage_limit;
+                    }
+                }
+            }
+        }
+    }
+    Ok((sponsored_gas, sponsored_storage))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum TransactionStatus {
     Packed,
@@ -294,17 +835,266 @@ pub enum TransactionStatus {
     Pending(PendingReason),
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum PendingReason {
     FutureNonce,
     NotEnoughCash,
 }
 
+/// Whether a transaction of a sender at some nonce could be packed once the
+/// transactions already in the pool are.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum NonceReachability {
+    /// The nonce continues the transactions in the pool from the state nonce.
+    Reachable,
+    /// The nonce can not be packed until the contained nonce is filled.
+    BehindGap(U256),
+    /// The nonce is below the state nonce and has already been used.
+    Stale,
+}
+
+/// A snapshot of the whole pool built by `full_report` for debugging.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PoolReport {
+    pub capacity: usize,
+    pub total_deferred: usize,
+    pub total_unpacked: usize,
+    pub total_ready_accounts: usize,
+    pub total_received: usize,
+    pub lifetime_received: usize,
+    /// The senders sorted by address.
+    pub senders: Vec<SenderReport>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SenderReport {
+    pub address: Address,
+    /// The nonce and balance cached for readiness calculation.
+    pub local_nonce_and_balance: Option<(U256, U256)>,
+    /// The last time a transaction of this sender was garbage collected.
+    pub gc_timestamp: Option<u64>,
+    /// The transactions sorted by nonce.
+    pub transactions: Vec<TransactionReport>,
+}
+
+/// A sender as tracked by the garbage collector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GcEntry {
+    pub address: Address,
+    /// The number of transactions which can be collected, i.e. whose nonces
+    /// are below the state nonce.
+    pub count: usize,
+    /// The last time a transaction of this sender was garbage collected, or
+    /// when the sender was first tracked.
+    pub timestamp: u64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionReport {
+    pub hash: H256,
+    pub nonce: U256,
+    pub gas_price: U256,
+    pub gas: U256,
+    pub value: U256,
+    pub status: TransactionStatus,
+}
+
+/// Why a transaction leaves the transaction pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemovalReason {
+    /// Evicted by `collect_garbage` when the pool is full.
+    GarbageCollected,
+    /// Replaced by another transaction with the same sender and nonce.
+    Replaced,
+    /// Removed because it is already executed.
+    Pruned,
+    /// Removed because it stays in the pool for too long.
+    Expired,
+    /// Removed on request, e.g. when the pool is cleared.
+    Removed,
+}
+
+/// The counts of `collect_garbage` events since the pool is created. They are
+/// also reported as metrics, which are not recorded if metrics are disabled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, DeriveMallocSizeOf)]
+pub struct GcStats {
+    /// The number of evicted transactions.
+    pub evicted: usize,
+    /// The number of sweeps which stopped since no victim was found.
+    pub no_victim: usize,
+    /// The number of times a sender was skipped because the new transaction
+    /// is also sent by it.
+    pub spared_sender: usize,
+    /// The number of times a sender was skipped because its lowest-nonce
+    /// transaction is local.
+    pub spared_local: usize,
+}
+
+/// The sizes of the pool returned together by `stats`, so that they are read
+/// from the same state of the pool.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    pub total_deferred: usize,
+    pub total_ready_accounts: usize,
+    pub total_received: usize,
+    pub total_unpacked: usize,
+    pub remaining_quota: usize,
+    /// The sum of the gas limits of the ready transactions.
+    pub total_ready_gas: U256,
+    /// The number of senders with transactions in the pool.
+    pub total_senders: usize,
+}
+
+/// How `collect_garbage` picks a victim among the checked senders which have
+/// no executed transactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, DeriveMallocSizeOf)]
+pub enum GcStrategy {
+    /// The sender whose lowest-nonce transaction has the lowest gas price, if
+    /// it is lower than the gas price of the new transaction.
+    LowestGasPrice,
+    /// The sender with the oldest garbage collector timestamp.
+    OldestTimestamp,
+}
+
+impl Default for GcStrategy {
+    fn default() -> Self { GcStrategy::LowestGasPrice }
+}
+
+/// Detects that `collect_garbage` evicts transactions on most insertions, so
+/// that the pool mostly churns. It keeps the number of evictions of each of
+/// the latest `window` insertions.
+#[derive(DeriveMallocSizeOf)]
+struct GcThrashingDetector {
+    /// The detector is disabled if it's 0.
+    window: usize,
+    /// The pool is thrashing if the evictions exceed this percentage of the
+    /// insertions in a full window.
+    threshold_percent: u64,
+    recent_evictions: VecDeque<usize>,
+    total_evictions: usize,
+}
+
+impl GcThrashingDetector {
+    fn new(window: usize, threshold_percent: u64) -> Self {
+        GcThrashingDetector {
+            window,
+            threshold_percent,
+            recent_evictions: VecDeque::with_capacity(window),
+            total_evictions: 0,
+        }
+    }
+
+    fn record(&mut self, evictions: usize) {
+        if self.window == 0 {
+            return;
+        }
+        if self.recent_evictions.len() == self.window {
+            self.total_evictions -= self.recent_evictions.pop_front().unwrap();
+        }
+        self.recent_evictions.push_back(evictions);
+        self.total_evictions += evictions;
+    }
+
+    fn is_thrashing(&self) -> bool {
+        self.window != 0
+            && self.recent_evictions.len() == self.window
+            && self.total_evictions as u64 * 100
+                > self.threshold_percent * self.window as u64
+    }
+
+    fn clear(&mut self) {
+        self.recent_evictions.clear();
+        self.total_evictions = 0;
+    }
+}
+
+/// The clock of the pool in seconds since the UNIX epoch. It timestamps the
+/// inserted transactions and the senders in the garbage collector.
+pub trait TimeSource: Send + Sync {
+    fn current_timestamp(&self) -> u64;
+}
+
+/// The time source of the pool unless it is replaced by `set_time_source`.
+pub struct SystemTimeSource;
+
+impl TimeSource for SystemTimeSource {
+    fn current_timestamp(&self) -> u64 {
+        let since_the_epoch =
+            SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        since_the_epoch.as_secs()
+    }
+}
+
+/// The order of the transactions returned by `content_paginated`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentSortOrder {
+    /// By sender and then by nonce, both ascending.
+    NonceAscending,
+    /// By gas price, the highest first.
+    GasPriceDescending,
+}
+
+/// The sort key of a transaction in `content_paginated`. The hash breaks the
+/// ties so that pages do not overlap.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum ContentSortKey {
+    Nonce(Address, U256, H256),
+    GasPrice(Reverse<U256>, H256),
+}
+
+impl ContentSortKey {
+    fn new(tx: &SignedTransaction, order: ContentSortOrder) -> Self {
+        match order {
+            ContentSortOrder::NonceAscending => {
+                ContentSortKey::Nonce(tx.sender(), tx.nonce(), tx.hash())
+            }
+            ContentSortOrder::GasPriceDescending => {
+                ContentSortKey::GasPrice(Reverse(tx.gas_price), tx.hash())
+            }
+        }
+    }
+
+    fn tx_hash(&self) -> &H256 {
+        match self {
+            ContentSortKey::Nonce(_, _, hash) => hash,
+            ContentSortKey::GasPrice(_, hash) => hash,
+        }
+    }
+}
+
+/// A window of the ready and deferred transactions of `content`, together
+/// with the total number of each.
+pub struct ContentPage {
+    pub ready_txs: Vec<Arc<SignedTransaction>>,
+    pub total_ready: usize,
+    pub deferred_txs: Vec<Arc<SignedTransaction>>,
+    pub total_deferred: usize,
+}
+
+/// A conflict of a transaction batch reported by `validate_batch`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatchConflict {
+    /// Two transactions in the batch have the same sender and nonce.
+    Duplicate { first: H256, second: H256 },
+    /// The pool already has a packed transaction with the same sender and
+    /// nonce, so the transaction can not replace it.
+    AlreadyPacked { tx_hash: H256, packed_tx_hash: H256 },
+}
+
 #[derive(DeriveMallocSizeOf)]
 pub struct TransactionPoolInner {
     capacity: usize,
+    /// The number of received transactions since the pool is last cleared.
     total_received_count: usize,
+    /// The number of received transactions since the pool is created. Unlike
+    /// `total_received_count`, it is not reset by `clear`.
+    lifetime_received_count: usize,
     unpacked_transaction_count: usize,
     /// Tracks all transactions in the transaction pool by account and nonce.
     /// Packed and executed transactions will eventually be garbage collected.
@@ -323,25 +1113,144 @@ pub struct TransactionPoolInner {
     /// It should contain the same transaction set as `deferred_pool`.
     txs: HashMap<H256, Arc<SignedTransaction>>,
     tx_sponsored_gas_map: HashMap<H256, (U256, u64)>,
+    /// The timestamp when each transaction in `txs` is inserted, in seconds.
+    tx_insertion_timestamps: HashMap<H256, u64>,
+    /// The RLP sizes of the transactions in `txs` which have been packed,
+    /// dropped when the transactions leave `txs`.
+    tx_rlp_sizes: HashMap<H256, usize>,
+    /// The gas prices of the latest `packed_fee_window` packed transactions,
+    /// the oldest first.
+    recent_packed_gas_prices: VecDeque<U256>,
+    packed_fee_window: usize,
+    /// A transaction whose gas limit exceeds this percentage of the block gas
+    /// limit is not packed, so that it doesn't crowd out the others.
+    max_single_tx_gas_percent: u64,
+    /// The number of transactions a forced insertion may exceed `capacity`
+    /// by. The excess is collected by the next insertion which triggers
+    /// `collect_garbage`.
+    forced_insert_overflow_margin: usize,
+    /// Transactions held back from packing by `quarantine`. They stay in
+    /// `deferred_pool` but are never put into `ready_account_pool`.
+    quarantined: HashSet<H256>,
+    /// Transactions submitted through the RPC of this node. A sender whose
+    /// lowest-nonce transaction is local is not garbage collected unless the
+    /// pool is over `capacity`.
+    local_transactions: HashSet<H256>,
+    /// The number of slots of `capacity` which only local and packed
+    /// transactions can be inserted into.
+    local_reserved_slots: usize,
+    /// The balance a sender must keep after paying for its transactions for
+    /// them to be ready.
+    min_balance_reserve: U256,
+    /// If set, a transaction which is not packed is rejected if its
+    /// worst-case cost exceeds the balance of its sender by more than this.
+    strict_balance_margin: Option<U256>,
+    /// The max number of senders `collect_garbage` checks to pick a victim.
+    gc_check_count: usize,
+    gc_strategy: GcStrategy,
+    /// A transaction which is not packed is rejected if its gas price is
+    /// below this.
+    min_gas_price: U256,
+    /// A transaction is rejected if its nonce is at least this far after the
+    /// state nonce of its sender.
+    furthest_future_nonce_offset: u32,
+    /// If set, the pool is also full when the total size in bytes of `txs`
+    /// reaches it.
+    memory_capacity: Option<usize>,
+    /// The total size in bytes of `txs`, maintained on insertion and removal.
+    total_tx_size: usize,
+    gc_stats: GcStats,
+    /// Non-local transactions are rejected while it detects thrashing.
+    gc_thrashing_detector: GcThrashingDetector,
+    /// Receives the hash of every transaction removed from `txs`.
+    #[ignore_malloc_size_of = "a channel sender"]
+    removal_notifier: Option<Mutex<mpsc::Sender<(H256, RemovalReason)>>>,
+    /// Called with a sender which had no ready transaction when one of its
+    /// transactions becomes ready, e.g. after its nonce gap is filled.
+    #[ignore_malloc_size_of = "a callback"]
+    ready_callback: Option<Box<dyn Fn(&Address) + Send + Sync>>,
+    #[ignore_malloc_size_of = "a clock"]
+    time_source: Arc<dyn TimeSource>,
 }
 
 impl TransactionPoolInner {
-    pub fn new(
-        capacity: usize, tx_weight_scaling: u64, tx_weight_exp: u8,
-    ) -> Self {
+    pub fn new(config: &TxPoolConfig) -> Self {
         TransactionPoolInner {
-            capacity,
+            capacity: config.capacity,
             total_received_count: 0,
+            lifetime_received_count: 0,
             unpacked_transaction_count: 0,
-            deferred_pool: DeferredPool::new(),
+            deferred_pool: DeferredPool::new(
+                config.max_txs_per_sender,
+                config.min_replace_bump_percent,
+            ),
             ready_account_pool: ReadyAccountPool::new(
-                tx_weight_scaling,
-                tx_weight_exp,
+                config.tx_weight_scaling,
+                config.tx_weight_exp,
+                config.tx_weight_aging_factor,
             ),
             ready_nonces_and_balances: HashMap::new(),
             garbage_collector: GarbageCollector::default(),
             txs: HashMap::new(),
             tx_sponsored_gas_map: HashMap::new(),
+            tx_insertion_timestamps: HashMap::new(),
+            tx_rlp_sizes: HashMap::new(),
+            recent_packed_gas_prices: VecDeque::with_capacity(
+                config.packed_fee_window,
+            ),
+            packed_fee_window: config.packed_fee_window,
+            max_single_tx_gas_percent: config.max_single_tx_gas_percent,
+            forced_insert_overflow_margin: config.forced_insert_overflow_margin,
+            quarantined: HashSet::new(),
+            local_transactions: HashSet::new(),
+            local_reserved_slots: config.local_reserved_slots,
+            min_balance_reserve: config.min_balance_reserve,
+            strict_balance_margin: config.strict_balance_margin,
+            gc_check_count: config.gc_check_count,
+            gc_strategy: config.gc_strategy,
+            min_gas_price: config.min_gas_price.into(),
+            furthest_future_nonce_offset: config.furthest_future_nonce_offset,
+            memory_capacity: config.memory_capacity,
+            total_tx_size: 0,
+            gc_stats: GcStats::default(),
+            gc_thrashing_detector: GcThrashingDetector::new(
+                config.gc_thrashing_window,
+                config.gc_thrashing_threshold_percent,
+            ),
+            removal_notifier: None,
+            ready_callback: None,
+            time_source: Arc::new(SystemTimeSource),
+        }
+    }
+
+    /// Replace the clock of the pool, e.g. by one that tests can advance.
+    pub fn set_time_source(&mut self, time_source: Arc<dyn TimeSource>) {
+        self.ready_account_pool.time_source = time_source.clone();
+        self.time_source = time_source;
+    }
+
+    /// Register `notifier` to receive the hash of every transaction that
+    /// leaves the pool together with the reason. It replaces the previous
+    /// one.
+    pub fn set_removal_notifier(
+        &mut self, notifier: mpsc::Sender<(H256, RemovalReason)>,
+    ) {
+        self.removal_notifier = Some(Mutex::new(notifier));
+    }
+
+    /// Register `callback` to be called with each sender which turns from no
+    /// ready transaction to having one. It replaces the previous one.
+    pub fn set_ready_callback(
+        &mut self, callback: impl Fn(&Address) + Send + Sync + 'static,
+    ) {
+        self.ready_callback = Some(Box::new(callback));
+    }
+
+    fn notify_removal(&self, tx_hash: H256, reason: RemovalReason) {
+        if let Some(notifier) = &self.removal_notifier {
+            if notifier.lock().send((tx_hash, reason)).is_err() {
+                debug!("The removal notifier receiver is dropped");
+            }
         }
     }
 
@@ -350,8 +1259,17 @@ impl TransactionPoolInner {
         self.ready_account_pool.clear();
         self.ready_nonces_and_balances.clear();
         self.garbage_collector.clear();
+        for tx_hash in self.txs.keys() {
+            self.notify_removal(*tx_hash, RemovalReason::Removed);
+        }
         self.txs.clear();
         self.tx_sponsored_gas_map.clear();
+        self.tx_insertion_timestamps.clear();
+        self.tx_rlp_sizes.clear();
+        self.quarantined.clear();
+        self.local_transactions.clear();
+        self.gc_thrashing_detector.clear();
+        self.total_tx_size = 0;
         self.total_received_count = 0;
         self.unpacked_transaction_count = 0;
     }
@@ -362,10 +1280,38 @@ impl TransactionPoolInner {
         self.ready_account_pool.len()
     }
 
+    /// The sum of the packing weights of the ready transactions. A sender's
+    /// chance to be drawn by `pack_transactions` is its `ready_weight_of`
+    /// divided by this.
+    pub fn total_ready_weight(&self) -> u128 {
+        self.ready_account_pool.total_weight()
+    }
+
+    /// The packing weight of the ready transaction of `address`, i.e.
+    /// `(gas_price / tx_weight_scaling) ^ tx_weight_exp` plus its aging, or
+    /// `None` if it has no ready transaction.
+    pub fn ready_weight_of(&self, address: &Address) -> Option<u128> {
+        self.ready_account_pool.weight_of(address)
+    }
+
     pub fn total_received(&self) -> usize { self.total_received_count }
 
+    pub fn lifetime_received(&self) -> usize { self.lifetime_received_count }
+
     pub fn total_unpacked(&self) -> usize { self.unpacked_transaction_count }
 
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            total_deferred: self.total_deferred(),
+            total_ready_accounts: self.total_ready_accounts(),
+            total_received: self.total_received(),
+            total_unpacked: self.total_unpacked(),
+            remaining_quota: self.remaining_quota(),
+            total_ready_gas: self.ready_account_pool.total_gas(),
+            total_senders: self.deferred_pool.buckets.len(),
+        }
+    }
+
     pub fn get(&self, tx_hash: &H256) -> Option<Arc<SignedTransaction>> {
         self.txs.get(tx_hash).map(|x| x.clone())
     }
@@ -377,14 +1323,220 @@ impl TransactionPoolInner {
         bucket.get_tx_by_nonce(nonce).map(|tx| tx.transaction)
     }
 
-    pub fn is_full(&self) -> bool {
-        return self.total_deferred() >= self.capacity;
+    /// Return all transactions in the pool sorted by sender and then by
+    /// nonce, which does not depend on the iteration order of `txs`.
+    pub fn all_ordered(&self) -> Vec<Arc<SignedTransaction>> {
+        let mut senders: Vec<_> = self.deferred_pool.buckets.keys().collect();
+        senders.sort();
+        senders
+            .into_iter()
+            .flat_map(|sender| self.deferred_pool.all_txs(sender))
+            .collect()
+    }
+
+    /// Return the transactions in the pool which call `contract`.
+    pub fn transactions_to_contract(
+        &self, contract: &Address,
+    ) -> Vec<Arc<SignedTransaction>> {
+        self.txs
+            .values()
+            .filter(|tx| match &tx.action {
+                Action::Call(callee) => callee == contract,
+                Action::Create => false,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Return at most `limit` transactions in the pool which call `callee`
+    /// with `selector` as the first 4 bytes of their data.
+    pub fn find_by_selector(
+        &self, callee: Address, selector: [u8; 4], limit: usize,
+    ) -> Vec<Arc<SignedTransaction>> {
+        self.txs
+            .values()
+            .filter(|tx| match &tx.action {
+                Action::Call(address) => {
+                    *address == callee && tx.data.starts_with(&selector)
+                }
+                Action::Create => false,
+            })
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    /// Return the senders tracked by the garbage collector in the order they
+    /// would be picked as victims: the larger `count` first, and the earlier
+    /// `timestamp` first on a tie.
+    pub fn gc_debug_snapshot(&self) -> Vec<GcEntry> {
+        self.garbage_collector
+            .iter_by_priority()
+            .map(|node| GcEntry {
+                address: node.sender,
+                count: node.count,
+                timestamp: node.timestamp,
+            })
+            .collect()
+    }
+
+    /// Dump the state of every sender and transaction in the pool.
+    pub fn full_report(&self) -> PoolReport {
+        let mut senders: Vec<_> = self.deferred_pool.buckets.iter().collect();
+        senders.sort_by_key(|(address, _)| **address);
+        let senders = senders
+            .into_iter()
+            .map(|(address, bucket)| {
+                let local_nonce_and_balance =
+                    self.get_local_nonce_and_balance(address);
+                let (nonce, balance) =
+                    local_nonce_and_balance.unwrap_or((0.into(), 0.into()));
+                let balance = self.spendable_balance(balance);
+                let transactions = self
+                    .deferred_pool
+                    .all_txs(address)
+                    .into_iter()
+                    .map(|tx| {
+                        let status = if self
+                            .deferred_pool
+                            .check_tx_packed(*address, tx.nonce())
+                        {
+                            TransactionStatus::Packed
+                        } else {
+                            match bucket.check_pending_reason_with_local_info(
+                                nonce, balance, &tx,
+                            ) {
+                                Some(reason) => {
+                                    TransactionStatus::Pending(reason)
+                                }
+                                None => TransactionStatus::Ready,
+                            }
+                        };
+                        TransactionReport {
+                            hash: tx.hash(),
+                            nonce: tx.nonce(),
+                            gas_price: tx.gas_price,
+                            gas: tx.gas,
+                            value: tx.value,
+                            status,
+                        }
+                    })
+                    .collect();
+                SenderReport {
+                    address: *address,
+                    local_nonce_and_balance,
+                    gc_timestamp: self.garbage_collector.get_timestamp(address),
+                    transactions,
+                }
+            })
+            .collect();
+        PoolReport {
+            capacity: self.capacity,
+            total_deferred: self.total_deferred(),
+            total_unpacked: self.total_unpacked(),
+            total_ready_accounts: self.total_ready_accounts(),
+            total_received: self.total_received(),
+            lifetime_received: self.lifetime_received(),
+            senders,
+        }
+    }
+
+    /// Return at most `n` senders with the most transactions in the pool and
+    /// their transaction counts, the largest first. Ties are broken by the
+    /// sender address.
+    pub fn top_senders_by_count(&self, n: usize) -> Vec<(Address, usize)> {
+        let mut senders: Vec<_> = self
+            .deferred_pool
+            .buckets
+            .iter()
+            .map(|(sender, bucket)| (*sender, bucket.len()))
+            .collect();
+        let by_count = |a: &(Address, usize), b: &(Address, usize)| {
+            b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0))
+        };
+        if n < senders.len() {
+            senders.select_nth_unstable_by(n, by_count);
+            senders.truncate(n);
+        }
+        senders.sort_unstable_by(by_count);
+        senders
+    }
+
+    pub fn is_full(&self) -> bool { self.is_full_for(true) }
+
+    /// Like `is_full`, but a transaction which can not use the
+    /// `local_reserved_slots` sees a smaller capacity for the remote
+    /// transactions.
+    fn is_full_for(&self, use_reserved_slots: bool) -> bool {
+        self.is_full_with(
+            self.total_deferred(),
+            self.local_transactions.len(),
+            self.total_tx_size,
+            use_reserved_slots,
+        )
+    }
+
+    /// Like `is_full_for`, but for a pool holding `total_deferred`
+    /// transactions of `total_tx_size` bytes, `total_local` of which are
+    /// local.
+    fn is_full_with(
+        &self, total_deferred: usize, total_local: usize,
+        total_tx_size: usize, use_reserved_slots: bool,
+    ) -> bool
+    {
+        if let Some(memory_capacity) = self.memory_capacity {
+            if total_tx_size >= memory_capacity {
+                return true;
+            }
+        }
+        if total_deferred >= self.capacity {
+            return true;
+        }
+        // The local transactions in the reserved slots do not make the pool
+        // full for the remote ones, so a remote transaction evicts at most
+        // one transaction.
+        !use_reserved_slots
+            && total_deferred.saturating_sub(total_local)
+                >= self.capacity.saturating_sub(self.local_reserved_slots)
+    }
+
+    /// The total size in bytes of the transactions in the pool.
+    pub fn total_tx_size(&self) -> usize { self.total_tx_size }
+
+    fn tx_size(tx: &Arc<SignedTransaction>) -> usize {
+        tx.size_of(&mut new_malloc_size_ops())
+    }
+
+    fn add_tx_size(&mut self, tx: &Arc<SignedTransaction>) {
+        self.total_tx_size += Self::tx_size(tx);
+    }
+
+    fn sub_tx_size(&mut self, tx: &Arc<SignedTransaction>) {
+        self.total_tx_size =
+            self.total_tx_size.saturating_sub(Self::tx_size(tx));
+    }
+
+    /// Drops every per-transaction record kept for `tx_hash`.
+    fn forget_transaction(&mut self, tx_hash: &H256) {
+        self.txs.remove(tx_hash);
+        self.tx_sponsored_gas_map.remove(tx_hash);
+        self.tx_insertion_timestamps.remove(tx_hash);
+        self.tx_rlp_sizes.remove(tx_hash);
+        self.quarantined.remove(tx_hash);
+        self.local_transactions.remove(tx_hash);
+    }
+
+    /// The RLP size of `tx`, which is only computed the first time a
+    /// transaction in the pool is packed.
+    fn cached_rlp_size(&mut self, tx: &SignedTransaction) -> usize {
+        *self
+            .tx_rlp_sizes
+            .entry(tx.hash())
+            .or_insert_with(|| tx.rlp_size())
     }
 
     pub fn get_current_timestamp(&self) -> u64 {
-        let start = SystemTime::now();
-        let since_the_epoch = start.duration_since(UNIX_EPOCH).unwrap();
-        since_the_epoch.as_secs()
+        self.time_source.current_timestamp()
     }
 
     /// A sender has a transaction which is garbage collectable if
@@ -397,67 +1549,40 @@ impl TransactionPoolInner {
     /// We will pick a sender who has maximum number of transactions which are
     /// garbage collectable. And if there is a tie, the one who has minimum
     /// timestamp will be picked.
-    pub fn collect_garbage(&mut self, new_tx: &SignedTransaction) {
+    ///
+    /// Unless `use_reserved_slots` is set, transactions are collected until
+    /// the `local_reserved_slots` are free.
+    pub fn collect_garbage(
+        &mut self, new_tx: &SignedTransaction, use_reserved_slots: bool,
+    ) {
         let count_before_gc = self.total_deferred();
-        while self.is_full() && !self.garbage_collector.is_empty() {
+        while self.is_full_for(use_reserved_slots)
+            && !self.garbage_collector.is_empty()
+        {
+            // Local transactions are only collected when forced insertions
+            // have pushed the pool over its capacity.
+            let spare_local = self.total_deferred() <= self.capacity;
             let current_timestamp = self.get_current_timestamp();
-            let victim = {
-                let mut cnt = GC_CHECK_COUNT;
-                let mut poped_nodes = Vec::new();
-                let mut victim = None;
-                let mut min_gas_price = new_tx.gas_price;
-                while !self.garbage_collector.is_empty() && cnt != 0 {
-                    let node = self.garbage_collector.pop().unwrap();
-                    // Accounts which are not in `deferred_pool` may be inserted
-                    // into `garbage_collector`, we can just
-                    // ignore them.
-                    if !self.deferred_pool.contain_address(&node.sender) {
-                        continue;
-                    }
-                    poped_nodes.push(node.clone());
-
-                    // This node has executed transactions to GC. No need to
-                    // check more.
-                    if node.count > 0 {
-                        victim = Some(node);
-                        break;
-                    }
-
-                    // We do not GC a transaction from the same sender.
-                    if node.sender == new_tx.sender {
-                        continue;
-                    }
-
-                    // If all accounts are ready, we choose the one whose first
-                    // tx has the minimal gas price.
-                    let to_remove_tx = self
-                        .deferred_pool
-                        .get_lowest_nonce_tx(&node.sender)
-                        .unwrap();
-                    if to_remove_tx.gas_price < min_gas_price {
-                        min_gas_price = to_remove_tx.gas_price;
-                        victim = Some(node);
-                    }
-                    cnt -= 1;
-                }
-                // Insert back other nodes to keep `garbage_collector`
-                // unchanged.
-                for node in poped_nodes {
-                    if victim.is_some()
-                        && node.sender == victim.as_ref().unwrap().sender
-                    {
-                        // skip victim
-                        continue;
-                    }
-                    self.garbage_collector.insert(
-                        &node.sender,
-                        node.count,
-                        node.timestamp,
-                    );
-                }
-                match victim {
-                    Some(victim) => victim,
-                    None => return,
+            let mut garbage_collector =
+                std::mem::take(&mut self.garbage_collector);
+            let mut gc_stats = self.gc_stats;
+            let victim = self.pick_gc_victim(
+                &mut garbage_collector,
+                Some(new_tx),
+                spare_local,
+                |addr| {
+                    self.deferred_pool.get_lowest_nonce_tx_from(addr, 0.into())
+                },
+                Some(&mut gc_stats),
+            );
+            self.garbage_collector = garbage_collector;
+            self.gc_stats = gc_stats;
+            let victim = match victim {
+                Some(victim) => victim,
+                None => {
+                    GC_NO_VICTIM_COUNTER.inc(1);
+                    self.gc_stats.no_victim += 1;
+                    break;
                 }
             };
             let addr = victim.sender;
@@ -525,28 +1650,410 @@ impl TransactionPoolInner {
                 }
             }
 
+            GC_VICTIM_GAS_PRICE.update(
+                removed_tx.gas_price.min(U256::from(u64::max_value())).as_u64(),
+            );
+            self.gc_stats.evicted += 1;
+
             // maintain txs
-            self.txs.remove(&removed_tx.hash());
-            self.tx_sponsored_gas_map.remove(&removed_tx.hash());
+            self.sub_tx_size(&removed_tx);
+            self.forget_transaction(&removed_tx.hash());
+            self.notify_removal(
+                removed_tx.hash(),
+                RemovalReason::GarbageCollected,
+            );
         }
 
         GC_METER.mark(count_before_gc - self.total_deferred());
     }
 
+    /// Return the hashes of the transactions which would be evicted by
+    /// `collect_garbage` to insert `new_tx` from the network, in the order
+    /// they would be evicted, without changing the pool.
+    pub fn preview_eviction(&self, new_tx: &SignedTransaction) -> Vec<H256> {
+        // Nothing is collected for a replacement or for a transaction which
+        // is rejected before garbage collection.
+        if self.deferred_pool.check_sender_and_nonce_exists(
+            &new_tx.sender(),
+            &new_tx.nonce(),
+        ) || self.deferred_pool.is_sender_full(&new_tx.sender())
+        {
+            return Vec::new();
+        }
+        self.simulate_garbage_collection(
+            Some(new_tx),
+            |total_deferred, total_local, total_tx_size| {
+                self.is_full_with(
+                    total_deferred,
+                    total_local,
+                    total_tx_size,
+                    false, /* use_reserved_slots */
+                )
+            },
+        )
+        .iter()
+        .map(|tx| tx.hash())
+        .collect()
+    }
+
+    /// Return the transactions which `collect_garbage` would evict for
+    /// `new_tx`, in order, while `is_full` holds for the number of
+    /// transactions left, the number of local ones among them and their
+    /// total size, without changing the pool. Without `new_tx`, no sender is
+    /// spared for being the sender of the incoming transaction and every
+    /// checked sender is outbid.
+    fn simulate_garbage_collection(
+        &self, new_tx: Option<&SignedTransaction>,
+        is_full: impl Fn(usize, usize, usize) -> bool,
+    ) -> Vec<Arc<SignedTransaction>>
+    {
+        let mut evicted = Vec::new();
+        let mut garbage_collector = self.garbage_collector.clone();
+        // The nonces from which the transactions of the senders are left
+        // after the simulated evictions.
+        let mut next_nonces: HashMap<Address, U256> = HashMap::new();
+        let mut total_deferred = self.total_deferred();
+        let mut total_local = self.local_transactions.len();
+        let mut total_tx_size = self.total_tx_size;
+        let current_timestamp = self.get_current_timestamp();
+        while is_full(total_deferred, total_local, total_tx_size)
+            && !garbage_collector.is_empty()
+        {
+            let lowest_nonce_tx = |addr: &Address| {
+                let nonce = next_nonces.get(addr).cloned().unwrap_or_default();
+                self.deferred_pool.get_lowest_nonce_tx_from(addr, nonce)
+            };
+            let victim = match self.pick_gc_victim(
+                &mut garbage_collector,
+                new_tx,
+                total_deferred <= self.capacity, /* spare_local */
+                lowest_nonce_tx,
+                None,
+            ) {
+                Some(victim) => victim,
+                None => break,
+            };
+            let removed_tx = lowest_nonce_tx(&victim.sender).unwrap();
+            let next_nonce = removed_tx.nonce() + 1;
+            if self
+                .deferred_pool
+                .get_lowest_nonce_tx_from(&victim.sender, next_nonce)
+                .is_some()
+            {
+                garbage_collector.insert(
+                    &victim.sender,
+                    victim.count.saturating_sub(1),
+                    current_timestamp,
+                );
+            }
+            next_nonces.insert(victim.sender, next_nonce);
+            total_deferred -= 1;
+            if self.local_transactions.contains(&removed_tx.hash()) {
+                total_local -= 1;
+            }
+            total_tx_size =
+                total_tx_size.saturating_sub(Self::tx_size(&removed_tx));
+            evicted.push(removed_tx);
+        }
+        evicted
+    }
+
+    pub fn gc_stats(&self) -> GcStats { self.gc_stats }
+
+    /// Pop senders from `garbage_collector` until the victim of the next
+    /// collection for `new_tx` is picked, and insert the other popped senders
+    /// back. Without `new_tx`, the cheapest checked transaction is picked
+    /// under `GcStrategy::LowestGasPrice`. `lowest_nonce_tx` returns the
+    /// transaction which would be collected from a sender, or `None` if it
+    /// has no transactions left. The spared senders are counted in `stats`
+    /// if it is given.
+    fn pick_gc_victim(
+        &self, garbage_collector: &mut GarbageCollector,
+        new_tx: Option<&SignedTransaction>, spare_local: bool,
+        lowest_nonce_tx: impl Fn(&Address) -> Option<Arc<SignedTransaction>>,
+        mut stats: Option<&mut GcStats>,
+    ) -> Option<GarbageCollectorNode>
+    {
+        let mut cnt = self.gc_check_count;
+        let mut poped_nodes = Vec::new();
+        let mut victim = None;
+        let mut min_gas_price = new_tx.map(|tx| tx.gas_price);
+        while !garbage_collector.is_empty() && cnt != 0 {
+            let node = garbage_collector.pop().unwrap();
+            // Accounts which are not in `deferred_pool` may be inserted into
+            // `garbage_collector`, we can just ignore them.
+            let to_remove_tx = match lowest_nonce_tx(&node.sender) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            poped_nodes.push(node.clone());
+
+            // This node has executed transactions to GC. No need to check
+            // more.
+            if node.count > 0 {
+                victim = Some(node);
+                break;
+            }
+
+            // We do not GC a transaction from the same sender.
+            if new_tx.map_or(false, |tx| node.sender == tx.sender) {
+                if let Some(stats) = stats.as_mut() {
+                    GC_SPARED_SENDER_COUNTER.inc(1);
+                    stats.spared_sender += 1;
+                }
+                continue;
+            }
+
+            // If all accounts are ready, we choose the one whose first tx has
+            // the minimal gas price.
+            if spare_local
+                && self.local_transactions.contains(&to_remove_tx.hash())
+            {
+                if let Some(stats) = stats.as_mut() {
+                    GC_SPARED_LOCAL_COUNTER.inc(1);
+                    stats.spared_local += 1;
+                }
+                continue;
+            }
+            let is_better = match self.gc_strategy {
+                GcStrategy::LowestGasPrice => min_gas_price
+                    .map_or(true, |price| to_remove_tx.gas_price < price),
+                GcStrategy::OldestTimestamp => {
+                    victim.as_ref().map_or(true, |victim| {
+                        node.timestamp < victim.timestamp
+                    })
+                }
+            };
+            if is_better {
+                min_gas_price = Some(to_remove_tx.gas_price);
+                victim = Some(node);
+            }
+            cnt -= 1;
+        }
+        // Insert back other nodes to keep `garbage_collector` unchanged.
+        for node in poped_nodes {
+            if victim.is_some()
+                && node.sender == victim.as_ref().unwrap().sender
+            {
+                // skip victim
+                continue;
+            }
+            garbage_collector.insert(&node.sender, node.count, node.timestamp);
+        }
+        victim
+    }
+
+    /// Remove all the transactions of `sender`, e.g. when it is found
+    /// malicious, and return the number of removed transactions.
+    pub fn drop_sender(&mut self, sender: &Address) -> usize {
+        let txs = self.deferred_pool.all_txs(sender);
+        for tx in &txs {
+            let removed = self.deferred_pool.remove(sender, &tx.nonce());
+            if removed.map_or(false, |tx| !tx.is_already_packed()) {
+                self.unpacked_transaction_count = self
+                    .unpacked_transaction_count
+                    .checked_sub(1)
+                    .unwrap_or_else(|| {
+                        error!("unpacked_transaction_count under-flows.");
+                        0
+                    });
+            }
+            self.sub_tx_size(tx);
+            self.forget_transaction(&tx.hash());
+            self.notify_removal(tx.hash(), RemovalReason::Removed);
+        }
+        self.ready_account_pool.remove(sender);
+        self.ready_nonces_and_balances.remove(sender);
+        self.garbage_collector.remove(sender);
+        txs.len()
+    }
+
+    /// Mark the transactions of `address` with nonces from `from_nonce` to
+    /// `to_nonce` inclusive as packed, like `TransactionPool::set_tx_packed`
+    /// does for each of them, but recalculate the readiness of `address`
+    /// only once. Return the number of transactions newly marked.
+    pub fn set_range_packed(
+        &mut self, address: &Address, from_nonce: U256, to_nonce: U256,
+    ) -> usize {
+        let txs = self.deferred_pool.unpacked_txs_in_range(
+            address, from_nonce, to_nonce,
+        );
+        for tx in &txs {
+            let sponsored_info = self
+                .tx_sponsored_gas_map
+                .get(&tx.hash())
+                .cloned()
+                .unwrap_or((U256::zero(), 0));
+            self.insert_transaction_without_readiness_check(
+                tx.clone(),
+                true,  /* packed */
+                false, /* force */
+                None,  /* state_nonce_and_balance */
+                sponsored_info,
+            );
+        }
+        if !txs.is_empty() {
+            self.recalculate_readiness_with_local_info(address);
+        }
+        txs.len()
+    }
+
+    /// Remove the unpacked transactions which have stayed in the pool for more
+    /// than `max_age_secs` seconds, and return their hashes.
+    pub fn expire_transactions(&mut self, max_age_secs: u64) -> Vec<H256> {
+        let current_timestamp = self.get_current_timestamp();
+        self.expire_transactions_at(current_timestamp, max_age_secs)
+    }
+
+    fn expire_transactions_at(
+        &mut self, current_timestamp: u64, max_age_secs: u64,
+    ) -> Vec<H256> {
+        let expired: Vec<_> = self
+            .tx_insertion_timestamps
+            .iter()
+            .filter(|(_, inserted_at)| {
+                current_timestamp.saturating_sub(**inserted_at) > max_age_secs
+            })
+            .filter_map(|(tx_hash, _)| self.txs.get(tx_hash).cloned())
+            .filter(|tx| {
+                !self.deferred_pool.check_tx_packed(tx.sender(), tx.nonce())
+            })
+            .collect();
+
+        let mut senders = HashSet::new();
+        for tx in &expired {
+            self.deferred_pool.remove(&tx.sender(), &tx.nonce());
+            self.sub_tx_size(tx);
+            self.unpacked_transaction_count = self
+                .unpacked_transaction_count
+                .checked_sub(1)
+                .unwrap_or_else(|| {
+                    error!("unpacked_transaction_count under-flows.");
+                    0
+                });
+            self.forget_transaction(&tx.hash());
+            self.notify_removal(tx.hash(), RemovalReason::Expired);
+            senders.insert(tx.sender());
+        }
+
+        // Maintain the garbage collector and the readiness of the senders.
+        // Senders without transactions are ignored by `garbage_collector`.
+        for sender in &senders {
+            match self.get_local_nonce_and_balance(sender) {
+                Some((nonce, balance))
+                    if self.deferred_pool.contain_address(sender) =>
+                {
+                    self.recalculate_readiness_with_fixed_info(
+                        sender, nonce, balance,
+                    );
+                }
+                _ => {
+                    self.ready_nonces_and_balances.remove(sender);
+                    self.ready_account_pool.remove(sender);
+                }
+            }
+        }
+
+        expired.iter().map(|tx| tx.hash()).collect()
+    }
+
+    /// Return the gas price that a new transaction has to exceed to make
+    /// `collect_garbage` evict the lowest-nonce transaction of one of the
+    /// checked senders, or `None` if the pool is not full. Zero is returned if
+    /// an executed transaction can be collected or the victims are not chosen
+    /// by gas price, since any transaction can replace it.
+    pub fn displacement_price(&self) -> Option<U256> {
+        if !self.is_full() {
+            return None;
+        }
+        let mut min_gas_price: Option<U256> = None;
+        for node in self
+            .garbage_collector
+            .iter_by_priority()
+            .filter(|node| self.deferred_pool.contain_address(&node.sender))
+            .take(self.gc_check_count)
+        {
+            if node.count > 0 || self.gc_strategy != GcStrategy::LowestGasPrice
+            {
+                return Some(U256::zero());
+            }
+            let gas_price = self
+                .deferred_pool
+                .get_lowest_nonce_tx(&node.sender)?
+                .gas_price;
+            min_gas_price = Some(match min_gas_price {
+                Some(min_gas_price) => min_gas_price.min(gas_price),
+                None => gas_price,
+            });
+        }
+        min_gas_price
+    }
+
+    /// Estimate the bytes that would be freed by collecting garbage until at
+    /// most `new_capacity` transactions are left and they fit in the
+    /// `memory_capacity`, without changing the pool. The victims are chosen
+    /// as in `collect_garbage`, as if the incoming transaction outbid every
+    /// checked sender.
+    pub fn estimate_shrink_savings(&self, new_capacity: usize) -> usize {
+        self.simulate_garbage_collection(
+            None, /* new_tx */
+            |total_deferred, _total_local, total_tx_size| {
+                total_deferred > new_capacity
+                    || self.memory_capacity.map_or(false, |memory_capacity| {
+                        total_tx_size > memory_capacity
+                    })
+            },
+        )
+        .iter()
+        .map(Self::tx_size)
+        .sum()
+    }
+
     /// Collect garbage and return the remaining quota of the pool to insert new
     /// transactions.
     pub fn remaining_quota(&self) -> usize {
         let len = self.total_deferred();
-        self.capacity - len + self.garbage_collector.gc_size()
+        let gc_size = self.garbage_collector.gc_size();
+        match self.memory_capacity {
+            // The sizes of the new transactions are unknown, so the memory
+            // budget only rejects them all if nothing can be collected.
+            Some(memory_capacity)
+                if self.total_tx_size >= memory_capacity && gc_size == 0 =>
+            {
+                0
+            }
+            // Forced insertions may push the pool over its capacity.
+            _ => (self.capacity + gc_size).saturating_sub(len),
+        }
     }
 
     pub fn capacity(&self) -> usize { self.capacity }
 
     // the new inserting will fail if tx_pool is full (even if `force` is true)
     fn insert_transaction_without_readiness_check(
+        &mut self, transaction: Arc<SignedTransaction>, packed: bool,
+        force: bool, state_nonce_and_balance: Option<(U256, U256)>,
+        sponsored_gas_and_storage: (U256, u64),
+    ) -> InsertResult
+    {
+        self.insert_transaction_into_slots(
+            transaction,
+            packed,
+            force,
+            state_nonce_and_balance,
+            sponsored_gas_and_storage,
+            true, /* use_reserved_slots */
+        )
+    }
+
+    /// Like `insert_transaction_without_readiness_check`, but unless
+    /// `use_reserved_slots` is set, a new transaction can not take the
+    /// `local_reserved_slots`.
+    fn insert_transaction_into_slots(
         &mut self, transaction: Arc<SignedTransaction>, packed: bool,
         force: bool, state_nonce_and_balance: Option<(U256, U256)>,
         (sponsored_gas, sponsored_storage): (U256, u64),
+        use_reserved_slots: bool,
     ) -> InsertResult
     {
         let _timer = MeterTimer::time_func(
@@ -556,8 +2063,23 @@ impl TransactionPoolInner {
             &transaction.sender(),
             &transaction.nonce(),
         ) {
-            self.collect_garbage(transaction.as_ref());
-            if self.is_full() {
+            // Check it before garbage collection so that nothing is evicted
+            // for a transaction which can not be inserted anyway.
+            if self.deferred_pool.is_sender_full(&transaction.sender()) {
+                return InsertResult::Failed(format!(
+                    "Sender {:?} already has {} transactions in the pool",
+                    transaction.sender(),
+                    self.deferred_pool.max_txs_per_sender
+                ));
+            }
+            let count_before_gc = self.total_deferred();
+            self.collect_garbage(transaction.as_ref(), use_reserved_slots);
+            self.gc_thrashing_detector
+                .record(count_before_gc - self.total_deferred());
+            let within_margin = force
+                && self.total_deferred()
+                    < self.capacity + self.forced_insert_overflow_margin;
+            if self.is_full_for(use_reserved_slots) && !within_margin {
                 return InsertResult::Failed("Transaction Pool is full".into());
             }
         }
@@ -599,11 +2121,15 @@ impl TransactionPoolInner {
                     count,
                     timestamp,
                 );
+                self.add_tx_size(&transaction);
                 self.txs.insert(transaction.hash(), transaction.clone());
                 self.tx_sponsored_gas_map.insert(
                     transaction.hash(),
                     (sponsored_gas, sponsored_storage),
                 );
+                let current_timestamp = self.get_current_timestamp();
+                self.tx_insertion_timestamps
+                    .insert(transaction.hash(), current_timestamp);
                 if !packed {
                     self.unpacked_transaction_count += 1;
                 }
@@ -619,9 +2145,21 @@ impl TransactionPoolInner {
                             0
                         });
                 }
-                self.txs.remove(&replaced_tx.hash());
+                self.sub_tx_size(replaced_tx.get_arc_tx());
+                self.add_tx_size(&transaction);
+                // A transaction can replace itself, e.g. when it is marked as
+                // packed.
+                if replaced_tx.hash() != transaction.hash() {
+                    self.forget_transaction(&replaced_tx.hash());
+                    let current_timestamp = self.get_current_timestamp();
+                    self.tx_insertion_timestamps
+                        .insert(transaction.hash(), current_timestamp);
+                    self.notify_removal(
+                        replaced_tx.hash(),
+                        RemovalReason::Replaced,
+                    );
+                }
                 self.txs.insert(transaction.hash(), transaction.clone());
-                self.tx_sponsored_gas_map.remove(&replaced_tx.hash());
                 self.tx_sponsored_gas_map.insert(
                     transaction.hash(),
                     (sponsored_gas, sponsored_storage),
@@ -654,6 +2192,32 @@ impl TransactionPoolInner {
         }
     }
 
+    /// Return at most `limit` senders whose first unpacked transaction that
+    /// is not ready is pending for `reason_filter`, or any reason if it is
+    /// `None`, together with the transaction and the reason.
+    pub fn get_all_pending(
+        &self, reason_filter: Option<PendingReason>, limit: usize,
+    ) -> Vec<(Address, Arc<SignedTransaction>, PendingReason)> {
+        self.deferred_pool
+            .buckets
+            .keys()
+            .filter_map(|address| {
+                let (nonce, balance) = self
+                    .get_local_nonce_and_balance(address)
+                    .unwrap_or((0.into(), 0.into()));
+                let balance = self.spendable_balance(balance);
+                let (tx, reason) = self
+                    .deferred_pool
+                    .first_pending_tx(address, nonce, balance)?;
+                Some((*address, tx, reason))
+            })
+            .filter(|(_, _, reason)| {
+                reason_filter.map_or(true, |filter| filter == *reason)
+            })
+            .take(limit)
+            .collect()
+    }
+
     pub fn get_account_pending_transactions(
         &self, address: &Address, maybe_start_nonce: Option<U256>,
         maybe_limit: Option<usize>,
@@ -768,43 +2332,164 @@ impl TransactionPoolInner {
             .unwrap_or(state_nonce)
     }
 
+    /// Return the nonces of `address` from `state_nonce` up to its highest
+    /// nonce in the pool which have no transaction. A transaction after the
+    /// first gap can not be packed until the gap is filled.
+    pub fn get_nonce_gaps(
+        &self, address: &Address, state_nonce: U256,
+    ) -> Vec<U256> {
+        self.deferred_pool.nonce_gaps(address, state_nonce)
+    }
+
+    /// Classify a transaction of `address` at `nonce` against the
+    /// transactions in the pool without changing them.
+    pub fn nonce_reachability(
+        &self, address: &Address, nonce: U256, state_nonce: U256,
+    ) -> NonceReachability {
+        if nonce < state_nonce {
+            return NonceReachability::Stale;
+        }
+        let next_nonce = self.get_next_nonce(address, state_nonce);
+        if nonce <= next_nonce {
+            NonceReachability::Reachable
+        } else {
+            NonceReachability::BehindGap(next_nonce)
+        }
+    }
+
+    /// The part of `balance` that can be spent by ready transactions.
+    fn spendable_balance(&self, balance: U256) -> U256 {
+        balance.saturating_sub(self.min_balance_reserve)
+    }
+
     fn recalculate_readiness_with_local_info(&mut self, addr: &Address) {
         let (nonce, balance) = self
             .get_local_nonce_and_balance(addr)
             .unwrap_or((0.into(), 0.into()));
+        let balance = self.spendable_balance(balance);
         let ret = self
             .deferred_pool
             .recalculate_readiness_with_local_info(addr, nonce, balance);
-        self.ready_account_pool.update(addr, ret);
+        self.update_ready_tx(addr, ret);
+    }
+
+    /// Set `ready_tx` as the ready transaction of `addr` unless it is
+    /// quarantined, in which case no transaction of `addr` is ready until it
+    /// is released. `ready_callback` is called if `addr` had no ready
+    /// transaction before.
+    fn update_ready_tx(
+        &mut self, addr: &Address, ready_tx: Option<Arc<SignedTransaction>>,
+    ) {
+        let ready_tx =
+            ready_tx.filter(|tx| !self.quarantined.contains(&tx.hash()));
+        let becomes_ready =
+            ready_tx.is_some() && self.ready_account_pool.get(addr).is_none();
+        self.ready_account_pool.update(addr, ready_tx);
+        if becomes_ready {
+            if let Some(callback) = &self.ready_callback {
+                callback(addr);
+            }
+        }
+    }
+
+    /// Hold back a transaction from packing without removing it. The later
+    /// transactions of the same sender can not be packed either until it is
+    /// released. Return `false` if the transaction is not in the pool.
+    pub fn quarantine(&mut self, tx_hash: H256) -> bool {
+        let sender = match self.txs.get(&tx_hash) {
+            Some(tx) => tx.sender(),
+            None => return false,
+        };
+        self.quarantined.insert(tx_hash);
+        self.recalculate_readiness_with_local_info(&sender);
+        true
+    }
+
+    /// Undo `quarantine` so that the transaction can be packed again.
+    /// Return `false` if the transaction is not quarantined.
+    pub fn release(&mut self, tx_hash: H256) -> bool {
+        if !self.quarantined.remove(&tx_hash) {
+            return false;
+        }
+        if let Some(sender) = self.txs.get(&tx_hash).map(|tx| tx.sender()) {
+            self.recalculate_readiness_with_local_info(&sender);
+        }
+        true
+    }
+
+    /// Check if `tx` is still the first ready transaction of its sender in
+    /// `deferred_pool`.
+    fn is_ready_head(&self, tx: &SignedTransaction) -> bool {
+        let (nonce, balance) = self
+            .get_local_nonce_and_balance(&tx.sender())
+            .unwrap_or((0.into(), 0.into()));
+        let balance = self.spendable_balance(balance);
+        self.deferred_pool
+            .recalculate_readiness_with_local_info(&tx.sender(), nonce, balance)
+            .map_or(false, |ready_tx| ready_tx.hash() == tx.hash())
     }
 
     fn recalculate_readiness_with_fixed_info(
         &mut self, addr: &Address, nonce: U256, balance: U256,
     ) {
         self.update_nonce_and_balance(addr, nonce, balance);
+        let balance = self.spendable_balance(balance);
         let ret = self
             .deferred_pool
             .recalculate_readiness_with_local_info(addr, nonce, balance);
-        self.ready_account_pool.update(addr, ret);
+        self.update_ready_tx(addr, ret);
+    }
+
+    /// Set the nonce and balance of `addr` and recalculate its readiness with
+    /// them, for callers without an `AccountCache`.
+    pub fn set_account_state_and_recalculate(
+        &mut self, addr: &Address, nonce: U256, balance: U256,
+    ) {
+        self.recalculate_readiness_with_fixed_info(addr, nonce, balance);
     }
 
     fn recalculate_readiness_with_state(
         &mut self, addr: &Address, account_cache: &AccountCache,
     ) -> StateDbResult<()> {
         let _timer = MeterTimer::time_func(TX_POOL_RECALCULATE.as_ref());
+        #[cfg(test)]
+        RECALCULATE_COUNT.with(|count| count.set(count.get() + 1));
         let (nonce, balance) = self
             .get_and_update_nonce_and_balance_from_storage(
                 addr,
                 account_cache,
             )?;
+        let balance = self.spendable_balance(balance);
         let ret = self
             .deferred_pool
             .recalculate_readiness_with_local_info(addr, nonce, balance);
-        self.ready_account_pool.update(addr, ret);
+        self.update_ready_tx(addr, ret);
 
         Ok(())
     }
 
+    /// Re-read the nonce and balance of `address` from `account_cache`, e.g.
+    /// after a reorg changes them, and recalculate its readiness. A ready
+    /// transaction which the new balance can not afford becomes pending.
+    /// Return the status of the first unpacked transaction from the new
+    /// nonce, or `None` if there is no such transaction.
+    pub fn revalidate_account(
+        &mut self, address: &Address, account_cache: &AccountCache,
+    ) -> StateDbResult<Option<TransactionStatus>> {
+        self.recalculate_readiness_with_state(address, account_cache)?;
+        if self.ready_account_pool.get(address).is_some() {
+            return Ok(Some(TransactionStatus::Ready));
+        }
+        let (nonce, balance) = self
+            .get_local_nonce_and_balance(address)
+            .unwrap_or((0.into(), 0.into()));
+        let balance = self.spendable_balance(balance);
+        Ok(self
+            .deferred_pool
+            .first_pending_tx(address, nonce, balance)
+            .map(|(_, reason)| TransactionStatus::Pending(reason)))
+    }
+
     pub fn check_tx_packed_in_deferred_pool(&self, tx_hash: &H256) -> bool {
         match self.txs.get(tx_hash) {
             Some(tx) => {
@@ -814,17 +2499,61 @@ impl TransactionPoolInner {
         }
     }
 
-    /// pack at most num_txs transactions randomly
+    /// Return the transactions in `block_txs` that are currently marked as
+    /// packed. If the block becomes orphaned, pass them to `unpack`.
+    pub fn snapshot_packed(&self, block_txs: &[H256]) -> Vec<H256> {
+        block_txs
+            .iter()
+            .filter(|tx_hash| self.check_tx_packed_in_deferred_pool(tx_hash))
+            .cloned()
+            .collect()
+    }
+
+    /// Mark the transactions in `block_txs` as unpacked again, recalculating
+    /// the readiness once for each affected sender. Returns the number of
+    /// transactions that were unpacked.
+    pub fn unpack(&mut self, block_txs: &[H256]) -> usize {
+        let mut senders = HashSet::new();
+        let mut unpacked = 0;
+        for tx_hash in block_txs {
+            if !self.check_tx_packed_in_deferred_pool(tx_hash) {
+                continue;
+            }
+            let tx = self.txs[tx_hash].clone();
+            let sponsored_gas = self
+                .tx_sponsored_gas_map
+                .get(tx_hash)
+                .cloned()
+                .unwrap_or((U256::from(0), 0));
+            self.insert_transaction_without_readiness_check(
+                tx.clone(),
+                false, /* packed */
+                true,  /* force */
+                None,  /* state_nonce_and_balance */
+                sponsored_gas,
+            );
+            senders.insert(tx.sender());
+            unpacked += 1;
+        }
+        for sender in &senders {
+            self.recalculate_readiness_with_local_info(sender);
+        }
+        unpacked
+    }
+
+    /// pack at most num_txs transactions randomly. If `with_rlp` is set and
+    /// `num_txs` is not zero, the packed transactions are also returned as
+    /// an rlp list.
     pub fn pack_transactions<'a>(
         &mut self, num_txs: usize, block_gas_limit: U256,
         block_size_limit: usize, best_epoch_height: u64,
         best_block_number: u64, verification_config: &VerificationConfig,
-        machine: &Machine,
-    ) -> Vec<Arc<SignedTransaction>>
+        machine: &Machine, order: PackingOrder, with_rlp: bool,
+    ) -> (Vec<Arc<SignedTransaction>>, Option<Vec<u8>>)
     {
         let mut packed_transactions: Vec<Arc<SignedTransaction>> = Vec::new();
         if num_txs == 0 {
-            return packed_transactions;
+            return (packed_transactions, None);
         }
 
         let mut total_tx_gas_limit: U256 = 0.into();
@@ -836,8 +2565,45 @@ impl TransactionPoolInner {
         let spec = machine.spec(best_block_number);
         let transitions = &machine.params().transition_heights;
 
-        'out: while let Some(tx) = self.ready_account_pool.pop() {
-            let tx_size = tx.rlp_size();
+        self.ready_account_pool.refresh_weight_scaling();
+        self.ready_account_pool.refresh_aging();
+        let mut price_queue = match order {
+            PackingOrder::Random => None,
+            PackingOrder::GasPrice => {
+                Some(self.ready_account_pool.price_queue())
+            }
+        };
+
+        'out: while let Some(tx) = match &mut price_queue {
+            Some(queue) => self.ready_account_pool.pop_by_price(queue),
+            None => self.ready_account_pool.pop(),
+        } {
+            if !self.is_ready_head(&tx) {
+                warn!(
+                    "Ready pool is inconsistent with deferred pool, skip \
+                     transaction {:?} and resync sender {:?}",
+                    tx.hash(),
+                    tx.sender()
+                );
+                self.recalculate_readiness_with_local_info(&tx.sender());
+                if let Some(queue) = &mut price_queue {
+                    self.ready_account_pool
+                        .requeue_by_price(queue, &tx.sender());
+                }
+                continue 'out;
+            }
+
+            // An oversized transaction stays ready for later blocks, but it
+            // is not drawn again in this round.
+            if tx.gas_limit().saturating_mul(100.into())
+                > block_gas_limit
+                    .saturating_mul(self.max_single_tx_gas_percent.into())
+            {
+                recycle_txs.push(tx.clone());
+                continue 'out;
+            }
+
+            let tx_size = self.cached_rlp_size(&tx);
             if block_gas_limit - total_tx_gas_limit < *tx.gas_limit()
                 || block_size_limit - total_tx_size < tx_size
             {
@@ -863,6 +2629,9 @@ impl TransactionPoolInner {
                     continue 'out;
                 }
                 PackingCheckResult::Drop => {
+                    // The transaction is not put back, so its sender is no
+                    // longer ready.
+                    self.ready_account_pool.remove(&tx.sender());
                     continue 'out;
                 }
             }
@@ -871,6 +2640,7 @@ impl TransactionPoolInner {
             total_tx_size += tx_size;
 
             packed_transactions.push(tx.clone());
+            self.record_packed_gas_price(tx.gas_price);
             self.insert_transaction_without_readiness_check(
                 tx.clone(),
                 true, /* packed */
@@ -882,6 +2652,9 @@ impl TransactionPoolInner {
                     .unwrap_or((U256::from(0), 0)),
             );
             self.recalculate_readiness_with_local_info(&tx.sender());
+            if let Some(queue) = &mut price_queue {
+                self.ready_account_pool.requeue_by_price(queue, &tx.sender());
+            }
 
             if packed_transactions.len() >= num_txs {
                 break 'out;
@@ -908,25 +2681,79 @@ impl TransactionPoolInner {
             self.recalculate_readiness_with_local_info(&tx.sender());
         }
 
-        if log::max_level() >= log::Level::Debug {
-            let mut rlp_s = RlpStream::new();
+        debug!(
+            "After packing packed_transactions: {}, total rlp size: {}",
+            packed_transactions.len(),
+            total_tx_size,
+        );
+        let mut rlp_bytes = None;
+        if with_rlp {
+            let mut rlp_s = RlpStream::new_list(packed_transactions.len());
             for tx in &packed_transactions {
                 rlp_s.append::<TransactionWithSignature>(&**tx);
             }
-            debug!(
-                "After packing packed_transactions: {}, rlp size: {}",
-                packed_transactions.len(),
-                rlp_s.out().len(),
-            );
+            rlp_bytes = Some(rlp_s.out());
         }
 
-        packed_transactions
+        (packed_transactions, rlp_bytes)
     }
 
-    pub fn notify_modified_accounts(
+    fn record_packed_gas_price(&mut self, gas_price: U256) {
+        if self.packed_fee_window == 0 {
+            return;
+        }
+        if self.recent_packed_gas_prices.len() == self.packed_fee_window {
+            self.recent_packed_gas_prices.pop_front();
+        }
+        self.recent_packed_gas_prices.push_back(gas_price);
+    }
+
+    /// Return the gas price at `percentile` (0 to 100) among the recently
+    /// packed transactions, or `None` if no transaction has been packed or
+    /// `percentile` is larger than 100.
+    pub fn recent_packed_fee_percentile(&self, percentile: u8) -> Option<U256> {
+        if self.recent_packed_gas_prices.is_empty() || percentile > 100 {
+            return None;
+        }
+        let mut gas_prices: Vec<U256> =
+            self.recent_packed_gas_prices.iter().cloned().collect();
+        gas_prices.sort();
+        let index = (gas_prices.len() - 1) * percentile as usize / 100;
+        Some(gas_prices[index])
+    }
+
+    /// Return the gas price at `percentile` (0 to 100, a larger one is taken
+    /// as 100) among the ready transactions, which a new transaction needs to
+    /// compete with to be packed. It's zero if the pool is not full.
+    pub fn suggested_gas_price(&self, percentile: u8) -> U256 {
+        if !self.is_full() {
+            return U256::zero();
+        }
+        let gas_prices = self.ready_account_pool.sorted_gas_prices();
+        if gas_prices.is_empty() {
+            return U256::zero();
+        }
+        let index =
+            (gas_prices.len() - 1) * cmp::min(percentile, 100) as usize / 100;
+        gas_prices[index]
+    }
+
+    /// Recalculate the readiness of the accounts modified by execution. An
+    /// account may be modified several times, and only its last state is
+    /// used, so it is recalculated once.
+    pub fn notify_modified_accounts(
         &mut self, accounts_from_execution: Vec<Account>,
     ) {
-        for account in &accounts_from_execution {
+        let mut seen = HashSet::new();
+        let latest: Vec<_> = accounts_from_execution
+            .iter()
+            .rev()
+            .filter(|account| seen.insert(*account.address()))
+            .collect();
+        for account in latest.into_iter().rev() {
+            let _timer = MeterTimer::time_func(TX_POOL_RECALCULATE.as_ref());
+            #[cfg(test)]
+            RECALCULATE_COUNT.with(|count| count.set(count.get() + 1));
             self.recalculate_readiness_with_fixed_info(
                 account.address(),
                 account.nonce,
@@ -935,6 +2762,212 @@ impl TransactionPoolInner {
         }
     }
 
+    /// Aggregate the sponsored gas and storage of the transactions calling
+    /// `sponsor_contract` by their senders.
+    pub fn sponsored_by_sender(
+        &self, sponsor_contract: &Address,
+    ) -> HashMap<Address, (U256, u64)> {
+        let mut sponsored = HashMap::new();
+        for (tx_hash, (sponsored_gas, sponsored_storage)) in
+            &self.tx_sponsored_gas_map
+        {
+            if sponsored_gas.is_zero() && *sponsored_storage == 0 {
+                continue;
+            }
+            let tx = match self.txs.get(tx_hash) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            match &tx.action {
+                Action::Call(callee) if callee == sponsor_contract => {}
+                _ => continue,
+            }
+            let entry = sponsored
+                .entry(tx.sender())
+                .or_insert((U256::zero(), 0));
+            entry.0 += *sponsored_gas;
+            entry.1 += *sponsored_storage;
+        }
+        sponsored
+    }
+
+    /// Re-evaluate the sponsorship of unpacked transactions calling
+    /// `sponsor_contract` with the latest sponsor balances. The balances are
+    /// spent on the transactions by sender and then nonce, and the
+    /// transactions that the rest of the balances can no longer afford lose
+    /// their sponsorship. The readiness of their senders is recalculated.
+    pub fn notify_sponsor_balance(
+        &mut self, sponsor_contract: Address, available_gas_balance: U256,
+        available_collateral: U256,
+    )
+    {
+        let mut txs: Vec<_> = self
+            .transactions_to_contract(&sponsor_contract)
+            .into_iter()
+            .filter(|tx| {
+                !self.deferred_pool.check_tx_packed(tx.sender(), tx.nonce())
+            })
+            .collect();
+        txs.sort_by_key(|tx| (tx.sender(), tx.nonce()));
+
+        let mut remaining_gas_balance = available_gas_balance;
+        let mut remaining_collateral = available_collateral;
+        let mut to_update = Vec::new();
+        for tx in txs {
+            let (sponsored_gas, sponsored_storage) =
+                match self.tx_sponsored_gas_map.get(&tx.hash()) {
+                    Some(sponsored) => *sponsored,
+                    None => continue,
+                };
+            let mut new_sponsored_gas = sponsored_gas;
+            if !sponsored_gas.is_zero() {
+                let gas_fee = estimated_gas_fee(&tx);
+                if gas_fee > remaining_gas_balance {
+                    new_sponsored_gas = U256::zero();
+                } else {
+                    remaining_gas_balance -= gas_fee;
+                }
+            }
+            let mut new_sponsored_storage = sponsored_storage;
+            if sponsored_storage != 0 {
+                let collateral = U256::from(tx.storage_limit)
+                    * *DRIPS_PER_STORAGE_COLLATERAL_UNIT;
+                if collateral > remaining_collateral {
+                    new_sponsored_storage = 0;
+                } else {
+                    remaining_collateral -= collateral;
+                }
+            }
+            if new_sponsored_gas != sponsored_gas
+                || new_sponsored_storage != sponsored_storage
+            {
+                to_update.push((tx, new_sponsored_gas, new_sponsored_storage));
+            }
+        }
+
+        self.update_sponsored_info(to_update);
+    }
+
+    /// Re-evaluate the sponsorship of unpacked transactions calling
+    /// `sponsored_contract` with the sponsor info and the commission
+    /// privileges in `account_cache`, and recalculate the readiness of the
+    /// senders whose transactions are sponsored differently.
+    pub fn refresh_sponsored_info(
+        &mut self, sponsored_contract: &Address, account_cache: &AccountCache,
+    ) -> Result<(), String> {
+        let mut to_update = Vec::new();
+        for tx in self.transactions_to_contract(sponsored_contract) {
+            if self.deferred_pool.check_tx_packed(tx.sender(), tx.nonce()) {
+                continue;
+            }
+            let (sponsored_gas, sponsored_storage) =
+                sponsored_info(account_cache, &tx)?;
+            if self.tx_sponsored_gas_map.get(&tx.hash())
+                != Some(&(sponsored_gas, sponsored_storage))
+            {
+                to_update.push((tx, sponsored_gas, sponsored_storage));
+            }
+        }
+        self.update_sponsored_info(to_update);
+        Ok(())
+    }
+
+    /// Replace the sponsored gas and storage of unpacked transactions, and
+    /// recalculate the readiness of their senders.
+    fn update_sponsored_info(
+        &mut self, to_update: Vec<(Arc<SignedTransaction>, U256, u64)>,
+    ) {
+        let mut affected_senders = HashSet::new();
+        for (tx, sponsored_gas, sponsored_storage) in to_update {
+            self.deferred_pool.insert(
+                TxWithReadyInfo {
+                    transaction: tx.clone(),
+                    packed: false,
+                    sponsored_gas,
+                    sponsored_storage,
+                },
+                true, /* force */
+            );
+            self.tx_sponsored_gas_map
+                .insert(tx.hash(), (sponsored_gas, sponsored_storage));
+            affected_senders.insert(tx.sender());
+        }
+        for sender in affected_senders {
+            self.recalculate_readiness_with_local_info(&sender);
+        }
+    }
+
+    /// Return the unpacked transactions of the same sender that follow the
+    /// transaction `tx_hash` without a nonce gap. They can only become ready
+    /// after `tx_hash` is executed.
+    pub fn dependents(&self, tx_hash: &H256) -> Vec<Arc<SignedTransaction>> {
+        match self.txs.get(tx_hash) {
+            Some(tx) => self
+                .deferred_pool
+                .contiguous_txs_after(&tx.sender(), tx.nonce()),
+            None => Vec::new(),
+        }
+    }
+
+    /// Return the hashes of packed transactions whose nonces are below the
+    /// cached state nonce of their senders, so they are already executed.
+    pub fn confirmed_transactions(&self) -> Vec<H256> {
+        let mut confirmed = Vec::new();
+        for (addr, (state_nonce, _)) in &self.ready_nonces_and_balances {
+            confirmed.extend(
+                self.deferred_pool
+                    .packed_txs_below(addr, *state_nonce)
+                    .iter()
+                    .map(|tx| tx.hash()),
+            );
+        }
+        confirmed
+    }
+
+    /// Enable or disable adjusting the weight scaling of the ready pool to
+    /// the observed gas prices. When enabled, the scaling is updated now and
+    /// then before packing once every `TX_WEIGHT_SCALING_INTERVAL`. When
+    /// disabled, the configured scaling is restored.
+    pub fn set_auto_weight_scaling(&mut self, enabled: bool) {
+        let ready_account_pool = &mut self.ready_account_pool;
+        ready_account_pool.auto_weight_scaling = enabled;
+        if enabled {
+            ready_account_pool.update_weight_scaling();
+        } else {
+            // The cheap transactions also lose the minimum weight of auto
+            // scaling, so all weights are recomputed.
+            ready_account_pool.tx_weight_scaling =
+                ready_account_pool.configured_weight_scaling;
+            ready_account_pool.reweight();
+        }
+    }
+
+    /// Draw the random values of the weighted sampling in packing from a
+    /// generator seeded with `seed`, so that the same pool packs transactions
+    /// in the same order. `None` restores the thread local generator.
+    pub fn set_packing_seed(&mut self, seed: Option<u64>) {
+        self.ready_account_pool.rng = match seed {
+            Some(seed) => PopRng::Seeded(StdRng::seed_from_u64(seed)),
+            None => PopRng::Thread,
+        };
+    }
+
+    /// Return the ready transactions of all senders, sorted by the same
+    /// priority used in packing.
+    pub fn ready_sorted_by_priority(&self) -> Vec<Arc<SignedTransaction>> {
+        self.ready_account_pool.sorted_by_weight()
+    }
+
+    /// Return at most `limit` ready transactions whose gas prices are in
+    /// `[min, max]`, the highest gas price first.
+    pub fn get_transactions_in_price_range(
+        &self, min: U256, max: U256, limit: usize,
+    ) -> Vec<Arc<SignedTransaction>> {
+        let mut txs = self.ready_account_pool.in_price_range(min, max);
+        txs.truncate(limit);
+        txs
+    }
+
     /// content retrieves the ready and deferred transactions.
     pub fn content(
         &self, address: Option<Address>,
@@ -959,94 +2992,369 @@ impl TransactionPoolInner {
         (ready_txs, deferred_txs)
     }
 
-    // Add transaction into deferred pool and maintain its readiness
-    // the packed tag provided
-    // if force tag is true, the replacement in nonce pool must be happened
-    pub fn insert_transaction_with_readiness_check(
+    /// Check `txs` for transactions with the same sender and nonce, both
+    /// within the batch and against the packed transactions in the pool,
+    /// without inserting anything.
+    pub fn validate_batch(
+        &self, txs: &[Arc<SignedTransaction>],
+    ) -> Vec<BatchConflict> {
+        let mut conflicts = Vec::new();
+        let mut seen: HashMap<(Address, U256), H256> = HashMap::new();
+        for tx in txs {
+            let key = (tx.sender(), tx.nonce());
+            if let Some(first) = seen.get(&key) {
+                conflicts.push(BatchConflict::Duplicate {
+                    first: *first,
+                    second: tx.hash(),
+                });
+                continue;
+            }
+            seen.insert(key, tx.hash());
+            if self.deferred_pool.check_tx_packed(tx.sender(), tx.nonce()) {
+                if let Some(packed_tx) =
+                    self.get_by_address2nonce(tx.sender(), tx.nonce())
+                {
+                    conflicts.push(BatchConflict::AlreadyPacked {
+                        tx_hash: tx.hash(),
+                        packed_tx_hash: packed_tx.hash(),
+                    });
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Return the hashes of the transactions of `addr` that are ready to be
+    /// packed in sequence under the locally cached nonce and balance.
+    fn ready_frontier(&self, addr: &Address) -> Vec<H256> {
+        match self.get_local_nonce_and_balance(addr) {
+            Some((nonce, balance)) => self.deferred_pool.ready_chain(
+                addr,
+                nonce,
+                self.spendable_balance(balance),
+            ),
+            None => Vec::new(),
+        }
+    }
+
+    /// Insert `transactions` one by one with `insert` and return its results
+    /// together with, for every sender touched by the batch, the hashes of
+    /// the transactions which became ready because of the batch. The ready
+    /// frontier of each sender is compared once before and once after the
+    /// whole batch, so transactions that only become ready after a later one
+    /// in the batch fills a nonce gap are reported as well.
+    fn insert_batch_with<F, R>(
+        &mut self, transactions: Vec<Arc<SignedTransaction>>, mut insert: F,
+    ) -> (Vec<R>, HashMap<Address, Vec<H256>>)
+    where F: FnMut(&mut Self, Arc<SignedTransaction>) -> R {
+        let senders: Vec<_> =
+            transactions.iter().map(|tx| tx.sender()).collect();
+        self.track_promotions(&senders, |pool| {
+            transactions.into_iter().map(|tx| insert(pool, tx)).collect()
+        })
+    }
+
+    /// Run `f` and return its result together with the hashes of the
+    /// transactions of `senders` which became ready during `f`.
+    fn track_promotions<F, R>(
+        &mut self, senders: &[Address], f: F,
+    ) -> (R, HashMap<Address, Vec<H256>>)
+    where F: FnOnce(&mut Self) -> R {
+        let mut ready_before = HashMap::new();
+        for sender in senders {
+            if !ready_before.contains_key(sender) {
+                let frontier: HashSet<H256> =
+                    self.ready_frontier(sender).into_iter().collect();
+                ready_before.insert(*sender, frontier);
+            }
+        }
+
+        let results = f(self);
+
+        let mut promotions = HashMap::new();
+        for (sender, before) in ready_before {
+            let promoted: Vec<H256> = self
+                .ready_frontier(&sender)
+                .into_iter()
+                .filter(|hash| !before.contains(hash))
+                .collect();
+            if !promoted.is_empty() {
+                promotions.insert(sender, promoted);
+            }
+        }
+        (results, promotions)
+    }
+
+    /// Insert a batch of transactions with readiness check. Besides the
+    /// result of each insertion, return the hashes of the transactions that
+    /// became ready to be packed, grouped by sender. The state of each
+    /// sender is read and its readiness is recalculated only once, after all
+    /// its transactions in the batch are inserted.
+    pub fn insert_transactions_batch(
         &mut self, account_cache: &AccountCache,
-        transaction: Arc<SignedTransaction>, packed: bool, force: bool,
-    ) -> Result<(), String>
+        transactions: Vec<Arc<SignedTransaction>>, packed: bool, force: bool,
+    ) -> (Vec<Result<(), String>>, HashMap<Address, Vec<H256>>)
     {
         let _timer = MeterTimer::time_func(TX_POOL_INNER_INSERT_TIMER.as_ref());
-        let mut sponsored_gas = U256::from(0);
-        let mut sponsored_storage = 0;
-
-        // Compute sponsored_gas for `transaction`
-        if let Action::Call(callee) = &transaction.action {
-            // FIXME: This is a quick fix for performance issue.
-            if callee.maybe_contract_address() {
-                if let Some(sponsor_info) =
-                    account_cache.get_sponsor_info(callee).map_err(|e| {
-                        format!(
-                            "Failed to read account_cache from storage: {}",
-                            e
-                        )
-                    })?
-                {
-                    if account_cache
-                        .check_commission_privilege(
-                            &callee,
-                            &transaction.sender(),
-                        )
-                        .map_err(|e| {
-                            format!(
+        // The senders in the order of their first transaction in the batch.
+        let mut senders = Vec::new();
+        let mut sender_txs: HashMap<Address, Vec<usize>> = HashMap::new();
+        for (i, tx) in transactions.iter().enumerate() {
+            sender_txs
+                .entry(tx.sender())
+                .or_insert_with(|| {
+                    senders.push(tx.sender());
+                    Vec::new()
+                })
+                .push(i);
+        }
+
+        self.track_promotions(&senders, |pool| {
+            let mut results = vec![Ok(()); transactions.len()];
+            for sender in &senders {
+                let indices = &sender_txs[sender];
+                let (state_nonce, state_balance) =
+                    match account_cache.get_nonce_and_balance(sender) {
+                        Ok(state) => state,
+                        Err(e) => {
+                            let err = format!(
                                 "Failed to read account_cache from storage: {}",
                                 e
-                            )
-                        })?
-                    {
-                        let estimated_gas_u512 =
-                            transaction.gas.full_mul(transaction.gas_price);
-                        // Normally, it is less than 2^128
-                        let estimated_gas = if estimated_gas_u512
-                            > U512::from(U128::max_value())
-                        {
-                            U256::from(U128::max_value())
-                        } else {
-                            transaction.gas * transaction.gas_price
-                        };
-                        if estimated_gas <= sponsor_info.sponsor_gas_bound
-                            && estimated_gas
-                                <= sponsor_info.sponsor_balance_for_gas
-                        {
-                            sponsored_gas = transaction.gas;
+                            );
+                            for i in indices {
+                                results[*i] = Err(err.clone());
+                            }
+                            continue;
                         }
-                        let estimated_collateral =
-                            U256::from(transaction.storage_limit)
-                                * *DRIPS_PER_STORAGE_COLLATERAL_UNIT;
-                        if estimated_collateral
-                            <= sponsor_info.sponsor_balance_for_collateral
-                        {
-                            sponsored_storage = transaction.storage_limit;
+                    };
+
+                let mut inserted = false;
+                for i in indices {
+                    results[*i] = pool.insert_transaction_with_state(
+                        account_cache,
+                        transactions[*i].clone(),
+                        packed,
+                        force,
+                        false, /* is_local */
+                        state_nonce,
+                        state_balance,
+                    );
+                    inserted |= results[*i].is_ok();
+                }
+                if !inserted {
+                    continue;
+                }
+                if let Err(e) =
+                    pool.recalculate_readiness_with_state(sender, account_cache)
+                {
+                    let err = format!(
+                        "Failed to read account_cache from storage: {}",
+                        e
+                    );
+                    for i in indices {
+                        if results[*i].is_ok() {
+                            results[*i] = Err(err.clone());
                         }
                     }
                 }
             }
+            results
+        })
+    }
+
+    /// Like `content`, but only return the transactions in
+    /// `[offset, offset + limit)` of each list sorted by `sort_by`. Only the
+    /// keys of the first `offset + limit` transactions are kept while
+    /// scanning the pool.
+    pub fn content_paginated(
+        &self, address: Option<Address>, offset: usize, limit: usize,
+        sort_by: ContentSortOrder,
+    ) -> ContentPage
+    {
+        let (ready_txs, total_ready) = self.paginate(
+            self.ready_account_pool
+                .treap
+                .iter()
+                .filter(|(sender, _)| address.map_or(true, |a| a == **sender))
+                .map(|(_, tx)| &**tx),
+            offset,
+            limit,
+            sort_by,
+        );
+        let (deferred_txs, total_deferred) = self.paginate(
+            self.txs
+                .values()
+                .filter(|tx| address.map_or(true, |a| a == tx.sender()))
+                .map(|tx| &**tx),
+            offset,
+            limit,
+            sort_by,
+        );
+        ContentPage {
+            ready_txs,
+            total_ready,
+            deferred_txs,
+            total_deferred,
+        }
+    }
+
+    /// Return the transactions in `[offset, offset + limit)` of `txs` sorted
+    /// by `sort_by`, and the number of `txs`.
+    fn paginate<'a>(
+        &self, txs: impl Iterator<Item = &'a SignedTransaction>, offset: usize,
+        limit: usize, sort_by: ContentSortOrder,
+    ) -> (Vec<Arc<SignedTransaction>>, usize)
+    {
+        let window = offset.saturating_add(limit);
+        // A max-heap of the smallest `window` keys seen so far.
+        let mut smallest = BinaryHeap::new();
+        let mut total = 0;
+        for tx in txs {
+            total += 1;
+            if window == 0 {
+                continue;
+            }
+            let key = ContentSortKey::new(tx, sort_by);
+            if smallest.len() < window {
+                smallest.push(key);
+            } else if smallest.peek().map_or(false, |largest| key < *largest) {
+                smallest.pop();
+                smallest.push(key);
+            }
         }
+        let page = smallest
+            .into_sorted_vec()
+            .into_iter()
+            .skip(offset)
+            .filter_map(|key| self.txs.get(key.tx_hash()).cloned())
+            .collect();
+        (page, total)
+    }
 
+    // Add transaction into deferred pool and maintain its readiness
+    // the packed tag provided
+    // if force tag is true, the replacement in nonce pool must be happened
+    // if is_local tag is true, the transaction is spared by garbage collection
+    pub fn insert_transaction_with_readiness_check(
+        &mut self, account_cache: &AccountCache,
+        transaction: Arc<SignedTransaction>, packed: bool, force: bool,
+        is_local: bool,
+    ) -> Result<(), String>
+    {
+        let _timer = MeterTimer::time_func(TX_POOL_INNER_INSERT_TIMER.as_ref());
         let (state_nonce, state_balance) = account_cache
             .get_nonce_and_balance(&transaction.sender)
             .map_err(|e| {
                 format!("Failed to read account_cache from storage: {}", e)
             })?;
 
+        self.insert_transaction_with_state(
+            account_cache,
+            transaction.clone(),
+            packed,
+            force,
+            is_local,
+            state_nonce,
+            state_balance,
+        )?;
+
+        self.recalculate_readiness_with_state(
+            &transaction.sender,
+            account_cache,
+        )
+        .map_err(|e| {
+            format!("Failed to read account_cache from storage: {}", e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Insert `transaction` into the deferred pool given the nonce and
+    /// balance of its sender in the state, without recalculating the
+    /// readiness of the sender.
+    fn insert_transaction_with_state(
+        &mut self, account_cache: &AccountCache,
+        transaction: Arc<SignedTransaction>, packed: bool, force: bool,
+        is_local: bool, state_nonce: U256, state_balance: U256,
+    ) -> Result<(), String>
+    {
+        // Packed transactions come from blocks, so they are kept whatever
+        // their gas prices are.
+        if !packed && transaction.gas_price < self.min_gas_price {
+            return Err(format!(
+                "Transaction {:?} is discarded due to gas price {} less than \
+                 the minimum value {} of the pool",
+                transaction.hash(),
+                transaction.gas_price,
+                self.min_gas_price
+            ));
+        }
+
+        if !packed && !is_local && self.gc_thrashing_detector.is_thrashing() {
+            // Count the rejection as an insertion without eviction, so that
+            // the pool accepts transactions again once the window is passed.
+            self.gc_thrashing_detector.record(0);
+            return Err(format!(
+                "Transaction {:?} is rejected since the pool is thrashing on \
+                 garbage collection",
+                transaction.hash()
+            ));
+        }
+
+        let (sponsored_gas, sponsored_storage) =
+            sponsored_info(account_cache, &transaction)?;
+
         if transaction.hash[0] & 254 == 0 {
             trace!(
                 "Transaction {:?} sender: {:?} current nonce: {:?}, state nonce:{:?}",
                 transaction.hash, transaction.sender, transaction.nonce, state_nonce
             );
         }
+
+        self.check_nonce_range(&transaction, state_nonce, packed)?;
+        if !packed {
+            self.check_affordable(&transaction, state_balance, sponsored_gas)?;
+        }
+
+        let tx_hash = transaction.hash();
+        let result = self.insert_transaction_into_slots(
+            transaction,
+            packed,
+            force,
+            Some((state_nonce, state_balance)),
+            (sponsored_gas, sponsored_storage),
+            packed || is_local, /* use_reserved_slots */
+        );
+        if let InsertResult::Failed(info) = result {
+            return Err(format!("Failed imported to deferred pool: {}", info));
+        }
+        self.total_received_count += 1;
+        self.lifetime_received_count += 1;
+        if is_local {
+            self.local_transactions.insert(tx_hash);
+        }
+        Ok(())
+    }
+
+    /// Check that the nonce of `transaction` is neither too far after nor,
+    /// unless it is `packed`, before `state_nonce`.
+    fn check_nonce_range(
+        &self, transaction: &SignedTransaction, state_nonce: U256,
+        packed: bool,
+    ) -> Result<(), String>
+    {
         if transaction.nonce
-            >= state_nonce
-                + U256::from(FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET)
+            >= state_nonce + U256::from(self.furthest_future_nonce_offset)
         {
             trace!(
                 "Transaction {:?} is discarded due to in too distant future",
                 transaction.hash()
             );
             return Err(format!(
-                "Transaction {:?} is discarded due to in too distant future",
-                transaction.hash()
+                "Transaction {:?} is discarded due to in too distant future, \
+                 the nonce must be less than {}",
+                transaction.hash(),
+                state_nonce + U256::from(self.furthest_future_nonce_offset)
             ));
         } else if !packed /* Because we may get slightly out-dated state for transaction pool, we should allow transaction pool to set already past-nonce transactions to packed. */
             && transaction.nonce < state_nonce
@@ -1060,47 +3368,162 @@ impl TransactionPoolInner {
                 transaction.hash()
             ));
         }
+        Ok(())
+    }
 
-        let result = self.insert_transaction_without_readiness_check(
-            transaction.clone(),
-            packed,
-            force,
-            Some((state_nonce, state_balance)),
-            (sponsored_gas, sponsored_storage),
-        );
-        if let InsertResult::Failed(info) = result {
-            return Err(format!("Failed imported to deferred pool: {}", info));
+    /// With `strict_balance_margin` set, check that the sender of
+    /// `transaction` can pay `gas * gas_price + value` with `state_balance`
+    /// and the margin, where the gas paid by the sponsor is excluded.
+    fn check_affordable(
+        &self, transaction: &SignedTransaction, state_balance: U256,
+        sponsored_gas: U256,
+    ) -> Result<(), String>
+    {
+        let margin = match self.strict_balance_margin {
+            Some(margin) => margin,
+            None => return Ok(()),
+        };
+        let cost = (transaction.gas - sponsored_gas)
+            .full_mul(transaction.gas_price)
+            + U512::from(transaction.value);
+        if cost > U512::from(state_balance) + U512::from(margin) {
+            return Err(format!(
+                "Transaction {:?} is discarded since its cost {} exceeds the \
+                 balance {} of the sender",
+                transaction.hash(),
+                cost,
+                state_balance
+            ));
         }
+        Ok(())
+    }
 
-        self.recalculate_readiness_with_state(
-            &transaction.sender,
-            account_cache,
-        )
-        .map_err(|e| {
-            format!("Failed to read account_cache from storage: {}", e)
-        })?;
+    /// Write all transactions in the pool to `w` as an RLP list, which can be
+    /// restored by `load_from_reader`. The sponsored gas and storage are not
+    /// saved.
+    pub fn save_to_writer<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        let mut txs: Vec<_> = self.txs.values().collect();
+        // Restore the transactions of a sender in nonce order.
+        txs.sort_by_key(|tx| (tx.sender(), tx.nonce()));
+        let mut rlp_s = RlpStream::new_list(txs.len());
+        for tx in txs {
+            rlp_s.append::<SignedTransaction>(&**tx);
+        }
+        w.write_all(&rlp_s.out())
+    }
 
-        Ok(())
+    /// Insert the transactions saved by `save_to_writer` with the readiness
+    /// check against `account_cache`. The transactions that are rejected,
+    /// e.g. whose nonces are already stale, are dropped. Return the number of
+    /// restored transactions.
+    pub fn load_from_reader<R: io::Read>(
+        &mut self, r: &mut R, account_cache: &AccountCache,
+    ) -> io::Result<usize> {
+        let mut bytes = Vec::new();
+        r.read_to_end(&mut bytes)?;
+        let txs: Vec<SignedTransaction> =
+            Rlp::new(&bytes).as_list().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{:?}", e))
+            })?;
+        let mut restored = 0;
+        for tx in txs {
+            let tx_hash = tx.hash();
+            match self.insert_transaction_with_readiness_check(
+                account_cache,
+                Arc::new(tx),
+                false, /* packed */
+                false, /* force */
+                false, /* is_local */
+            ) {
+                Ok(()) => restored += 1,
+                Err(e) => {
+                    debug!("Drop saved transaction {:?}: {}", tx_hash, e)
+                }
+            }
+        }
+        Ok(restored)
     }
 }
 
 #[cfg(test)]
 mod test_transaction_pool_inner {
-    use super::{DeferredPool, InsertResult, TxWithReadyInfo};
-    use cfx_types::{Address, U256};
+    use super::{
+        BatchConflict, ContentSortOrder, DeferredPool, GcEntry, GcStrategy,
+        InsertResult, NonceReachability, PackingOrder, PendingReason, PopRng,
+        ReadyAccountPool, RemovalReason, TimeSource, TransactionPoolInner,
+        TransactionStatus, TxWithReadyInfo, DEFAULT_GC_CHECK_COUNT,
+        FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET, RECALCULATE_COUNT,
+        TX_WEIGHT_AGING_GRANULARITY,
+    };
+    use crate::{
+        machine::new_machine_with_builtin, sync::utils::new_test_pos_verifier,
+        test_helpers::get_state_for_genesis_write,
+        transaction_pool::{account_cache::AccountCache, TxPoolConfig},
+        verification::VerificationConfig, vm_factory::VmFactory,
+    };
+    use cfx_parameters::{
+        block::{MAX_BLOCK_SIZE_IN_BYTES, REFEREE_DEFAULT_BOUND},
+        consensus::TRANSACTION_DEFAULT_EPOCH_BOUND,
+    };
+    use cfx_state::{state_trait::StateOpsTrait, CleanupMode};
+    use cfx_storage::tests::{
+        new_state_manager_for_unit_test, FakeStateManager,
+    };
+    use cfx_types::{address_util::AddressUtil, Address, U256};
     use keylib::{Generator, KeyPair, Random};
-    use primitives::{Action, SignedTransaction, Transaction};
-    use std::sync::Arc;
+    use malloc_size_of::{new_malloc_size_ops, MallocSizeOf};
+    use primitives::{
+        Account, Action, SignedTransaction, Transaction,
+        TransactionWithSignature,
+    };
+    use rand::{rngs::StdRng, SeedableRng};
+    use std::{
+        collections::HashSet,
+        sync::{
+            atomic::{AtomicU64, AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+    };
+
+    /// Return an account cache over a state where each of `accounts` has
+    /// `balance` and the given nonce. The storage manager must outlive the
+    /// state read by the cache.
+    fn new_test_account_cache(
+        accounts: &[(Address, u64)], balance: U256,
+    ) -> (FakeStateManager, AccountCache) {
+        let storage_manager = new_state_manager_for_unit_test();
+        let mut state = get_state_for_genesis_write(&storage_manager);
+        for (address, nonce) in accounts {
+            state
+                .add_balance(
+                    address,
+                    &balance,
+                    CleanupMode::NoEmpty,
+                    U256::zero(), /* account_start_nonce */
+                )
+                .unwrap();
+            state.set_nonce(address, &(*nonce).into()).unwrap();
+        }
+        (storage_manager, AccountCache::new(Arc::new(state)))
+    }
 
     fn new_test_tx(
         sender: &KeyPair, nonce: usize, gas_price: usize, value: usize,
     ) -> Arc<SignedTransaction> {
+        new_test_call_tx(sender, nonce, gas_price, value, Address::random())
+    }
+
+    fn new_test_call_tx(
+        sender: &KeyPair, nonce: usize, gas_price: usize, value: usize,
+        callee: Address,
+    ) -> Arc<SignedTransaction>
+    {
         Arc::new(
             Transaction {
                 nonce: U256::from(nonce),
                 gas_price: U256::from(gas_price),
                 gas: U256::from(50000),
-                action: Action::Call(Address::random()),
+                action: Action::Call(callee),
                 value: U256::from(value),
                 storage_limit: 0,
                 epoch_height: 0,
@@ -1125,17 +3548,113 @@ mod test_transaction_pool_inner {
         }
     }
 
-    #[test]
-    fn test_deferred_pool_insert_and_remove() {
-        let mut deferred_pool = DeferredPool::new();
+    /// The config of the test pools, with a small window of packed fees.
+    fn test_config(capacity: usize) -> TxPoolConfig {
+        TxPoolConfig {
+            capacity,
+            packed_fee_window: 10,
+            ..Default::default()
+        }
+    }
 
-        // insert txs of same sender
-        let alice = Random.generate().unwrap();
-        let bob = Random.generate().unwrap();
-        let eva = Random.generate().unwrap();
+    fn new_test_pool(capacity: usize) -> TransactionPoolInner {
+        new_test_pool_with_gc(
+            capacity,
+            DEFAULT_GC_CHECK_COUNT,
+            GcStrategy::LowestGasPrice,
+        )
+    }
 
-        let alice_tx1 = new_test_tx_with_read_info(
-            &alice, 5, 10, 100, false, /* packed */
+    fn new_test_pool_with_gc(
+        capacity: usize, gc_check_count: usize, gc_strategy: GcStrategy,
+    ) -> TransactionPoolInner {
+        TransactionPoolInner::new(&TxPoolConfig {
+            gc_check_count,
+            gc_strategy,
+            ..test_config(capacity)
+        })
+    }
+
+    /// Pack at most `num_txs` transactions at epoch 0 without block gas or
+    /// size limits.
+    fn pack_test_txs(
+        pool: &mut TransactionPoolInner, num_txs: usize, with_rlp: bool,
+    ) -> (Vec<Arc<SignedTransaction>>, Option<Vec<u8>>)
+    {
+        pack_test_txs_in_order(
+            pool,
+            num_txs,
+            U256::max_value(),
+            PackingOrder::Random,
+            with_rlp,
+        )
+    }
+
+    /// Pack at most `num_txs` transactions in `order` at epoch 0 within
+    /// `block_gas_limit`.
+    fn pack_test_txs_in_order(
+        pool: &mut TransactionPoolInner, num_txs: usize, block_gas_limit: U256,
+        order: PackingOrder, with_rlp: bool,
+    ) -> (Vec<Arc<SignedTransaction>>, Option<Vec<u8>>)
+    {
+        let machine = Arc::new(new_machine_with_builtin(
+            Default::default(),
+            VmFactory::new(1024 * 32),
+        ));
+        let verification_config = VerificationConfig::new(
+            true, /* test_mode */
+            REFEREE_DEFAULT_BOUND,
+            MAX_BLOCK_SIZE_IN_BYTES,
+            TRANSACTION_DEFAULT_EPOCH_BOUND,
+            machine.clone(),
+            new_test_pos_verifier(),
+        );
+        pool.pack_transactions(
+            num_txs,
+            block_gas_limit,
+            usize::max_value(), /* block_size_limit */
+            0,                  /* best_epoch_height */
+            0,                  /* best_block_number */
+            &verification_config,
+            &machine,
+            order,
+            with_rlp,
+        )
+    }
+
+    /// Insert `tx` as a newly received transaction with the given state and
+    /// maintain its readiness.
+    fn insert_test_tx(
+        pool: &mut TransactionPoolInner, tx: Arc<SignedTransaction>,
+        state_nonce: usize, state_balance: U256,
+    ) -> InsertResult
+    {
+        let result = pool.insert_transaction_without_readiness_check(
+            tx.clone(),
+            false, /* packed */
+            false, /* force */
+            Some((state_nonce.into(), state_balance)),
+            (U256::zero(), 0),
+        );
+        pool.recalculate_readiness_with_fixed_info(
+            &tx.sender(),
+            state_nonce.into(),
+            state_balance,
+        );
+        result
+    }
+
+    #[test]
+    fn test_deferred_pool_insert_and_remove() {
+        let mut deferred_pool = DeferredPool::new(usize::max_value(), 0);
+
+        // insert txs of same sender
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let eva = Random.generate().unwrap();
+
+        let alice_tx1 = new_test_tx_with_read_info(
+            &alice, 5, 10, 100, false, /* packed */
         );
         let alice_tx2 = new_test_tx_with_read_info(
             &alice, 6, 10, 100, false, /* packed */
@@ -1219,9 +3738,53 @@ mod test_transaction_pool_inner {
         assert_eq!(deferred_pool.contain_address(&bob.address()), false);
     }
 
+    #[test]
+    fn test_deferred_pool_min_replace_bump() {
+        let mut deferred_pool = DeferredPool::new(usize::max_value(), 10);
+        let alice = Random.generate().unwrap();
+
+        let tx = new_test_tx_with_read_info(
+            &alice, 1, 100, 100, false, /* packed */
+        );
+        assert_eq!(
+            deferred_pool.insert(tx.clone(), false /* force */),
+            InsertResult::NewAdded
+        );
+
+        // A bump below 10% is rejected with the required gas price.
+        let tx_low = new_test_tx_with_read_info(
+            &alice, 1, 109, 100, false, /* packed */
+        );
+        assert_eq!(
+            deferred_pool.insert(tx_low, false /* force */),
+            InsertResult::Failed("Tx with same nonce already inserted. To replace it, you need to specify a gas price >= 110 (10% higher)".into())
+        );
+
+        // Exactly 10% higher is enough.
+        let tx_exact = new_test_tx_with_read_info(
+            &alice, 1, 110, 100, false, /* packed */
+        );
+        assert_eq!(
+            deferred_pool.insert(tx_exact.clone(), false /* force */),
+            InsertResult::Updated(tx.clone())
+        );
+
+        // `force` bypasses the rule even with a lower gas price.
+        assert_eq!(
+            deferred_pool.insert(tx.clone(), true /* force */),
+            InsertResult::Updated(tx_exact)
+        );
+        assert_eq!(
+            deferred_pool.buckets[&alice.address()]
+                .get_tx_by_nonce(1.into()),
+            Some(tx)
+        );
+    }
+
     #[test]
     fn test_deferred_pool_recalculate_readiness() {
-        let mut deferred_pool = super::DeferredPool::new();
+        let mut deferred_pool =
+            super::DeferredPool::new(usize::max_value(), 0);
 
         let alice = Random.generate().unwrap();
 
@@ -1339,4 +3902,2585 @@ mod test_transaction_pool_inner {
             None
         );
     }
+
+    #[test]
+    fn test_clear_keeps_lifetime_received() {
+        let mut pool = new_test_pool(100);
+        let alice = Random.generate().unwrap();
+        let (_storage_manager, account_cache) = new_test_account_cache(
+            &[(alice.address(), 0)],
+            U256::from(1_000_000_000),
+        );
+        for nonce in 0..3 {
+            pool.insert_transaction_with_readiness_check(
+                &account_cache,
+                new_test_tx(&alice, nonce, 10, 100),
+                false, /* packed */
+                false, /* force */
+                false, /* is_local */
+            )
+            .unwrap();
+        }
+        // A rejected transaction is not counted.
+        assert!(pool
+            .insert_transaction_with_readiness_check(
+                &account_cache,
+                new_test_tx(&alice, 0, 5, 100),
+                false, /* packed */
+                false, /* force */
+                false, /* is_local */
+            )
+            .is_err());
+        assert_eq!(pool.total_received(), 3);
+        assert_eq!(pool.lifetime_received(), 3);
+
+        pool.clear();
+        assert_eq!(pool.total_received(), 0);
+        assert_eq!(pool.lifetime_received(), 3);
+
+        pool.insert_transaction_with_readiness_check(
+            &account_cache,
+            new_test_tx(&alice, 0, 10, 100),
+            false, /* packed */
+            false, /* force */
+            false, /* is_local */
+        )
+        .unwrap();
+        assert_eq!(pool.total_received(), 1);
+        assert_eq!(pool.lifetime_received(), 4);
+    }
+
+    #[test]
+    fn test_drop_sender() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+        for nonce in 0..3 {
+            let tx = new_test_tx(&alice, nonce, 10, 100);
+            insert_test_tx(&mut pool, tx, 0, balance);
+        }
+        let bob_txs: Vec<_> =
+            (0..2).map(|nonce| new_test_tx(&bob, nonce, 10, 100)).collect();
+        for tx in &bob_txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+        assert_eq!(pool.total_unpacked(), 5);
+
+        assert_eq!(pool.drop_sender(&alice.address()), 3);
+        assert_eq!(pool.drop_sender(&alice.address()), 0);
+        assert!(!pool.deferred_pool.contain_address(&alice.address()));
+        assert_eq!(pool.ready_account_pool.get(&alice.address()), None);
+        assert_eq!(pool.get_local_nonce_and_balance(&alice.address()), None);
+        assert_eq!(
+            pool.garbage_collector.get_timestamp(&alice.address()),
+            None
+        );
+        assert_eq!(pool.total_deferred(), 2);
+        assert_eq!(pool.total_unpacked(), 2);
+        assert_eq!(pool.tx_sponsored_gas_map.len(), 2);
+
+        // Bob is untouched.
+        assert_eq!(pool.deferred_pool.all_txs(&bob.address()), bob_txs);
+        assert_eq!(
+            pool.ready_account_pool.get(&bob.address()),
+            Some(bob_txs[0].clone())
+        );
+        assert_eq!(
+            pool.get_local_nonce_and_balance(&bob.address()),
+            Some((0.into(), balance))
+        );
+        assert!(pool.garbage_collector.get_timestamp(&bob.address()).is_some());
+    }
+
+    #[test]
+    fn test_revalidate_account() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let tx = new_test_tx(&alice, 0, 10, 100);
+        let (_storage_manager, account_cache) = new_test_account_cache(
+            &[(alice.address(), 0)],
+            U256::from(1_000_000),
+        );
+        insert_test_tx(&mut pool, tx.clone(), 0, U256::from(1_000_000));
+        assert_eq!(
+            pool.revalidate_account(&alice.address(), &account_cache)
+                .unwrap(),
+            Some(TransactionStatus::Ready)
+        );
+        assert_eq!(pool.ready_account_pool.get(&alice.address()), Some(tx));
+
+        // The balance after the reorg only covers the transferred value.
+        let (_storage_manager, account_cache) =
+            new_test_account_cache(&[(alice.address(), 0)], U256::from(100));
+        assert_eq!(
+            pool.revalidate_account(&alice.address(), &account_cache)
+                .unwrap(),
+            Some(TransactionStatus::Pending(PendingReason::NotEnoughCash))
+        );
+        assert_eq!(pool.ready_account_pool.get(&alice.address()), None);
+        assert_eq!(
+            pool.get_account_pending_transactions(&alice.address(), None, None)
+                .1,
+            Some(TransactionStatus::Pending(PendingReason::NotEnoughCash))
+        );
+    }
+
+    #[test]
+    fn test_notify_modified_accounts_dedup() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(1_000_000);
+        let alice_txs: Vec<_> =
+            (0..2).map(|nonce| new_test_tx(&alice, nonce, 10, 100)).collect();
+        for tx in &alice_txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+        let bob_tx = new_test_tx(&bob, 0, 10, 100);
+        insert_test_tx(&mut pool, bob_tx.clone(), 0, balance);
+
+        let account = |sender: &KeyPair, balance: u64, nonce: u64| {
+            Account::new_empty_with_balance(
+                &sender.address(),
+                &balance.into(),
+                &nonce.into(),
+            )
+        };
+        let recalculate_count = || RECALCULATE_COUNT.with(|count| count.get());
+        let start = recalculate_count();
+        pool.notify_modified_accounts(vec![
+            account(&alice, 0, 0),
+            account(&bob, 1_000_000, 0),
+            account(&alice, 1_000_000, 1),
+            account(&bob, 0, 0),
+        ]);
+        assert_eq!(recalculate_count() - start, 2);
+
+        // Only the last state of each account is used.
+        assert_eq!(
+            pool.get_local_nonce_and_balance(&alice.address()),
+            Some((1.into(), 1_000_000.into()))
+        );
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(alice_txs[1].clone())
+        );
+        assert_eq!(
+            pool.get_local_nonce_and_balance(&bob.address()),
+            Some((0.into(), 0.into()))
+        );
+        assert_eq!(pool.ready_account_pool.get(&bob.address()), None);
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut pool = new_test_pool(10);
+        assert_eq!(pool.stats().remaining_quota, 10);
+
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(1_000_000);
+        insert_test_tx(&mut pool, new_test_tx(&alice, 0, 10, 100), 0, balance);
+        insert_test_tx(&mut pool, new_test_tx(&alice, 1, 10, 100), 0, balance);
+        // Bob's transaction has a nonce gap, so bob has no ready transaction.
+        insert_test_tx(&mut pool, new_test_tx(&bob, 1, 10, 100), 0, balance);
+
+        let stats = pool.stats();
+        assert_eq!(stats.total_deferred, pool.total_deferred());
+        assert_eq!(stats.total_ready_accounts, pool.total_ready_accounts());
+        assert_eq!(stats.total_received, pool.total_received());
+        assert_eq!(stats.total_unpacked, pool.total_unpacked());
+        assert_eq!(stats.remaining_quota, pool.remaining_quota());
+        assert_eq!(stats.total_deferred, 3);
+        assert_eq!(stats.total_ready_accounts, 1);
+        assert_eq!(stats.total_ready_gas, U256::from(50000));
+        assert_eq!(stats.total_senders, 2);
+    }
+
+    #[test]
+    fn test_notify_sponsor_balance() {
+        let mut pool = new_test_pool(100);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let contract = Address::random();
+        let other_contract = Address::random();
+        // Only the transferred value is paid by the senders themselves.
+        let balance = U256::from(100);
+
+        let alice_tx = new_test_call_tx(&alice, 0, 10, 100, contract);
+        let bob_tx = new_test_call_tx(&bob, 0, 10, 100, other_contract);
+        for tx in vec![alice_tx.clone(), bob_tx.clone()] {
+            assert_eq!(
+                pool.insert_transaction_without_readiness_check(
+                    tx.clone(),
+                    false, /* packed */
+                    false, /* force */
+                    Some((0.into(), balance)),
+                    (U256::from(50000), 0),
+                ),
+                InsertResult::NewAdded
+            );
+            pool.recalculate_readiness_with_fixed_info(
+                &tx.sender(),
+                0.into(),
+                balance,
+            );
+        }
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(alice_tx.clone())
+        );
+        assert_eq!(
+            pool.ready_account_pool.get(&bob.address()),
+            Some(bob_tx.clone())
+        );
+
+        // The sponsor can still afford the gas fee of 500000.
+        pool.notify_sponsor_balance(contract, 500000.into(), U256::zero());
+        assert_eq!(
+            pool.sponsored_by_sender(&contract).get(&alice.address()),
+            Some(&(U256::from(50000), 0))
+        );
+
+        pool.notify_sponsor_balance(contract, 499999.into(), U256::zero());
+        assert!(pool.sponsored_by_sender(&contract).is_empty());
+        assert_eq!(
+            pool.tx_sponsored_gas_map.get(&alice_tx.hash()),
+            Some(&(U256::zero(), 0))
+        );
+        assert_eq!(pool.ready_account_pool.get(&alice.address()), None);
+
+        // Transactions calling other contracts are not affected.
+        assert_eq!(
+            pool.tx_sponsored_gas_map.get(&bob_tx.hash()),
+            Some(&(U256::from(50000), 0))
+        );
+        assert_eq!(pool.ready_account_pool.get(&bob.address()), Some(bob_tx));
+    }
+
+    #[test]
+    fn test_notify_sponsor_balance_of_several_txs() {
+        let mut pool = new_test_pool(100);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let contract = Address::random();
+        let mut txs = vec![
+            new_test_call_tx(&alice, 0, 10, 100, contract),
+            new_test_call_tx(&alice, 1, 10, 100, contract),
+            new_test_call_tx(&bob, 0, 10, 100, contract),
+        ];
+        for tx in &txs {
+            assert_eq!(
+                pool.insert_transaction_without_readiness_check(
+                    tx.clone(),
+                    false, /* packed */
+                    false, /* force */
+                    Some((0.into(), U256::from(1000))),
+                    (U256::from(50000), 0),
+                ),
+                InsertResult::NewAdded
+            );
+        }
+        txs.sort_by_key(|tx| (tx.sender(), tx.nonce()));
+
+        // Each transaction costs the sponsor a gas fee of 500000, so the
+        // balance only covers the first two by sender and then nonce. The
+        // same transactions stay sponsored when notified again.
+        for _ in 0..2 {
+            pool.notify_sponsor_balance(
+                contract,
+                1_000_000.into(),
+                U256::zero(),
+            );
+            for (i, tx) in txs.iter().enumerate() {
+                let sponsored_gas =
+                    if i < 2 { U256::from(50000) } else { U256::zero() };
+                assert_eq!(
+                    pool.tx_sponsored_gas_map.get(&tx.hash()),
+                    Some(&(sponsored_gas, 0))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_refresh_sponsored_info() {
+        let mut pool = new_test_pool(100);
+        let alice = Random.generate().unwrap();
+        let sponsor = Address::random();
+        let mut contract = Address::random();
+        contract.set_contract_type_bits();
+        // Only the transferred value is paid by the sender.
+        let balance = U256::from(100);
+        let new_account_cache = |sponsor_balance: u64| {
+            let storage_manager = new_state_manager_for_unit_test();
+            let mut state = get_state_for_genesis_write(&storage_manager);
+            state
+                .add_balance(
+                    &alice.address(),
+                    &balance,
+                    CleanupMode::NoEmpty,
+                    U256::zero(), /* account_start_nonce */
+                )
+                .unwrap();
+            state
+                .new_contract(&contract, U256::zero(), U256::zero())
+                .unwrap();
+            state
+                .set_sponsor_for_gas(
+                    &contract,
+                    &sponsor,
+                    &sponsor_balance.into(),
+                    &U256::from(500000), /* upper_bound */
+                )
+                .unwrap();
+            state
+                .add_commission_privilege(contract, sponsor, alice.address())
+                .unwrap();
+            (storage_manager, AccountCache::new(Arc::new(state)))
+        };
+
+        let tx = new_test_call_tx(&alice, 0, 10, 100, contract);
+        let (_storage_manager, account_cache) = new_account_cache(500000);
+        pool.insert_transaction_with_state(
+            &account_cache,
+            tx.clone(),
+            false, /* packed */
+            false, /* force */
+            false, /* is_local */
+            0.into(),
+            balance,
+        )
+        .unwrap();
+        pool.recalculate_readiness_with_fixed_info(
+            &alice.address(),
+            0.into(),
+            balance,
+        );
+        assert_eq!(
+            pool.tx_sponsored_gas_map.get(&tx.hash()),
+            Some(&(U256::from(50000), 0))
+        );
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(tx.clone())
+        );
+
+        // Nothing changes while the sponsor can afford the gas fee.
+        pool.refresh_sponsored_info(&contract, &account_cache).unwrap();
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(tx.clone())
+        );
+
+        let (_storage_manager, account_cache) = new_account_cache(0);
+        pool.refresh_sponsored_info(&contract, &account_cache).unwrap();
+        assert_eq!(
+            pool.tx_sponsored_gas_map.get(&tx.hash()),
+            Some(&(U256::zero(), 0))
+        );
+        assert!(pool.sponsored_by_sender(&contract).is_empty());
+        assert_eq!(pool.ready_account_pool.get(&alice.address()), None);
+    }
+
+    #[test]
+    fn test_pack_with_rechecked_high_weight_tx() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let carol = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000_000u64);
+        // Alice's transaction takes almost all the weight, but it can only be
+        // packed in a much later epoch.
+        let alice_tx = Arc::new(
+            Transaction {
+                nonce: U256::zero(),
+                gas_price: U256::from(1_000_000),
+                gas: U256::from(50000),
+                action: Action::Call(Address::random()),
+                value: U256::zero(),
+                storage_limit: 0,
+                epoch_height: 2 * TRANSACTION_DEFAULT_EPOCH_BOUND,
+                chain_id: 0,
+                data: Vec::new(),
+            }
+            .sign(alice.secret()),
+        );
+        let bob_tx = new_test_tx(&bob, 0, 1, 0);
+        let carol_tx = new_test_tx(&carol, 0, 1, 0);
+        for tx in &[&alice_tx, &bob_tx, &carol_tx] {
+            insert_test_tx(&mut pool, (*tx).clone(), 0, balance);
+        }
+
+        let (packed, _) = pack_test_txs(&mut pool, 10, false);
+        let packed: HashSet<_> = packed.iter().map(|tx| tx.hash()).collect();
+        let expected: HashSet<_> =
+            vec![bob_tx.hash(), carol_tx.hash()].into_iter().collect();
+        assert_eq!(packed, expected);
+        // The rejected transaction stays ready for the later blocks.
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(alice_tx)
+        );
+    }
+
+    #[test]
+    fn test_ready_pool_aging() {
+        let mut ready_pool = ReadyAccountPool::new(
+            1,  /* tx_weight_scaling */
+            1,  /* tx_weight_exp */
+            10, /* tx_weight_aging_factor */
+        );
+        let time_source = Arc::new(MockTimeSource::default());
+        ready_pool.time_source = time_source.clone();
+        ready_pool.rng = PopRng::Seeded(StdRng::seed_from_u64(0));
+        let alice = Random.generate().unwrap();
+        ready_pool.insert(new_test_tx(&alice, 0, 1, 0));
+        // The other senders become ready much later than Alice.
+        time_source.advance(1_000_000);
+        for _ in 0..10 {
+            let sender = Random.generate().unwrap();
+            ready_pool.insert(new_test_tx(&sender, 0, 1_000_000, 0));
+        }
+        // Pop and put back the drawn transaction like packing does, and tell
+        // if Alice's transaction is drawn.
+        let alice_drawn = |ready_pool: &mut ReadyAccountPool, draws: usize| {
+            (0..draws).any(|_| {
+                let tx = ready_pool.pop().unwrap();
+                let is_alice = tx.sender() == alice.address();
+                ready_pool.insert(tx);
+                is_alice
+            })
+        };
+        // The aging is not counted until the weights are refreshed.
+        assert_eq!(ready_pool.weight_of(&alice.address()), Some(1));
+        assert!(!alice_drawn(&mut ready_pool, 20));
+
+        // Once refreshed like before packing, Alice's transaction has about
+        // half of the weight.
+        ready_pool.refresh_aging();
+        assert_eq!(
+            ready_pool.weight_of(&alice.address()),
+            Some(1 + 10 * 1_000_000)
+        );
+        assert_eq!(ready_pool.total_weight(), 1 + 10 * 1_000_000 * 2);
+        assert!(alice_drawn(&mut ready_pool, 20));
+        // The weights are kept until the aging granularity has passed.
+        time_source.advance(TX_WEIGHT_AGING_GRANULARITY - 1);
+        ready_pool.refresh_aging();
+        assert_eq!(
+            ready_pool.weight_of(&alice.address()),
+            Some(1 + 10 * 1_000_000)
+        );
+        // Alice keeps aging after her transaction is drawn and put back.
+        time_source.advance(1);
+        ready_pool.refresh_aging();
+        assert_eq!(
+            ready_pool.weight_of(&alice.address()),
+            Some(1 + 10 * (1_000_000 + TX_WEIGHT_AGING_GRANULARITY))
+        );
+        // Re-weighting reads the clock only once, in `refresh_aging`.
+        let reads = time_source.reads();
+        time_source.advance(TX_WEIGHT_AGING_GRANULARITY);
+        ready_pool.refresh_aging();
+        assert_eq!(time_source.reads(), reads + 1);
+    }
+
+    #[test]
+    fn test_ready_pool_without_aging() {
+        let mut ready_pool = ReadyAccountPool::new(
+            1, /* tx_weight_scaling */
+            1, /* tx_weight_exp */
+            0, /* tx_weight_aging_factor */
+        );
+        let time_source = Arc::new(MockTimeSource::default());
+        ready_pool.time_source = time_source.clone();
+        for _ in 0..3 {
+            let sender = Random.generate().unwrap();
+            ready_pool.insert(new_test_tx(&sender, 0, 10, 0));
+        }
+        time_source.advance(TX_WEIGHT_AGING_GRANULARITY);
+        ready_pool.refresh_aging();
+        ready_pool.reweight();
+        assert_eq!(time_source.reads(), 0);
+        assert!(ready_pool.ready_since.is_empty());
+        assert_eq!(ready_pool.total_weight(), 30);
+    }
+
+    #[test]
+    fn test_suggested_gas_price() {
+        let mut pool = new_test_pool(10);
+        let balance = U256::from(1_000_000_000);
+        for gas_price in 1..=10 {
+            assert_eq!(pool.suggested_gas_price(50), U256::zero());
+            let sender = Random.generate().unwrap();
+            let tx = new_test_tx(&sender, 0, gas_price * 10, 100);
+            insert_test_tx(&mut pool, tx, 0, balance);
+        }
+        assert_eq!(pool.total_ready_accounts(), 10);
+
+        assert_eq!(pool.suggested_gas_price(0), 10.into());
+        assert_eq!(pool.suggested_gas_price(50), 50.into());
+        assert_eq!(pool.suggested_gas_price(90), 90.into());
+        assert_eq!(pool.suggested_gas_price(100), 100.into());
+        assert_eq!(pool.suggested_gas_price(200), 100.into());
+    }
+
+    #[test]
+    fn test_recent_packed_fee_percentile() {
+        let mut pool = new_test_pool(100);
+        assert_eq!(pool.recent_packed_fee_percentile(50), None);
+
+        for gas_price in 1..=10 {
+            pool.record_packed_gas_price(U256::from(gas_price * 10));
+        }
+        assert_eq!(pool.recent_packed_fee_percentile(0), Some(10.into()));
+        assert_eq!(pool.recent_packed_fee_percentile(50), Some(50.into()));
+        assert_eq!(pool.recent_packed_fee_percentile(100), Some(100.into()));
+        assert_eq!(pool.recent_packed_fee_percentile(101), None);
+
+        // The window keeps the latest 10 gas prices only.
+        for _ in 0..5 {
+            pool.record_packed_gas_price(U256::from(1));
+        }
+        assert_eq!(pool.recent_packed_fee_percentile(0), Some(1.into()));
+        assert_eq!(pool.recent_packed_fee_percentile(50), Some(1.into()));
+        assert_eq!(pool.recent_packed_fee_percentile(60), Some(60.into()));
+        assert_eq!(pool.recent_packed_fee_percentile(100), Some(100.into()));
+    }
+
+    #[test]
+    fn test_max_single_tx_gas_percent() {
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let carol = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000_000u64);
+        let big_tx = Arc::new(
+            Transaction {
+                nonce: U256::zero(),
+                gas_price: U256::from(100),
+                gas: U256::from(900_000),
+                action: Action::Call(Address::random()),
+                value: U256::zero(),
+                storage_limit: 0,
+                epoch_height: 0,
+                chain_id: 0,
+                data: Vec::new(),
+            }
+            .sign(alice.secret()),
+        );
+        let bob_tx = new_test_tx(&bob, 0, 10, 100);
+        let carol_tx = new_test_tx(&carol, 0, 10, 100);
+        let pack = |max_single_tx_gas_percent| {
+            let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+                max_single_tx_gas_percent,
+                ..test_config(10)
+            });
+            for tx in &[&big_tx, &bob_tx, &carol_tx] {
+                insert_test_tx(&mut pool, (*tx).clone(), 0, balance);
+            }
+            let (packed, _) = pack_test_txs_in_order(
+                &mut pool,
+                10,
+                U256::from(1_000_000),
+                PackingOrder::Random,
+                false,
+            );
+            let mut packed: Vec<_> =
+                packed.iter().map(|tx| tx.hash()).collect();
+            packed.sort();
+            (pool, packed)
+        };
+
+        // All transactions fit in the block without the cap.
+        let (_, packed) = pack(100);
+        let mut expected = vec![big_tx.hash(), bob_tx.hash(), carol_tx.hash()];
+        expected.sort();
+        assert_eq!(packed, expected);
+
+        let (pool, packed) = pack(50);
+        let mut expected = vec![bob_tx.hash(), carol_tx.hash()];
+        expected.sort();
+        assert_eq!(packed, expected);
+        // The oversized transaction is kept ready.
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(big_tx.clone())
+        );
+    }
+
+    #[test]
+    fn test_cached_rlp_size() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+        let alice_tx = new_test_tx(&alice, 0, 10, 100);
+        let bob_tx = new_test_tx(&bob, 0, 20, 100);
+        insert_test_tx(&mut pool, alice_tx.clone(), 0, balance);
+        insert_test_tx(&mut pool, bob_tx.clone(), 0, balance);
+        assert!(pool.tx_rlp_sizes.is_empty());
+
+        let (packed, _) = pack_test_txs(&mut pool, 10, false);
+        assert_eq!(packed.len(), 2);
+        for tx in &[&alice_tx, &bob_tx] {
+            assert_eq!(
+                pool.tx_rlp_sizes.get(&tx.hash()),
+                Some(&tx.rlp_size())
+            );
+        }
+
+        // The cached size is used instead of encoding the transaction again,
+        // and it is kept over packing rounds.
+        pool.tx_rlp_sizes.insert(alice_tx.hash(), 1);
+        assert_eq!(pool.cached_rlp_size(&alice_tx), 1);
+        pack_test_txs(&mut pool, 10, false);
+        assert_eq!(pool.tx_rlp_sizes.get(&alice_tx.hash()), Some(&1));
+
+        // The size of a replaced transaction is dropped, and the new one is
+        // computed for itself.
+        let alice_tx2 = new_test_tx(&alice, 0, 20, 100);
+        insert_test_tx(&mut pool, alice_tx2.clone(), 0, balance);
+        assert!(!pool.tx_rlp_sizes.contains_key(&alice_tx.hash()));
+        assert_eq!(pool.cached_rlp_size(&alice_tx2), alice_tx2.rlp_size());
+    }
+
+    #[test]
+    fn test_pack_skips_stale_ready_transaction() {
+        let mut pool = new_test_pool(100);
+        let alice = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+        let alice_tx = new_test_tx(&alice, 0, 10, 100);
+        insert_test_tx(&mut pool, alice_tx.clone(), 0, balance);
+
+        // Simulate a drift: the ready pool holds a transaction that is not in
+        // the deferred pool.
+        let stale_tx = new_test_tx(&alice, 0, 20, 100);
+        pool.ready_account_pool.insert(stale_tx.clone());
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(stale_tx)
+        );
+
+        let (packed, _) = pack_test_txs(&mut pool, 10, false);
+        assert_eq!(packed, vec![alice_tx]);
+    }
+
+    #[test]
+    fn test_displacement_price() {
+        let mut pool = new_test_pool(3);
+        let balance = U256::from(1_000_000_000);
+        let senders: Vec<_> =
+            (0..3).map(|_| Random.generate().unwrap()).collect();
+        for (i, sender) in senders.iter().enumerate() {
+            assert_eq!(pool.displacement_price(), None);
+            insert_test_tx(
+                &mut pool,
+                new_test_tx(sender, 0, 10 * (i + 1), 100),
+                0,
+                balance,
+            );
+        }
+        assert!(pool.is_full());
+        assert_eq!(pool.displacement_price(), Some(10.into()));
+
+        // A new transaction with a lower gas price cannot get in.
+        let alice = Random.generate().unwrap();
+        assert!(matches!(
+            insert_test_tx(
+                &mut pool,
+                new_test_tx(&alice, 0, 10, 100),
+                0,
+                balance
+            ),
+            InsertResult::Failed(_)
+        ));
+        assert_eq!(
+            insert_test_tx(
+                &mut pool,
+                new_test_tx(&alice, 0, 11, 100),
+                0,
+                balance
+            ),
+            InsertResult::NewAdded
+        );
+        assert_eq!(pool.displacement_price(), Some(11.into()));
+
+        // Once a transaction is executed, it is collected first.
+        pool.recalculate_readiness_with_fixed_info(
+            &senders[2].address(),
+            1.into(),
+            balance,
+        );
+        pool.garbage_collector.insert(&senders[2].address(), 1, 0);
+        assert_eq!(pool.displacement_price(), Some(U256::zero()));
+    }
+
+    #[test]
+    fn test_auto_weight_scaling() {
+        let mut pool = new_test_pool(100);
+        let balance = U256::from(1_000_000_000_000_000u64);
+        let senders: Vec<_> =
+            (0..3).map(|_| Random.generate().unwrap()).collect();
+        for (i, sender) in senders.iter().enumerate() {
+            insert_test_tx(
+                &mut pool,
+                new_test_tx(sender, 0, 1_000_000 * (i + 1), 0),
+                0,
+                balance,
+            );
+        }
+        // Auto scaling is disabled by default.
+        pool.ready_account_pool.update_weight_scaling();
+        assert_eq!(pool.ready_account_pool.tx_weight_scaling, 1);
+
+        pool.set_auto_weight_scaling(true);
+        assert_eq!(pool.ready_account_pool.tx_weight_scaling, 2000);
+        assert_eq!(pool.ready_account_pool.treap.sum_weight(), 3000);
+
+        // The gas prices rise by a thousand times.
+        for (i, sender) in senders.iter().enumerate() {
+            insert_test_tx(
+                &mut pool,
+                new_test_tx(sender, 0, 1_000_000_000 * (i + 1), 0),
+                0,
+                balance,
+            );
+        }
+        pool.ready_account_pool.update_weight_scaling();
+        assert_eq!(pool.ready_account_pool.tx_weight_scaling, 2_000_000);
+        assert_eq!(pool.ready_account_pool.treap.sum_weight(), 3000);
+
+        // A transaction cheaper than the scaling still has a non-zero weight.
+        let alice = Random.generate().unwrap();
+        let cheap_tx = new_test_tx(&alice, 0, 1, 0);
+        insert_test_tx(&mut pool, cheap_tx.clone(), 0, balance);
+        assert_eq!(pool.ready_account_pool.weight(&cheap_tx), 1);
+
+        // The configured scaling is restored once auto scaling is disabled.
+        pool.set_auto_weight_scaling(false);
+        assert_eq!(pool.ready_account_pool.tx_weight_scaling, 1);
+        assert_eq!(pool.ready_account_pool.treap.sum_weight(), 6_000_000_001);
+    }
+
+    #[test]
+    fn test_weight_scaling_interval() {
+        let mut ready_pool = ReadyAccountPool::new(
+            1, /* tx_weight_scaling */
+            1, /* tx_weight_exp */
+            0, /* tx_weight_aging_factor */
+        );
+        let time_source = Arc::new(MockTimeSource::default());
+        ready_pool.time_source = time_source.clone();
+        ready_pool.auto_weight_scaling = true;
+        let senders: Vec<_> =
+            (0..3).map(|_| Random.generate().unwrap()).collect();
+        for (i, sender) in senders.iter().enumerate() {
+            ready_pool.insert(new_test_tx(sender, 0, 1_000_000 * (i + 1), 0));
+        }
+        time_source.advance(TX_WEIGHT_SCALING_INTERVAL);
+        ready_pool.refresh_weight_scaling();
+        assert_eq!(ready_pool.tx_weight_scaling, 2000);
+
+        // The gas prices rise, but the scaling is kept until the interval
+        // has passed.
+        for (i, sender) in senders.iter().enumerate() {
+            ready_pool
+                .insert(new_test_tx(sender, 0, 1_000_000_000 * (i + 1), 0));
+        }
+        time_source.advance(TX_WEIGHT_SCALING_INTERVAL - 1);
+        ready_pool.refresh_weight_scaling();
+        assert_eq!(ready_pool.tx_weight_scaling, 2000);
+        time_source.advance(1);
+        ready_pool.refresh_weight_scaling();
+        assert_eq!(ready_pool.tx_weight_scaling, 2_000_000);
+        assert_eq!(ready_pool.total_weight(), 3000);
+    }
+
+    #[test]
+    fn test_pack_transactions_with_rlp() {
+        let mut pool = new_test_pool(100);
+        let balance = U256::from(1_000_000_000);
+        for _ in 0..3 {
+            let sender = Random.generate().unwrap();
+            insert_test_tx(
+                &mut pool,
+                new_test_tx(&sender, 0, 10, 100),
+                0,
+                balance,
+            );
+        }
+
+        let (packed, rlp_bytes) = pack_test_txs(&mut pool, 10, true);
+        assert_eq!(packed.len(), 3);
+        let decoded: Vec<TransactionWithSignature> =
+            rlp::decode_list(&rlp_bytes.unwrap());
+        assert_eq!(
+            decoded.iter().map(|tx| tx.hash()).collect::<Vec<_>>(),
+            packed.iter().map(|tx| tx.hash()).collect::<Vec<_>>()
+        );
+
+        let (packed, rlp_bytes) = pack_test_txs(&mut pool, 10, false);
+        assert_eq!(packed.len(), 3);
+        assert_eq!(rlp_bytes, None);
+    }
+
+    #[test]
+    fn test_find_by_selector() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let contract = Address::random();
+        let selector = [0xa9, 0x05, 0x9c, 0xbb];
+        let new_tx = |nonce: usize, action: Action, data: Vec<u8>| {
+            Arc::new(
+                Transaction {
+                    nonce: U256::from(nonce),
+                    gas_price: U256::from(10),
+                    gas: U256::from(50000),
+                    action,
+                    value: U256::zero(),
+                    storage_limit: 0,
+                    epoch_height: 0,
+                    chain_id: 0,
+                    data,
+                }
+                .sign(alice.secret()),
+            )
+        };
+        let mut call_data = selector.to_vec();
+        call_data.extend_from_slice(&[0; 32]);
+        let txs = vec![
+            new_tx(0, Action::Call(contract), call_data.clone()),
+            new_tx(1, Action::Call(contract), selector.to_vec()),
+            // Another function, data shorter than a selector and no data.
+            new_tx(2, Action::Call(contract), vec![0x70, 0xa0, 0x82, 0x31]),
+            new_tx(3, Action::Call(contract), selector[..3].to_vec()),
+            new_tx(4, Action::Call(contract), Vec::new()),
+            // Another callee and a contract creation.
+            new_tx(5, Action::Call(Address::random()), call_data.clone()),
+            new_tx(6, Action::Create, call_data),
+        ];
+        for tx in &txs {
+            assert_eq!(
+                insert_test_tx(&mut pool, tx.clone(), 0, 1_000_000.into()),
+                InsertResult::NewAdded
+            );
+        }
+
+        let mut found: Vec<_> = pool
+            .find_by_selector(contract, selector, 10)
+            .iter()
+            .map(|tx| tx.hash())
+            .collect();
+        found.sort();
+        let mut expected = vec![txs[0].hash(), txs[1].hash()];
+        expected.sort();
+        assert_eq!(found, expected);
+
+        assert_eq!(pool.find_by_selector(contract, selector, 1).len(), 1);
+        assert!(pool.find_by_selector(contract, [0; 4], 10).is_empty());
+    }
+
+    #[test]
+    fn test_ready_callback() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let address = alice.address();
+        let became_ready = Arc::new(AtomicUsize::new(0));
+        let counter = became_ready.clone();
+        pool.set_ready_callback(move |addr| {
+            assert_eq!(*addr, address);
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        let balance = U256::from(1_000_000_000);
+
+        // Alice has no ready transaction before the gap at nonce 0 is filled.
+        insert_test_tx(&mut pool, new_test_tx(&alice, 1, 10, 100), 0, balance);
+        assert_eq!(became_ready.load(Ordering::SeqCst), 0);
+        insert_test_tx(&mut pool, new_test_tx(&alice, 0, 10, 100), 0, balance);
+        assert_eq!(became_ready.load(Ordering::SeqCst), 1);
+
+        // Alice stays ready, so the callback is not called again.
+        insert_test_tx(&mut pool, new_test_tx(&alice, 2, 10, 100), 0, balance);
+        pool.recalculate_readiness_with_local_info(&address);
+        assert_eq!(became_ready.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_removal_notifier() {
+        let mut pool = new_test_pool(2);
+        let (sender, receiver) = mpsc::channel();
+        pool.set_removal_notifier(sender);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let carol = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+
+        let alice_tx = new_test_tx(&alice, 0, 10, 100);
+        let bob_tx = new_test_tx(&bob, 0, 20, 100);
+        insert_test_tx(&mut pool, alice_tx.clone(), 0, balance);
+        insert_test_tx(&mut pool, bob_tx.clone(), 0, balance);
+        assert!(receiver.try_recv().is_err());
+
+        // Replace Bob's transaction with a higher gas price.
+        let bob_tx2 = new_test_tx(&bob, 0, 30, 100);
+        assert!(matches!(
+            insert_test_tx(&mut pool, bob_tx2.clone(), 0, balance),
+            InsertResult::Updated(_)
+        ));
+        assert_eq!(
+            receiver.try_recv(),
+            Ok((bob_tx.hash(), RemovalReason::Replaced))
+        );
+
+        // The pool is full, so Alice's transaction is garbage collected.
+        let carol_tx = new_test_tx(&carol, 0, 20, 100);
+        assert_eq!(
+            insert_test_tx(&mut pool, carol_tx.clone(), 0, balance),
+            InsertResult::NewAdded
+        );
+        assert_eq!(
+            receiver.try_recv(),
+            Ok((alice_tx.hash(), RemovalReason::GarbageCollected))
+        );
+        assert!(receiver.try_recv().is_err());
+
+        pool.clear();
+        let mut removed: Vec<_> = receiver.try_iter().collect();
+        removed.sort_by_key(|(tx_hash, _)| *tx_hash);
+        let mut expected = vec![
+            (bob_tx2.hash(), RemovalReason::Removed),
+            (carol_tx.hash(), RemovalReason::Removed),
+        ];
+        expected.sort_by_key(|(tx_hash, _)| *tx_hash);
+        assert_eq!(removed, expected);
+    }
+
+    #[test]
+    fn test_preview_eviction() {
+        let mut pool = new_test_pool(10);
+        let (sender, receiver) = mpsc::channel();
+        pool.set_removal_notifier(sender);
+        let balance = U256::from(1_000_000_000);
+        let senders: Vec<_> =
+            (0..4).map(|_| Random.generate().unwrap()).collect();
+        let txs: Vec<_> = senders[..3]
+            .iter()
+            .zip(&[20, 10, 30])
+            .map(|(sender, gas_price)| new_test_tx(sender, 0, *gas_price, 100))
+            .collect();
+        for tx in &txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+        let new_tx = new_test_tx(&senders[3], 0, 40, 100);
+        assert!(pool.preview_eviction(&new_tx).is_empty());
+
+        // Only one transaction fits in the memory once it shrinks, so the two
+        // cheapest ones are evicted.
+        pool.memory_capacity = Some(pool.total_tx_size() / 3 + 1);
+        let gc_before = pool.gc_debug_snapshot();
+        let preview = pool.preview_eviction(&new_tx);
+        assert_eq!(preview, vec![txs[1].hash(), txs[0].hash()]);
+        assert_eq!(pool.gc_debug_snapshot(), gc_before);
+        assert_eq!(pool.total_deferred(), 3);
+
+        // Replacing a transaction evicts nothing.
+        let replacement = new_test_tx(&senders[0], 0, 40, 100);
+        assert!(pool.preview_eviction(&replacement).is_empty());
+
+        assert_eq!(
+            insert_test_tx(&mut pool, new_tx.clone(), 0, balance),
+            InsertResult::NewAdded
+        );
+        let evicted: Vec<_> = receiver
+            .try_iter()
+            .map(|(tx_hash, reason)| {
+                assert_eq!(reason, RemovalReason::GarbageCollected);
+                tx_hash
+            })
+            .collect();
+        assert_eq!(evicted, preview);
+    }
+
+    #[test]
+    fn test_insert_transactions_batch_promotions() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+
+        let alice_txs: Vec<_> = (0..3)
+            .map(|nonce| new_test_tx(&alice, nonce, 10, 0))
+            .collect();
+        let bob_txs: Vec<_> =
+            (0..2).map(|nonce| new_test_tx(&bob, nonce, 10, 0)).collect();
+
+        // Bob's first transaction is already ready before the batch.
+        insert_test_tx(&mut pool, bob_txs[0].clone(), 0, balance);
+
+        // Insert in reverse nonce order, so that nothing is ready until the
+        // last insertion of each sender fills the nonce gap.
+        let batch = alice_txs
+            .iter()
+            .rev()
+            .chain(bob_txs.iter().skip(1))
+            .cloned()
+            .collect();
+        let (results, promotions) =
+            pool.insert_batch_with(batch, |pool, tx| {
+                insert_test_tx(pool, tx, 0, balance)
+            });
+        assert_eq!(results.len(), 4);
+        assert!(results.iter().all(|r| *r == InsertResult::NewAdded));
+
+        assert_eq!(promotions.len(), 2);
+        assert_eq!(
+            promotions[&alice.address()],
+            alice_txs.iter().map(|tx| tx.hash()).collect::<Vec<_>>()
+        );
+        assert_eq!(promotions[&bob.address()], vec![bob_txs[1].hash()]);
+    }
+
+    #[test]
+    fn test_insert_transactions_batch_recalculates_once_per_sender() {
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let (_storage_manager, account_cache) = new_test_account_cache(
+            &[(alice.address(), 0), (bob.address(), 0)],
+            U256::from(10_000_000_000u64),
+        );
+        let txs: Vec<_> = (0..3)
+            .map(|nonce| new_test_tx(&alice, nonce, 10, 0))
+            .chain((0..2).map(|nonce| new_test_tx(&bob, nonce, 10, 0)))
+            // A transaction too far in the future is rejected.
+            .chain(std::iter::once(new_test_tx(
+                &bob,
+                FURTHEST_FUTURE_TRANSACTION_NONCE_OFFSET as usize,
+                10,
+                0,
+            )))
+            .collect();
+        let recalculate_count = || RECALCULATE_COUNT.with(|count| count.get());
+
+        let mut serial_pool = new_test_pool(10);
+        let start = recalculate_count();
+        let serial_results: Vec<_> = txs
+            .iter()
+            .map(|tx| {
+                serial_pool.insert_transaction_with_readiness_check(
+                    &account_cache,
+                    tx.clone(),
+                    false, /* packed */
+                    false, /* force */
+                    false, /* is_local */
+                )
+            })
+            .collect();
+        assert_eq!(recalculate_count() - start, 5);
+
+        let mut batch_pool = new_test_pool(10);
+        let start = recalculate_count();
+        let (batch_results, promotions) = batch_pool.insert_transactions_batch(
+            &account_cache,
+            txs.clone(),
+            false, /* packed */
+            false, /* force */
+        );
+        assert_eq!(recalculate_count() - start, 2);
+
+        assert_eq!(batch_results, serial_results);
+        assert!(batch_results[5].is_err());
+        for sender in &[&alice, &bob] {
+            assert_eq!(
+                batch_pool.ready_frontier(&sender.address()),
+                serial_pool.ready_frontier(&sender.address())
+            );
+            assert_eq!(
+                batch_pool.ready_account_pool.get(&sender.address()),
+                serial_pool.ready_account_pool.get(&sender.address())
+            );
+        }
+        assert_eq!(promotions[&alice.address()].len(), 3);
+        assert_eq!(promotions[&bob.address()].len(), 2);
+    }
+
+    #[test]
+    fn test_estimate_shrink_savings() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+
+        let alice_txs: Vec<_> = (0..3)
+            .map(|nonce| new_test_tx(&alice, nonce, 10, 0))
+            .collect();
+        for tx in &alice_txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+        let bob_txs: Vec<_> =
+            (0..3).map(|nonce| new_test_tx(&bob, nonce, 20, 0)).collect();
+        for tx in &bob_txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+
+        assert_eq!(pool.estimate_shrink_savings(6), 0);
+        assert_eq!(pool.estimate_shrink_savings(10), 0);
+
+        // The cheaper sender is evicted first, from its lowest nonce.
+        let mut ops = new_malloc_size_ops();
+        let expected =
+            alice_txs[0].size_of(&mut ops) + alice_txs[1].size_of(&mut ops);
+        assert_eq!(pool.estimate_shrink_savings(4), expected);
+
+        // Nothing is actually evicted.
+        assert_eq!(pool.total_deferred(), 6);
+        assert_eq!(pool.estimate_shrink_savings(4), expected);
+
+        // The memory budget is kept as well.
+        pool.memory_capacity = Some(pool.total_tx_size() - 1);
+        assert_eq!(
+            pool.estimate_shrink_savings(10),
+            alice_txs[0].size_of(&mut ops)
+        );
+        pool.memory_capacity = None;
+
+        // Local transactions are spared as by `collect_garbage`.
+        pool.local_transactions.insert(alice_txs[0].hash());
+        let expected =
+            bob_txs[0].size_of(&mut ops) + bob_txs[1].size_of(&mut ops);
+        assert_eq!(pool.estimate_shrink_savings(4), expected);
+        // Only local transactions would be left to evict.
+        assert_eq!(
+            pool.estimate_shrink_savings(2),
+            expected + bob_txs[2].size_of(&mut ops)
+        );
+    }
+
+    #[test]
+    fn test_validate_batch() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+
+        let packed_tx = new_test_tx(&bob, 0, 10, 0);
+        assert_eq!(
+            pool.insert_transaction_without_readiness_check(
+                packed_tx.clone(),
+                true,  /* packed */
+                false, /* force */
+                Some((0.into(), U256::from(10_000_000_000u64))),
+                (U256::zero(), 0),
+            ),
+            InsertResult::NewAdded
+        );
+
+        let alice_tx = new_test_tx(&alice, 0, 10, 0);
+        let alice_dup = new_test_tx(&alice, 0, 20, 0);
+        let bob_tx = new_test_tx(&bob, 0, 20, 0);
+        let bob_next = new_test_tx(&bob, 1, 20, 0);
+        let batch =
+            vec![alice_tx.clone(), bob_tx.clone(), alice_dup.clone(), bob_next];
+
+        let expected = vec![
+            BatchConflict::AlreadyPacked {
+                tx_hash: bob_tx.hash(),
+                packed_tx_hash: packed_tx.hash(),
+            },
+            BatchConflict::Duplicate {
+                first: alice_tx.hash(),
+                second: alice_dup.hash(),
+            },
+        ];
+        assert_eq!(pool.validate_batch(&batch), expected);
+        // Nothing is inserted.
+        assert_eq!(pool.total_deferred(), 1);
+        assert!(pool.validate_batch(&[alice_tx]).is_empty());
+    }
+
+    #[test]
+    fn test_forced_insert_overflow_margin() {
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            forced_insert_overflow_margin: 1,
+            ..test_config(2)
+        });
+        let balance = U256::from(10_000_000_000u64);
+        for _ in 0..2 {
+            let sender = Random.generate().unwrap();
+            let tx = new_test_tx(&sender, 0, 10, 0);
+            insert_test_tx(&mut pool, tx, 0, balance);
+        }
+        assert!(pool.is_full());
+
+        // The new transactions are too cheap to evict anything.
+        let mut insert = |force| {
+            let sender = Random.generate().unwrap();
+            pool.insert_transaction_without_readiness_check(
+                new_test_tx(&sender, 0, 1, 0),
+                false, /* packed */
+                force,
+                Some((0.into(), balance)),
+                (U256::zero(), 0),
+            )
+        };
+        assert!(matches!(insert(false), InsertResult::Failed(_)));
+        assert_eq!(insert(true), InsertResult::NewAdded);
+        assert!(matches!(insert(true), InsertResult::Failed(_)));
+        assert_eq!(pool.total_deferred(), 3);
+        // No quota is left in the pool over its capacity.
+        assert_eq!(pool.remaining_quota(), 0);
+        assert_eq!(pool.stats().remaining_quota, 0);
+    }
+
+    #[test]
+    fn test_all_ordered() {
+        let senders: Vec<_> =
+            (0..3).map(|_| Random.generate().unwrap()).collect();
+        let balance = U256::from(10_000_000_000u64);
+        let txs: Vec<_> = senders
+            .iter()
+            .flat_map(|sender| {
+                (0..3).map(move |nonce| new_test_tx(sender, nonce, 10, 0))
+            })
+            .collect();
+
+        let mut expected = txs.clone();
+        expected.sort_by_key(|tx| (tx.sender(), tx.nonce()));
+        let expected: Vec<_> = expected.iter().map(|tx| tx.hash()).collect();
+
+        // The order does not depend on the insertion order.
+        let reversed: Vec<_> = txs.iter().rev().cloned().collect();
+        for insert_order in vec![txs, reversed] {
+            let mut pool = new_test_pool(10);
+            for tx in insert_order {
+                insert_test_tx(&mut pool, tx, 0, balance);
+            }
+            let ordered: Vec<_> =
+                pool.all_ordered().iter().map(|tx| tx.hash()).collect();
+            assert_eq!(ordered, expected);
+        }
+    }
+
+    #[test]
+    fn test_quarantine_and_release() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+        let txs: Vec<_> = (0..3)
+            .map(|nonce| new_test_tx(&alice, nonce, 10, 0))
+            .collect();
+        for tx in &txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+        let ready_hash = |pool: &TransactionPoolInner| {
+            pool.ready_account_pool
+                .get(&alice.address())
+                .map(|tx| tx.hash())
+        };
+        assert_eq!(ready_hash(&pool), Some(txs[0].hash()));
+
+        assert!(!pool.quarantine(new_test_tx(&alice, 5, 10, 0).hash()));
+        assert!(pool.quarantine(txs[0].hash()));
+        assert_eq!(ready_hash(&pool), None);
+        assert!(pack_test_txs(&mut pool, 10, false).0.is_empty());
+        assert_eq!(pool.total_deferred(), 3);
+
+        assert!(pool.release(txs[0].hash()));
+        assert!(!pool.release(txs[0].hash()));
+        assert_eq!(ready_hash(&pool), Some(txs[0].hash()));
+    }
+
+    #[test]
+    fn test_quarantine_suppresses_dependents() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+        let txs: Vec<_> = (0..3)
+            .map(|nonce| new_test_tx(&alice, nonce, 10, 0))
+            .collect();
+        for tx in &txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+
+        // The transaction before the quarantined one can still be packed,
+        // but neither the quarantined one nor its dependents.
+        assert!(pool.quarantine(txs[1].hash()));
+        let (packed, _) = pack_test_txs(&mut pool, 10, false);
+        assert_eq!(
+            packed.iter().map(|tx| tx.hash()).collect::<Vec<_>>(),
+            vec![txs[0].hash()]
+        );
+        assert!(pool.ready_account_pool.get(&alice.address()).is_none());
+
+        assert!(pool.release(txs[1].hash()));
+        let (packed, _) = pack_test_txs(&mut pool, 10, false);
+        assert_eq!(
+            packed.iter().map(|tx| tx.hash()).collect::<Vec<_>>(),
+            vec![txs[1].hash(), txs[2].hash()]
+        );
+    }
+
+    #[test]
+    fn test_min_balance_reserve() {
+        let alice = Random.generate().unwrap();
+        // The transaction costs 50000 * 10.
+        let tx = new_test_tx(&alice, 0, 10, 0);
+        let balance = U256::from(600_000);
+        let new_pool = |min_balance_reserve: u64| {
+            let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+                min_balance_reserve: U256::from(min_balance_reserve),
+                ..test_config(10)
+            });
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+            pool
+        };
+
+        let pool = new_pool(200_000);
+        assert!(pool.ready_account_pool.get(&alice.address()).is_none());
+
+        let pool = new_pool(100_000);
+        assert_eq!(
+            pool.ready_account_pool
+                .get(&alice.address())
+                .map(|tx| tx.hash()),
+            Some(tx.hash())
+        );
+    }
+
+    #[test]
+    fn test_set_account_state_and_recalculate() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let tx = new_test_tx(&alice, 1, 10, 0);
+        let balance = U256::from(10_000_000_000u64);
+
+        // Nonce 0 is not executed yet, so the transaction is not ready.
+        insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        assert!(pool.ready_account_pool.get(&alice.address()).is_none());
+
+        pool.set_account_state_and_recalculate(
+            &alice.address(),
+            1.into(),
+            balance,
+        );
+        assert_eq!(
+            pool.get_local_nonce_and_balance(&alice.address()),
+            Some((1.into(), balance))
+        );
+        assert_eq!(
+            pool.ready_account_pool
+                .get(&alice.address())
+                .map(|tx| tx.hash()),
+            Some(tx.hash())
+        );
+    }
+
+    #[test]
+    fn test_pack_transactions_by_price() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let carol = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+
+        let alice_txs =
+            vec![new_test_tx(&alice, 0, 30, 0), new_test_tx(&alice, 1, 50, 0)];
+        let bob_tx = new_test_tx(&bob, 0, 10, 0);
+        let carol_tx = new_test_tx(&carol, 0, 20, 0);
+        for tx in alice_txs.iter().chain(&[bob_tx.clone(), carol_tx.clone()]) {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+
+        // The second transaction of alice becomes ready after the first one
+        // is packed, and it is taken before the cheaper ones.
+        let expected = vec![
+            alice_txs[0].hash(),
+            alice_txs[1].hash(),
+            carol_tx.hash(),
+            bob_tx.hash(),
+        ];
+        for _ in 0..2 {
+            let (packed, _) = pack_test_txs_in_order(
+                &mut pool,
+                10,
+                U256::max_value(),
+                PackingOrder::GasPrice,
+                false,
+            );
+            let hashes: Vec<_> = packed.iter().map(|tx| tx.hash()).collect();
+            assert_eq!(hashes, expected);
+        }
+        assert_eq!(pool.total_unpacked(), 4);
+
+        let (packed, _) = pack_test_txs_in_order(
+            &mut pool,
+            2,
+            U256::max_value(),
+            PackingOrder::GasPrice,
+            false,
+        );
+        assert_eq!(packed.len(), 2);
+        assert_eq!(packed[1].hash(), alice_txs[1].hash());
+    }
+
+    #[test]
+    fn test_get_nonce_gaps() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let carol = Random.generate().unwrap();
+        fn insert_nonces(
+            pool: &mut TransactionPoolInner, sender: &KeyPair, nonces: &[usize],
+        ) {
+            let balance = U256::from(10_000_000_000u64);
+            for nonce in nonces {
+                let tx = new_test_tx(sender, *nonce, 10, 0);
+                insert_test_tx(pool, tx, 5 /* state_nonce */, balance);
+            }
+        }
+
+        insert_nonces(&mut pool, &alice, &[5, 6, 7]);
+        assert!(pool.get_nonce_gaps(&alice.address(), 5.into()).is_empty());
+
+        insert_nonces(&mut pool, &bob, &[5, 6, 8]);
+        assert_eq!(
+            pool.get_nonce_gaps(&bob.address(), 5.into()),
+            vec![U256::from(7)]
+        );
+
+        insert_nonces(&mut pool, &carol, &[6, 9, 10, 12]);
+        assert_eq!(
+            pool.get_nonce_gaps(&carol.address(), 5.into()),
+            vec![5, 7, 8, 11]
+                .into_iter()
+                .map(U256::from)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            pool.get_nonce_gaps(&carol.address(), 9.into()),
+            vec![U256::from(11)]
+        );
+
+        let eva = Random.generate().unwrap();
+        assert!(pool.get_nonce_gaps(&eva.address(), 0.into()).is_empty());
+    }
+
+    #[test]
+    fn test_nonce_reachability() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+        for nonce in &[5, 6, 8] {
+            let tx = new_test_tx(&alice, *nonce, 10, 0);
+            insert_test_tx(&mut pool, tx, 5 /* state_nonce */, balance);
+        }
+        let total_deferred = pool.total_deferred();
+        let reachability = |nonce: u64| {
+            pool.nonce_reachability(&alice.address(), nonce.into(), 5.into())
+        };
+
+        // Replacing a transaction or appending right after them is ready.
+        assert_eq!(reachability(6), NonceReachability::Reachable);
+        assert_eq!(reachability(7), NonceReachability::Reachable);
+        // Nonce 7 is missing, so anything after it waits.
+        assert_eq!(reachability(9), NonceReachability::BehindGap(7.into()));
+        assert_eq!(reachability(4), NonceReachability::Stale);
+
+        let bob = Random.generate().unwrap();
+        let reachability = |nonce: u64| {
+            pool.nonce_reachability(&bob.address(), nonce.into(), 3.into())
+        };
+        assert_eq!(reachability(3), NonceReachability::Reachable);
+        assert_eq!(reachability(4), NonceReachability::BehindGap(3.into()));
+
+        assert_eq!(pool.total_deferred(), total_deferred);
+    }
+
+    #[test]
+    fn test_pack_transactions_with_seed() {
+        let balance = U256::from(10_000_000_000u64);
+        let txs: Vec<_> = (1..=10)
+            .map(|gas_price| {
+                let sender = Random.generate().unwrap();
+                new_test_tx(&sender, 0, gas_price, 0)
+            })
+            .collect();
+
+        let pack_with_seed = |seed| {
+            let mut pool = new_test_pool(10);
+            pool.set_packing_seed(Some(seed));
+            for tx in &txs {
+                insert_test_tx(&mut pool, tx.clone(), 0, balance);
+            }
+            let (packed, _) = pack_test_txs(&mut pool, 10, false);
+            packed.iter().map(|tx| tx.hash()).collect::<Vec<_>>()
+        };
+        let packed = pack_with_seed(42);
+        assert_eq!(packed.len(), 10);
+        assert_eq!(pack_with_seed(42), packed);
+    }
+
+    #[test]
+    fn test_pack_transactions_by_price_gas_limit() {
+        let mut pool = new_test_pool(10);
+        let balance = U256::from(10_000_000_000u64);
+        for gas_price in 1..=5 {
+            let sender = Random.generate().unwrap();
+            let tx = new_test_tx(&sender, 0, gas_price, 0);
+            insert_test_tx(&mut pool, tx, 0, balance);
+        }
+
+        // Each transaction has a gas limit of 50000.
+        let block_gas_limit = U256::from(120_000);
+        let (packed, _) = pack_test_txs_in_order(
+            &mut pool,
+            10,
+            block_gas_limit,
+            PackingOrder::GasPrice,
+            false,
+        );
+        let gas_prices: Vec<_> = packed.iter().map(|tx| tx.gas_price).collect();
+        assert_eq!(gas_prices, vec![U256::from(5), U256::from(4)]);
+        let total_gas = packed
+            .iter()
+            .fold(U256::zero(), |total, tx| total + *tx.gas_limit());
+        assert!(total_gas <= block_gas_limit);
+        assert_eq!(pool.total_ready_accounts(), 5);
+    }
+
+    #[test]
+    fn test_max_txs_per_sender() {
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            max_txs_per_sender: 3,
+            ..test_config(10)
+        });
+        let alice = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+        for nonce in 0..3 {
+            let tx = new_test_tx(&alice, nonce, 10, 0);
+            assert_eq!(
+                insert_test_tx(&mut pool, tx, 0, balance),
+                InsertResult::NewAdded
+            );
+        }
+
+        let tx = new_test_tx(&alice, 3, 10, 0);
+        let result = insert_test_tx(&mut pool, tx, 0, balance);
+        assert!(matches!(result, InsertResult::Failed(_)));
+        assert_eq!(pool.total_deferred(), 3);
+        assert_eq!(pool.total_unpacked(), 3);
+
+        // A replacement does not add a transaction, so it is still allowed.
+        let replacement = new_test_tx(&alice, 1, 20, 0);
+        assert!(matches!(
+            insert_test_tx(&mut pool, replacement.clone(), 0, balance),
+            InsertResult::Updated(_)
+        ));
+        assert_eq!(pool.total_deferred(), 3);
+        assert_eq!(pool.total_unpacked(), 3);
+        assert_eq!(
+            pool.get_by_address2nonce(alice.address(), 1.into())
+                .map(|tx| tx.hash()),
+            Some(replacement.hash())
+        );
+
+        // Other senders are not affected.
+        let bob = Random.generate().unwrap();
+        assert_eq!(
+            insert_test_tx(&mut pool, new_test_tx(&bob, 0, 10, 0), 0, balance),
+            InsertResult::NewAdded
+        );
+    }
+
+    #[test]
+    fn test_content_paginated_boundaries() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+        let txs: Vec<_> = (0..5)
+            .map(|nonce| new_test_tx(&alice, nonce, 10, 0))
+            .collect();
+        for tx in &txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+        let hashes = |txs: &[Arc<SignedTransaction>]| -> Vec<_> {
+            txs.iter().map(|tx| tx.hash()).collect()
+        };
+        let page = |offset, limit| {
+            pool.content_paginated(
+                Some(alice.address()),
+                offset,
+                limit,
+                ContentSortOrder::NonceAscending,
+            )
+        };
+
+        let first = page(0, 2);
+        assert_eq!(first.total_deferred, 5);
+        assert_eq!(hashes(&first.deferred_txs), hashes(&txs[0..2]));
+        assert_eq!(first.total_ready, 1);
+        assert_eq!(hashes(&first.ready_txs), hashes(&txs[0..1]));
+
+        let last = page(4, 2);
+        assert_eq!(hashes(&last.deferred_txs), hashes(&txs[4..5]));
+        assert!(last.ready_txs.is_empty());
+
+        let beyond = page(5, 2);
+        assert!(beyond.deferred_txs.is_empty());
+        assert_eq!(beyond.total_deferred, 5);
+
+        let empty = page(1, 0);
+        assert!(empty.deferred_txs.is_empty());
+        assert_eq!(empty.total_deferred, 5);
+
+        let all = page(0, usize::max_value());
+        assert_eq!(hashes(&all.deferred_txs), hashes(&txs));
+
+        let bob = Random.generate().unwrap();
+        let other = pool.content_paginated(
+            Some(bob.address()),
+            0,
+            10,
+            ContentSortOrder::NonceAscending,
+        );
+        assert!(other.deferred_txs.is_empty());
+        assert_eq!(other.total_deferred, 0);
+    }
+
+    #[test]
+    fn test_content_paginated_sort_orders() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+        let txs = vec![
+            new_test_tx(&alice, 0, 20, 0),
+            new_test_tx(&alice, 1, 40, 0),
+            new_test_tx(&bob, 0, 30, 0),
+            new_test_tx(&bob, 1, 10, 0),
+        ];
+        for tx in &txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+
+        let mut by_nonce = txs.clone();
+        by_nonce.sort_by_key(|tx| (tx.sender(), tx.nonce()));
+        let page = pool.content_paginated(
+            None,
+            1,
+            2,
+            ContentSortOrder::NonceAscending,
+        );
+        assert_eq!(
+            page.deferred_txs.iter().map(|tx| tx.hash()).collect::<Vec<_>>(),
+            by_nonce[1..3].iter().map(|tx| tx.hash()).collect::<Vec<_>>()
+        );
+
+        let page = pool.content_paginated(
+            None,
+            0,
+            3,
+            ContentSortOrder::GasPriceDescending,
+        );
+        assert_eq!(page.total_deferred, 4);
+        assert_eq!(
+            page.deferred_txs
+                .iter()
+                .map(|tx| tx.gas_price)
+                .collect::<Vec<_>>(),
+            vec![U256::from(40), U256::from(30), U256::from(20)]
+        );
+        // The ready transactions are the first one of each sender.
+        assert_eq!(page.total_ready, 2);
+        assert_eq!(
+            page.ready_txs.iter().map(|tx| tx.gas_price).collect::<Vec<_>>(),
+            vec![U256::from(30), U256::from(20)]
+        );
+    }
+
+    #[test]
+    fn test_expire_transactions() {
+        let mut pool = new_test_pool(10);
+        let (sender, receiver) = mpsc::channel();
+        pool.set_removal_notifier(sender);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+
+        let stale_tx = new_test_tx(&alice, 0, 10, 100);
+        let later_tx = new_test_tx(&alice, 1, 10, 100);
+        let fresh_tx = new_test_tx(&bob, 0, 10, 100);
+        insert_test_tx(&mut pool, stale_tx.clone(), 0, balance);
+        insert_test_tx(&mut pool, later_tx.clone(), 0, balance);
+        insert_test_tx(&mut pool, fresh_tx.clone(), 0, balance);
+        // Use a manual clock instead of the insertion time.
+        let mut now = 1000;
+        pool.tx_insertion_timestamps.insert(stale_tx.hash(), now - 100);
+        pool.tx_insertion_timestamps.insert(later_tx.hash(), now);
+        pool.tx_insertion_timestamps.insert(fresh_tx.hash(), now);
+        assert!(pool.expire_transactions_at(now, 60).is_empty());
+
+        now += 30;
+        assert_eq!(pool.expire_transactions_at(now, 60), vec![stale_tx.hash()]);
+        assert_eq!(
+            receiver.try_recv(),
+            Ok((stale_tx.hash(), RemovalReason::Expired))
+        );
+        assert!(pool.get(&stale_tx.hash()).is_none());
+        assert_eq!(pool.total_deferred(), 2);
+        assert_eq!(pool.total_unpacked(), 2);
+        // Alice's remaining transaction waits for the expired nonce.
+        assert!(pool.ready_account_pool.get(&alice.address()).is_none());
+        assert_eq!(
+            pool.ready_account_pool.get(&bob.address()).unwrap().hash(),
+            fresh_tx.hash()
+        );
+
+        // Packed transactions never expire.
+        pack_test_txs(&mut pool, 1, false);
+        now += 100;
+        assert_eq!(pool.expire_transactions_at(now, 60), vec![later_tx.hash()]);
+        assert!(!pool.deferred_pool.contain_address(&alice.address()));
+        assert!(pool.get_local_nonce_and_balance(&alice.address()).is_none());
+        assert!(pool.get(&fresh_tx.hash()).is_some());
+    }
+
+    #[test]
+    fn test_memory_capacity() {
+        let new_large_tx = |sender: &KeyPair, gas_price: usize| {
+            Arc::new(
+                Transaction {
+                    nonce: U256::zero(),
+                    gas_price: U256::from(gas_price),
+                    gas: U256::from(50000),
+                    action: Action::Call(Address::random()),
+                    value: U256::zero(),
+                    storage_limit: 0,
+                    epoch_height: 0,
+                    chain_id: 0,
+                    data: vec![0; 10_000],
+                }
+                .sign(sender.secret()),
+            )
+        };
+        let balance = U256::from(1_000_000_000);
+        let senders: Vec<_> =
+            (0..4).map(|_| Random.generate().unwrap()).collect();
+        let txs: Vec<_> = senders
+            .iter()
+            .enumerate()
+            .map(|(i, sender)| new_large_tx(sender, 10 * (i + 1)))
+            .collect();
+        let tx_size = txs[0].size_of(&mut new_malloc_size_ops());
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            memory_capacity: Some(2 * tx_size + 1),
+            ..test_config(10)
+        });
+
+        for tx in &txs[..3] {
+            assert_eq!(
+                insert_test_tx(&mut pool, tx.clone(), 0, balance),
+                InsertResult::NewAdded
+            );
+        }
+        assert_eq!(pool.total_tx_size(), 3 * tx_size);
+        // Far below the count limit, but over the memory budget.
+        assert!(pool.is_full());
+        assert_eq!(pool.remaining_quota(), 0);
+
+        // The budget makes room for the new transaction by garbage collection
+        // of the cheapest one.
+        assert_eq!(
+            insert_test_tx(&mut pool, txs[3].clone(), 0, balance),
+            InsertResult::NewAdded
+        );
+        assert_eq!(pool.total_deferred(), 3);
+        assert!(pool.get(&txs[0].hash()).is_none());
+        assert_eq!(pool.total_tx_size(), 3 * tx_size);
+
+        pool.clear();
+        assert_eq!(pool.total_tx_size(), 0);
+
+        // Without the budget only the count limit applies.
+        let mut pool = new_test_pool(10);
+        for tx in &txs {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+        assert_eq!(pool.total_tx_size(), 4 * tx_size);
+        assert!(!pool.is_full());
+    }
+
+    #[test]
+    fn test_get_all_pending() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let carol = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+
+        // Alice's nonce 0 is missing.
+        let future_tx = new_test_tx(&alice, 1, 10, 100);
+        insert_test_tx(&mut pool, future_tx.clone(), 0, balance);
+        // Bob can not afford the gas.
+        let poor_tx = new_test_tx(&bob, 0, 10, 100);
+        insert_test_tx(&mut pool, poor_tx.clone(), 0, U256::from(1000));
+        // Only Carol's second transaction is pending.
+        insert_test_tx(&mut pool, new_test_tx(&carol, 0, 10, 100), 0, balance);
+        let carol_tx = new_test_tx(&carol, 2, 10, 100);
+        insert_test_tx(&mut pool, carol_tx.clone(), 0, balance);
+
+        let future =
+            pool.get_all_pending(Some(PendingReason::FutureNonce), 10);
+        let mut future: Vec<_> = future
+            .into_iter()
+            .map(|(address, tx, _)| (address, tx.hash()))
+            .collect();
+        future.sort();
+        let mut expected = vec![
+            (alice.address(), future_tx.hash()),
+            (carol.address(), carol_tx.hash()),
+        ];
+        expected.sort();
+        assert_eq!(future, expected);
+
+        let poor = pool.get_all_pending(Some(PendingReason::NotEnoughCash), 10);
+        assert_eq!(poor.len(), 1);
+        assert_eq!(poor[0].0, bob.address());
+        assert_eq!(poor[0].1.hash(), poor_tx.hash());
+        assert_eq!(poor[0].2, PendingReason::NotEnoughCash);
+
+        assert_eq!(pool.get_all_pending(None, 10).len(), 3);
+        assert_eq!(pool.get_all_pending(None, 2).len(), 2);
+        assert!(pool.get_all_pending(None, 0).is_empty());
+    }
+
+    #[test]
+    fn test_furthest_future_nonce_offset() {
+        let pool = TransactionPoolInner::new(&TxPoolConfig {
+            furthest_future_nonce_offset: 5,
+            ..test_config(10)
+        });
+        let alice = Random.generate().unwrap();
+        let state_nonce = U256::from(3);
+
+        let tx = new_test_tx(&alice, 3 + 4, 10, 0);
+        assert!(pool.check_nonce_range(&tx, state_nonce, false).is_ok());
+        let tx = new_test_tx(&alice, 3 + 5, 10, 0);
+        let err = pool.check_nonce_range(&tx, state_nonce, false).unwrap_err();
+        assert!(err.contains("too distant future"));
+        assert!(err.contains("less than 8"));
+        // Stale nonces are still rejected unless packed.
+        let tx = new_test_tx(&alice, 2, 10, 0);
+        assert!(pool.check_nonce_range(&tx, state_nonce, false).is_err());
+        assert!(pool.check_nonce_range(&tx, state_nonce, true).is_ok());
+    }
+
+    #[test]
+    fn test_local_reserved_slots() {
+        let senders: Vec<_> =
+            (0..5).map(|_| Random.generate().unwrap()).collect();
+        let accounts: Vec<_> =
+            senders.iter().map(|sender| (sender.address(), 0)).collect();
+        let (_storage_manager, account_cache) =
+            new_test_account_cache(&accounts, U256::from(10_000_000_000u64));
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            local_reserved_slots: 1,
+            ..test_config(4)
+        });
+        let mut insert = |sender: &KeyPair, is_local| {
+            pool.insert_transaction_with_readiness_check(
+                &account_cache,
+                new_test_tx(sender, 0, 10, 0),
+                false, /* packed */
+                false, /* force */
+                is_local,
+            )
+        };
+
+        for sender in &senders[..3] {
+            insert(sender, false).unwrap();
+        }
+        // The last slot is reserved, and no remote transaction is cheaper.
+        let err = insert(&senders[3], false).unwrap_err();
+        assert!(err.contains("Transaction Pool is full"), "{}", err);
+        insert(&senders[3], true).unwrap();
+        // Local transactions can not exceed the capacity either.
+        assert!(insert(&senders[4], true).is_err());
+        assert_eq!(pool.total_deferred(), 4);
+    }
+
+    #[test]
+    fn test_remote_insert_with_occupied_reserved_slots() {
+        let senders: Vec<_> =
+            (0..5).map(|_| Random.generate().unwrap()).collect();
+        let accounts: Vec<_> =
+            senders.iter().map(|sender| (sender.address(), 0)).collect();
+        let (_storage_manager, account_cache) =
+            new_test_account_cache(&accounts, U256::from(10_000_000_000u64));
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            local_reserved_slots: 1,
+            ..test_config(4)
+        });
+        let txs: Vec<_> = senders
+            .iter()
+            .zip(&[10, 10, 20, 30, 40])
+            .map(|(sender, gas_price)| new_test_tx(sender, 0, *gas_price, 0))
+            .collect();
+        let mut insert = |tx: &Arc<SignedTransaction>, is_local| {
+            pool.insert_transaction_with_readiness_check(
+                &account_cache,
+                tx.clone(),
+                false, /* packed */
+                false, /* force */
+                is_local,
+            )
+        };
+
+        // The local transaction takes the reserved slot.
+        insert(&txs[0], true).unwrap();
+        for tx in &txs[1..4] {
+            insert(tx, false).unwrap();
+        }
+        // Only the cheapest remote transaction makes room for a new one.
+        insert(&txs[4], false).unwrap();
+        assert_eq!(pool.total_deferred(), 4);
+        assert!(pool.get(&txs[0].hash()).is_some());
+        assert!(pool.get(&txs[1].hash()).is_none());
+        assert!(pool.get(&txs[2].hash()).is_some());
+        assert_eq!(pool.gc_stats().evicted, 1);
+    }
+
+    #[test]
+    fn test_local_transactions_spared_by_gc() {
+        let senders: Vec<_> =
+            (0..4).map(|_| Random.generate().unwrap()).collect();
+        let accounts: Vec<_> =
+            senders.iter().map(|sender| (sender.address(), 0)).collect();
+        let (_storage_manager, account_cache) =
+            new_test_account_cache(&accounts, U256::from(10_000_000_000u64));
+        let local_tx = new_test_tx(&senders[0], 0, 1, 0);
+        let remote_txs: Vec<_> = senders[1..]
+            .iter()
+            .zip(&[10, 20, 30])
+            .map(|(sender, gas_price)| new_test_tx(sender, 0, *gas_price, 0))
+            .collect();
+        let insert = |pool: &mut TransactionPoolInner,
+                      tx: &Arc<SignedTransaction>,
+                      force: bool,
+                      is_local: bool| {
+            pool.insert_transaction_with_readiness_check(
+                &account_cache,
+                tx.clone(),
+                false, /* packed */
+                force,
+                is_local,
+            )
+        };
+
+        // The cheapest transaction is evicted if it's not local.
+        for is_local in &[false, true] {
+            let mut pool = new_test_pool(2);
+            insert(&mut pool, &local_tx, false, *is_local).unwrap();
+            insert(&mut pool, &remote_txs[0], false, false).unwrap();
+            assert!(pool.is_full());
+            insert(&mut pool, &remote_txs[1], false, false).unwrap();
+            assert_eq!(pool.get(&local_tx.hash()).is_some(), *is_local);
+            assert_eq!(pool.get(&remote_txs[0].hash()).is_none(), *is_local);
+            assert_eq!(pool.gc_stats().spared_local, *is_local as usize);
+        }
+
+        // Once forced insertions push the pool over its capacity, the local
+        // transaction is collected as well.
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            forced_insert_overflow_margin: 1,
+            ..test_config(1)
+        });
+        insert(&mut pool, &local_tx, false, true).unwrap();
+        insert(&mut pool, &remote_txs[0], true, false).unwrap();
+        assert_eq!(pool.total_deferred(), 2);
+        insert(&mut pool, &remote_txs[2], false, false).unwrap();
+        assert!(pool.get(&local_tx.hash()).is_none());
+        assert!(pool.local_transactions.is_empty());
+    }
+
+    #[test]
+    fn test_set_range_packed() {
+        let alice = Random.generate().unwrap();
+        let (_storage_manager, account_cache) = new_test_account_cache(
+            &[(alice.address(), 0)],
+            U256::from(10_000_000_000u64),
+        );
+        let txs: Vec<_> =
+            (0..5).map(|nonce| new_test_tx(&alice, nonce, 10, 0)).collect();
+        let new_pool = || {
+            let mut pool = new_test_pool(10);
+            for tx in &txs {
+                pool.insert_transaction_with_readiness_check(
+                    &account_cache,
+                    tx.clone(),
+                    false, /* packed */
+                    false, /* force */
+                    false, /* is_local */
+                )
+                .unwrap();
+            }
+            pool
+        };
+
+        let mut per_tx_pool = new_pool();
+        for tx in &txs[..3] {
+            per_tx_pool
+                .insert_transaction_with_readiness_check(
+                    &account_cache,
+                    tx.clone(),
+                    true,  /* packed */
+                    false, /* force */
+                    false, /* is_local */
+                )
+                .unwrap();
+        }
+
+        let mut pool = new_pool();
+        assert_eq!(
+            pool.set_range_packed(&alice.address(), 0.into(), 2.into()),
+            3
+        );
+        assert_eq!(pool.total_unpacked(), 2);
+        assert_eq!(pool.total_unpacked(), per_tx_pool.total_unpacked());
+        let ready_tx_hash = |pool: &TransactionPoolInner| {
+            pool.ready_account_pool
+                .get(&alice.address())
+                .map(|tx| tx.hash())
+        };
+        assert_eq!(ready_tx_hash(&pool), Some(txs[3].hash()));
+        assert_eq!(ready_tx_hash(&pool), ready_tx_hash(&per_tx_pool));
+
+        // Only the transactions which are not packed yet are counted.
+        assert_eq!(
+            pool.set_range_packed(&alice.address(), 2.into(), 10.into()),
+            2
+        );
+        assert_eq!(pool.total_unpacked(), 0);
+        assert_eq!(ready_tx_hash(&pool), None);
+    }
+
+    #[test]
+    fn test_gc_thrashing() {
+        let senders: Vec<_> =
+            (0..5).map(|_| Random.generate().unwrap()).collect();
+        let accounts: Vec<_> =
+            senders.iter().map(|sender| (sender.address(), 0)).collect();
+        let (_storage_manager, account_cache) =
+            new_test_account_cache(&accounts, U256::from(10_000_000_000u64));
+        // Each transaction is more expensive than the previous one, so it
+        // evicts it from the full pool.
+        let txs: Vec<_> = senders
+            .iter()
+            .zip(&[10, 20, 30, 40, 50])
+            .map(|(sender, gas_price)| new_test_tx(sender, 0, *gas_price, 0))
+            .collect();
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            gc_thrashing_window: 2,
+            gc_thrashing_threshold_percent: 50,
+            ..test_config(1)
+        });
+        let mut insert = |tx: &Arc<SignedTransaction>, is_local: bool| {
+            pool.insert_transaction_with_readiness_check(
+                &account_cache,
+                tx.clone(),
+                false, /* packed */
+                false, /* force */
+                is_local,
+            )
+        };
+
+        insert(&txs[0], false).unwrap();
+        insert(&txs[1], false).unwrap();
+        // One eviction in two insertions is not over the threshold.
+        insert(&txs[2], false).unwrap();
+
+        // Every insertion in the window has evicted a transaction now.
+        insert(&txs[3], true).unwrap();
+        let err = insert(&txs[4], false).unwrap_err();
+        assert!(err.contains("thrashing"), "{}", err);
+
+        assert_eq!(pool.gc_stats().evicted, 3);
+        assert!(pool.get(&txs[3].hash()).is_some());
+        assert!(pool.get(&txs[4].hash()).is_none());
+        // The rejection is counted in the window, which ends the thrashing.
+        assert!(!pool.gc_thrashing_detector.is_thrashing());
+    }
+
+    #[test]
+    fn test_strict_balance_margin() {
+        let alice = Random.generate().unwrap();
+        let (_storage_manager, account_cache) = new_test_account_cache(
+            &[(alice.address(), 0)],
+            U256::from(1_000_000),
+        );
+        // The gas costs 5_000_000 with the gas limit of 50_000.
+        let tx = new_test_tx(&alice, 0, 100, 0);
+        let insert = |margin| {
+            let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+                strict_balance_margin: margin,
+                ..test_config(10)
+            });
+            let result = pool.insert_transaction_with_readiness_check(
+                &account_cache,
+                tx.clone(),
+                false, /* packed */
+                false, /* force */
+                false, /* is_local */
+            );
+            let status = pool
+                .get_account_pending_transactions(&alice.address(), None, None)
+                .1;
+            (result, status)
+        };
+
+        let (result, status) = insert(Some(U256::zero()));
+        let err = result.unwrap_err();
+        assert!(err.contains("exceeds the balance 1000000"), "{}", err);
+        assert_eq!(status, None);
+
+        let (result, status) = insert(Some(U256::from(4_000_000)));
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            status,
+            Some(TransactionStatus::Pending(PendingReason::NotEnoughCash))
+        );
+
+        // The lenient pool keeps the transaction pending.
+        let (result, status) = insert(None);
+        assert_eq!(result, Ok(()));
+        assert_eq!(
+            status,
+            Some(TransactionStatus::Pending(PendingReason::NotEnoughCash))
+        );
+    }
+
+    #[test]
+    fn test_min_gas_price() {
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            min_gas_price: 10,
+            ..test_config(10)
+        });
+        let senders: Vec<_> =
+            (0..3).map(|_| Random.generate().unwrap()).collect();
+        let accounts: Vec<_> =
+            senders.iter().map(|sender| (sender.address(), 0)).collect();
+        let (_storage_manager, account_cache) =
+            new_test_account_cache(&accounts, U256::from(10_000_000_000u64));
+        let mut insert = |tx: &Arc<SignedTransaction>, packed| {
+            pool.insert_transaction_with_readiness_check(
+                &account_cache,
+                tx.clone(),
+                packed,
+                false, /* force */
+                false, /* is_local */
+            )
+        };
+
+        let below_floor = new_test_tx(&senders[0], 0, 9, 0);
+        let err = insert(&below_floor, false).unwrap_err();
+        assert!(err.contains("less than the minimum value 10"), "{}", err);
+
+        let at_floor = new_test_tx(&senders[1], 0, 10, 0);
+        insert(&at_floor, false).unwrap();
+
+        // Packed transactions from blocks bypass the floor.
+        let packed = new_test_tx(&senders[2], 0, 1, 0);
+        insert(&packed, true).unwrap();
+
+        assert_eq!(pool.total_deferred(), 2);
+        assert!(pool.get(&below_floor.hash()).is_none());
+        assert!(pool.get(&at_floor.hash()).is_some());
+        assert!(pool.get(&packed.hash()).is_some());
+    }
+
+    #[test]
+    fn test_unpack() {
+        let mut pool = new_test_pool(10);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+        let alice_txs: Vec<_> =
+            (0..3).map(|nonce| new_test_tx(&alice, nonce, 10, 100)).collect();
+        let bob_tx = new_test_tx(&bob, 0, 10, 100);
+        for tx in alice_txs.iter().chain(Some(&bob_tx)) {
+            insert_test_tx(&mut pool, tx.clone(), 0, balance);
+        }
+
+        // A block packs the first two transactions of Alice and Bob's one.
+        let block_txs =
+            vec![alice_txs[0].clone(), alice_txs[1].clone(), bob_tx];
+        for tx in &block_txs {
+            pool.insert_transaction_without_readiness_check(
+                tx.clone(),
+                true, /* packed */
+                true, /* force */
+                None, /* state_nonce_and_balance */
+                (U256::zero(), 0),
+            );
+            pool.recalculate_readiness_with_local_info(&tx.sender());
+        }
+        assert_eq!(pool.total_unpacked(), 1);
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(alice_txs[2].clone())
+        );
+        assert!(pool.ready_account_pool.get(&bob.address()).is_none());
+
+        let mut hashes: Vec<_> = block_txs.iter().map(|tx| tx.hash()).collect();
+        hashes.push(alice_txs[2].hash());
+        let snapshot = pool.snapshot_packed(&hashes);
+        assert_eq!(snapshot, hashes[..3].to_vec());
+
+        // The block is orphaned.
+        assert_eq!(pool.unpack(&snapshot), 3);
+        assert_eq!(pool.unpack(&snapshot), 0);
+        assert_eq!(pool.total_unpacked(), 4);
+        assert!(pool.snapshot_packed(&hashes).is_empty());
+        assert_eq!(
+            pool.ready_account_pool.get(&alice.address()),
+            Some(alice_txs[0].clone())
+        );
+        assert_eq!(
+            pool.ready_account_pool.get(&bob.address()),
+            Some(block_txs[2].clone())
+        );
+    }
+
+    #[test]
+    fn test_get_transactions_in_price_range() {
+        let mut pool = new_test_pool(10);
+        let balance = U256::from(1_000_000_000);
+        for gas_price in &[5, 10, 15, 20, 25] {
+            let sender = Random.generate().unwrap();
+            let tx = new_test_tx(&sender, 0, *gas_price, 100);
+            insert_test_tx(&mut pool, tx, 0, balance);
+        }
+        // A transaction which is not ready is never returned.
+        let sender = Random.generate().unwrap();
+        insert_test_tx(&mut pool, new_test_tx(&sender, 1, 15, 100), 0, balance);
+
+        let gas_prices = |txs: Vec<Arc<SignedTransaction>>| {
+            txs.iter().map(|tx| tx.gas_price.as_usize()).collect::<Vec<_>>()
+        };
+        let query = |min: usize, max: usize, limit| {
+            gas_prices(pool.get_transactions_in_price_range(
+                min.into(),
+                max.into(),
+                limit,
+            ))
+        };
+        assert_eq!(query(10, 20, 10), vec![20, 15, 10]);
+        assert_eq!(query(0, 100, 10), vec![25, 20, 15, 10, 5]);
+        assert_eq!(query(0, 100, 2), vec![25, 20]);
+        assert_eq!(query(11, 14, 10), Vec::<usize>::new());
+        assert_eq!(query(10, 20, 0), Vec::<usize>::new());
+        // The ready pool is not changed.
+        assert_eq!(pool.ready_account_pool.len(), 5);
+    }
+
+    #[test]
+    fn test_ready_weights() {
+        let mut pool = TransactionPoolInner::new(&TxPoolConfig {
+            tx_weight_scaling: 3,
+            tx_weight_exp: 2,
+            ..test_config(10)
+        });
+        let balance = U256::from(10_000_000_000u64);
+        let senders: Vec<_> =
+            (0..3).map(|_| Random.generate().unwrap()).collect();
+        for (sender, gas_price) in senders.iter().zip(&[10, 20, 31]) {
+            insert_test_tx(
+                &mut pool,
+                new_test_tx(sender, 0, *gas_price, 0),
+                0,
+                balance,
+            );
+        }
+        // `(gas_price / 3) ^ 2` with integer division.
+        let expected = [9, 36, 100];
+        for (sender, weight) in senders.iter().zip(&expected) {
+            assert_eq!(pool.ready_weight_of(&sender.address()), Some(*weight));
+        }
+        assert_eq!(pool.total_ready_weight(), expected.iter().sum::<u128>());
+
+        // A sender without ready transactions has no weight.
+        let stranger = Random.generate().unwrap();
+        assert_eq!(pool.ready_weight_of(&stranger.address()), None);
+        pool.ready_account_pool.remove(&senders[2].address());
+        assert_eq!(pool.total_ready_weight(), 45);
+    }
+
+    /// Fill a pool of capacity 2 with an expensive transaction from a sender
+    /// with an older garbage collector timestamp and a cheap one from a newer
+    /// sender, then insert a transaction priced between them. Returns the pool
+    /// and the senders of the expensive and of the cheap transactions.
+    fn gc_victim_after_insertion(
+        gc_check_count: usize, gc_strategy: GcStrategy,
+    ) -> (TransactionPoolInner, Address, Address) {
+        let mut pool = new_test_pool_with_gc(2, gc_check_count, gc_strategy);
+        let old = Random.generate().unwrap();
+        let new = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+        insert_test_tx(&mut pool, new_test_tx(&old, 0, 30, 100), 0, balance);
+        insert_test_tx(&mut pool, new_test_tx(&new, 0, 10, 100), 0, balance);
+        pool.garbage_collector.insert(&old.address(), 0, 100);
+        pool.garbage_collector.insert(&new.address(), 0, 200);
+
+        let tx = new_test_tx(&Random.generate().unwrap(), 0, 20, 100);
+        insert_test_tx(&mut pool, tx, 0, balance);
+        (pool, old.address(), new.address())
+    }
+
+    #[test]
+    fn test_gc_strategy_lowest_gas_price() {
+        let (pool, old, new) = gc_victim_after_insertion(
+            DEFAULT_GC_CHECK_COUNT,
+            GcStrategy::LowestGasPrice,
+        );
+        assert_eq!(pool.total_deferred(), 2);
+        assert!(pool.deferred_pool.contain_address(&old));
+        assert!(!pool.deferred_pool.contain_address(&new));
+        assert_eq!(pool.displacement_price(), Some(20.into()));
+
+        // Only the oldest sender is checked, and it is more expensive.
+        let (pool, old, new) =
+            gc_victim_after_insertion(1, GcStrategy::LowestGasPrice);
+        assert_eq!(pool.gc_stats().no_victim, 1);
+        assert!(pool.deferred_pool.contain_address(&old));
+        assert!(pool.deferred_pool.contain_address(&new));
+        // The checked sender is put back into the garbage collector.
+        assert_eq!(pool.garbage_collector.len(), 2);
+    }
+
+    #[test]
+    fn test_gc_strategy_oldest_timestamp() {
+        let (pool, old, new) = gc_victim_after_insertion(
+            DEFAULT_GC_CHECK_COUNT,
+            GcStrategy::OldestTimestamp,
+        );
+        assert_eq!(pool.total_deferred(), 2);
+        assert!(!pool.deferred_pool.contain_address(&old));
+        assert!(pool.deferred_pool.contain_address(&new));
+        assert_eq!(pool.displacement_price(), Some(U256::zero()));
+        assert_eq!(pool.garbage_collector.len(), 2);
+    }
+
+    /// A clock which only moves when the test advances it, and counts how
+    /// many times it is read.
+    #[derive(Default)]
+    struct MockTimeSource {
+        now: AtomicU64,
+        reads: AtomicUsize,
+    }
+
+    impl MockTimeSource {
+        fn advance(&self, secs: u64) {
+            self.now.fetch_add(secs, Ordering::SeqCst);
+        }
+
+        fn reads(&self) -> usize { self.reads.load(Ordering::SeqCst) }
+    }
+
+    impl TimeSource for MockTimeSource {
+        fn current_timestamp(&self) -> u64 {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.now.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn test_gc_debug_snapshot() {
+        let mut pool = new_test_pool(100);
+        let time_source = Arc::new(MockTimeSource::default());
+        pool.set_time_source(time_source.clone());
+        let balance = U256::from(1_000_000_000);
+        let senders: Vec<_> =
+            (0..4).map(|_| Random.generate().unwrap()).collect();
+        // The i-th sender has i + 1 transactions and is tracked at 10 * i.
+        for (i, sender) in senders.iter().enumerate() {
+            for nonce in 0..=i {
+                insert_test_tx(
+                    &mut pool,
+                    new_test_tx(sender, nonce, 10, 100),
+                    0,
+                    balance,
+                );
+            }
+            time_source.advance(10);
+        }
+        // Two transactions of the last sender and one of the third sender
+        // are executed.
+        pool.set_account_state_and_recalculate(
+            &senders[3].address(),
+            2.into(),
+            balance,
+        );
+        pool.set_account_state_and_recalculate(
+            &senders[2].address(),
+            1.into(),
+            balance,
+        );
+
+        let entry = |i: usize, count| GcEntry {
+            address: senders[i].address(),
+            count,
+            timestamp: 10 * i as u64,
+        };
+        assert_eq!(
+            pool.gc_debug_snapshot(),
+            vec![entry(3, 2), entry(2, 1), entry(0, 0), entry(1, 0)]
+        );
+        // Taking the snapshot doesn't change the collector.
+        assert_eq!(pool.gc_debug_snapshot().len(), 4);
+    }
+
+    #[test]
+    fn test_gc_timestamp_from_time_source() {
+        let gc_victim = |first: &KeyPair, second: &KeyPair| {
+            let mut pool = new_test_pool_with_gc(
+                2,
+                DEFAULT_GC_CHECK_COUNT,
+                GcStrategy::OldestTimestamp,
+            );
+            let time_source = Arc::new(MockTimeSource::default());
+            pool.set_time_source(time_source.clone());
+            let balance = U256::from(1_000_000_000);
+
+            for sender in &[first, second] {
+                time_source.advance(100);
+                let tx = new_test_tx(sender, 0, 10, 100);
+                insert_test_tx(&mut pool, tx, 0, balance);
+            }
+            assert_eq!(
+                pool.garbage_collector.get_timestamp(&first.address()),
+                Some(100)
+            );
+            assert_eq!(
+                pool.garbage_collector.get_timestamp(&second.address()),
+                Some(200)
+            );
+
+            time_source.advance(100);
+            let tx = new_test_tx(&Random.generate().unwrap(), 0, 10, 100);
+            insert_test_tx(&mut pool, tx, 0, balance);
+            assert_eq!(pool.total_deferred(), 2);
+            if !pool.deferred_pool.contain_address(&first.address()) {
+                first.address()
+            } else {
+                assert!(!pool.deferred_pool.contain_address(&second.address()));
+                second.address()
+            }
+        };
+
+        // The sender inserted earlier is collected first.
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        assert_eq!(gc_victim(&alice, &bob), alice.address());
+        assert_eq!(gc_victim(&bob, &alice), bob.address());
+    }
+
+    #[test]
+    fn test_gc_stats() {
+        let mut pool = new_test_pool(2);
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let carol = Random.generate().unwrap();
+        let dave = Random.generate().unwrap();
+        let balance = U256::from(1_000_000_000);
+        insert_test_tx(&mut pool, new_test_tx(&alice, 0, 10, 100), 0, balance);
+        insert_test_tx(&mut pool, new_test_tx(&bob, 0, 20, 100), 0, balance);
+        assert_eq!(pool.gc_stats(), Default::default());
+
+        // Alice's transaction is evicted.
+        let carol_tx = new_test_tx(&carol, 0, 30, 100);
+        assert_eq!(
+            insert_test_tx(&mut pool, carol_tx, 0, balance),
+            InsertResult::NewAdded
+        );
+        assert_eq!(pool.gc_stats().evicted, 1);
+        assert_eq!(pool.gc_stats().no_victim, 0);
+
+        // No transaction is cheaper than Dave's.
+        let dave_tx = new_test_tx(&dave, 0, 5, 100);
+        assert!(matches!(
+            insert_test_tx(&mut pool, dave_tx, 0, balance),
+            InsertResult::Failed(_)
+        ));
+        assert_eq!(pool.gc_stats().evicted, 1);
+        assert_eq!(pool.gc_stats().no_victim, 1);
+
+        // Bob's own transaction is spared, so Carol's is evicted.
+        let bob_tx = new_test_tx(&bob, 1, 100, 100);
+        assert_eq!(
+            insert_test_tx(&mut pool, bob_tx, 0, balance),
+            InsertResult::NewAdded
+        );
+        let stats = pool.gc_stats();
+        assert_eq!(stats.evicted, 2);
+        assert!(stats.spared_sender >= 1);
+        assert!(!pool.deferred_pool.contain_address(&carol.address()));
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let alice = Random.generate().unwrap();
+        let bob = Random.generate().unwrap();
+        let balance = U256::from(10_000_000_000u64);
+        let account_cache_with_nonce_of_alice = |nonce: u64| {
+            new_test_account_cache(
+                &[(alice.address(), nonce), (bob.address(), 0)],
+                balance,
+            )
+        };
+
+        let mut pool = new_test_pool(10);
+        let (_storage_manager, account_cache) =
+            account_cache_with_nonce_of_alice(0);
+        let txs = vec![
+            new_test_tx(&alice, 0, 10, 0),
+            new_test_tx(&alice, 1, 10, 0),
+            new_test_tx(&bob, 0, 10, 0),
+        ];
+        for tx in &txs {
+            pool.insert_transaction_with_readiness_check(
+                &account_cache,
+                tx.clone(),
+                false, /* packed */
+                false, /* force */
+                false, /* is_local */
+            )
+            .unwrap();
+        }
+        let mut saved = Vec::new();
+        pool.save_to_writer(&mut saved).unwrap();
+
+        // The first transaction of alice is executed before the restart, so it
+        // is dropped as stale.
+        let mut new_pool = new_test_pool(10);
+        let (_storage_manager, account_cache) =
+            account_cache_with_nonce_of_alice(1);
+        assert_eq!(
+            new_pool
+                .load_from_reader(&mut saved.as_slice(), &account_cache)
+                .unwrap(),
+            2
+        );
+        let mut restored: Vec<_> = new_pool.txs.values().cloned().collect();
+        restored.sort_by_key(|tx| (tx.sender(), tx.nonce()));
+        let mut expected = vec![txs[1].clone(), txs[2].clone()];
+        expected.sort_by_key(|tx| (tx.sender(), tx.nonce()));
+        assert_eq!(restored, expected);
+        assert!(new_pool.ready_account_pool.get(&alice.address()).is_some());
+        assert!(new_pool.ready_account_pool.get(&bob.address()).is_some());
+
+        // Bytes which are not an RLP list are rejected.
+        assert!(new_pool
+            .load_from_reader(&mut &[1u8][..], &account_cache)
+            .is_err());
+    }
 }
@@ -51,6 +51,8 @@ pub struct Env {
     pub pos_view: Option<u64>,
     /// Finalized epoch number by PoS view.
     pub finalized_epoch: Option<u64>,
+    /// The pivot block hash of `finalized_epoch`.
+    pub finalized_epoch_hash: Option<H256>,
     /// The transaction_epoch_bound used to verify if a transaction has
     /// expired.
     pub transaction_epoch_bound: u64,
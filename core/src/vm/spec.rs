@@ -147,6 +147,10 @@ pub struct Spec {
     pub cip78b: bool,
     /// CIP-80: Ethereum compatible signature recover
     pub cip80: bool,
+    /// Query the PoS stake of an address through internal contract
+    pub pos_stake_query: bool,
+    /// Query the latest PoS pivot decision through internal contract
+    pub pivot_decision_query: bool,
 }
 
 /// Wasm cost table
@@ -280,6 +284,8 @@ impl Spec {
             cip78a: false,
             cip78b: false,
             cip80: false,
+            pos_stake_query: false,
+            pivot_decision_query: false,
         }
     }
 
@@ -296,6 +302,10 @@ impl Spec {
         spec.cip72 = number >= params.transition_numbers.cip72b;
         spec.cip78a = number >= params.transition_numbers.cip78a;
         spec.cip80 = number >= params.transition_numbers.cip80;
+        spec.pos_stake_query =
+            number >= params.transition_numbers.pos_stake_query;
+        spec.pivot_decision_query =
+            number >= params.transition_numbers.pivot_decision_query;
         spec
     }
 
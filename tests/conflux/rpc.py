@@ -548,6 +548,12 @@ class RpcClient:
     def pos_new_round_timeout(self):
         return self.node.pos_trigger_timeout("new_round")
 
+    def pos_force_timeout(self, round):
+        return self.node.pos_force_timeout(int_to_hex(round))
+
+    def pos_drop_vote(self, round):
+        return self.node.pos_drop_vote(int_to_hex(round))
+
     def pos_force_sign_pivot_decision(self, block_hash, height):
         return self.node.pos_force_sign_pivot_decision(block_hash, height)
 
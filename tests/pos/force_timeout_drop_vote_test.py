@@ -0,0 +1,76 @@
+#!/usr/bin/env python3
+
+# allow imports from parent directory
+# source: https://stackoverflow.com/a/11158224
+import os, sys
+sys.path.insert(1, os.path.join(sys.path[0], '..'))
+
+import time
+
+from conflux.rpc import RpcClient
+from conflux.utils import int_to_hex
+from test_framework.test_framework import DefaultConfluxTestFramework
+from test_framework.util import *
+
+
+class PosForceTimeoutDropVoteTest(DefaultConfluxTestFramework):
+    def set_test_params(self):
+        self.num_nodes = 4
+        self.conf_parameters["vrf_proposal_threshold"] = '"{}"'.format(int_to_hex(int(2 ** 256 - 1)))
+        self.conf_parameters["pos_pivot_decision_defer_epoch_count"] = '120'
+        # No auto timeout.
+        self.pos_parameters["round_time_ms"] = 1000000000
+        self.conf_parameters["pos_round_per_term"] = '10'
+
+    def current_round(self, client):
+        return max(int(b["round"], 0) for b in client.pos_get_consensus_blocks())
+
+    def certified(self, client, round):
+        return any(int(b["round"], 0) == round and len(b["signatures"]) > 0
+                   for b in client.pos_get_consensus_blocks())
+
+    def run_test(self):
+        clients = []
+        for node in self.nodes:
+            clients.append(RpcClient(node))
+
+        # Initialize pos_consensus_blocks
+        for _ in range(3):
+            for client in clients:
+                client.pos_local_timeout()
+            time.sleep(0.5)
+            for client in clients:
+                client.pos_new_round_timeout()
+            time.sleep(0.5)
+        wait_until(lambda: clients[0].pos_status() is not None)
+        wait_until(lambda: clients[0].pos_status()["latestCommitted"] is not None)
+
+        # With two of the four nodes dropping their votes, the proposal of this
+        # round cannot get a QC.
+        round = self.current_round(clients[0])
+        for client in clients[2:]:
+            client.pos_drop_vote(round)
+        for client in clients:
+            client.pos_proposal_timeout()
+        time.sleep(0.5)
+        for client in clients:
+            client.pos_new_round_timeout()
+        time.sleep(0.5)
+        clients[0].generate_empty_blocks(1)
+        sync_blocks(self.nodes)
+        assert not self.certified(clients[0], round)
+        assert_equal(self.current_round(clients[0]), round)
+
+        # Forced timeouts form a TC without waiting for the round timers, so
+        # the nodes move on to the next round.
+        for client in clients:
+            client.pos_force_timeout(round)
+        time.sleep(0.5)
+        for client in clients:
+            client.pos_new_round_timeout()
+        wait_until(lambda: self.current_round(clients[0]) > round)
+        assert not self.certified(clients[0], round)
+
+
+if __name__ == '__main__':
+    PosForceTimeoutDropVoteTest().main()